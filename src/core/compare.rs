@@ -0,0 +1,104 @@
+use crate::core::sync::{device_package_states, Phone, User};
+use crate::core::uad_lists::PackageState;
+use std::collections::HashMap;
+
+/// A system package whose state differs between the source and the target device
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackageDiff {
+    pub name: String,
+    /// `None` when the source device doesn't have the package
+    pub source: Option<PackageState>,
+    /// `None` when the target device doesn't have the package
+    pub target: Option<PackageState>,
+}
+
+impl PackageDiff {
+    /// Only packages both devices have can be brought to the state of the source
+    pub const fn is_actionable(&self) -> bool {
+        self.source.is_some() && self.target.is_some()
+    }
+}
+
+/// Packages in a different state on `target`, or only on one of the devices, sorted by name
+pub fn diff_package_states(
+    source: &HashMap<String, PackageState>,
+    target: &HashMap<String, PackageState>,
+) -> Vec<PackageDiff> {
+    let mut diffs: Vec<PackageDiff> = source
+        .iter()
+        .filter(|&(name, state)| target.get(name) != Some(state))
+        .map(|(name, &state)| PackageDiff {
+            name: name.clone(),
+            source: Some(state),
+            target: target.get(name).copied(),
+        })
+        .chain(
+            target
+                .iter()
+                .filter(|(name, _)| !source.contains_key(*name))
+                .map(|(name, &state)| PackageDiff {
+                    name: name.clone(),
+                    source: None,
+                    target: Some(state),
+                }),
+        )
+        .collect();
+    diffs.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+    diffs
+}
+
+/// Compare the system packages of user 0 of both devices
+pub async fn compare_devices(source: Phone, target: Phone) -> Vec<PackageDiff> {
+    let states = |device: &Phone| {
+        let user = (device.android_sdk >= 21).then(User::default);
+        device_package_states(device, user.as_ref())
+    };
+    diff_package_states(&states(&source), &states(&target))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_package_states() {
+        let states = |s: &[(&str, PackageState)]| {
+            s.iter()
+                .map(|&(name, state)| (name.to_string(), state))
+                .collect::<HashMap<_, _>>()
+        };
+        let source = states(&[
+            ("com.a", PackageState::Uninstalled),
+            ("com.b", PackageState::Enabled),
+            ("com.c", PackageState::Disabled),
+        ]);
+        let target = states(&[
+            ("com.a", PackageState::Enabled),
+            ("com.b", PackageState::Enabled),
+            ("com.d", PackageState::Enabled),
+        ]);
+        let diff = |name: &str, here, there| PackageDiff {
+            name: name.to_string(),
+            source: here,
+            target: there,
+        };
+        let diffs = diff_package_states(&source, &target);
+        assert_eq!(
+            diffs,
+            vec![
+                diff(
+                    "com.a",
+                    Some(PackageState::Uninstalled),
+                    Some(PackageState::Enabled)
+                ),
+                diff("com.c", Some(PackageState::Disabled), None),
+                diff("com.d", None, Some(PackageState::Enabled)),
+            ]
+        );
+        assert_eq!(
+            diffs.iter().filter(|d| d.is_actionable()).count(),
+            1,
+            "only packages on both devices can be matched"
+        );
+    }
+}
@@ -1,13 +1,15 @@
-use crate::core::utils::DisplayablePath;
+use crate::core::utils::{format_diff_time_from_now, DisplayablePath, SortBy};
 use crate::core::{
     sync::{get_android_sdk, User},
     theme::Theme,
+    uad_lists::{PackageState, Removal, UadList},
 };
 use crate::gui::views::settings::Settings;
 use crate::CACHE_DIR;
 use crate::CONFIG_DIR;
 use serde::{Deserialize, Serialize};
 use static_init::dynamic;
+use std::fmt;
 use std::fs;
 use std::path::PathBuf;
 
@@ -19,10 +21,60 @@ pub struct Config {
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[allow(clippy::struct_excessive_bools, reason = "Not a state-machine")]
 pub struct GeneralSettings {
     pub theme: String,
     pub expert_mode: bool,
     pub backup_folder: PathBuf,
+    /// Most recent search terms first
+    #[serde(default)]
+    pub search_history: Vec<String>,
+    /// Log level of the log file, applied on next launch
+    #[serde(default = "default_log_level")]
+    pub log_level: String,
+    /// Also list the curated packages that aren't on the device
+    #[serde(default)]
+    pub show_absent_packages: bool,
+    /// Seconds after which disabled packages are checked again, to catch
+    /// those re-enabled behind our back (e.g. by Google Play Services). 0 is off.
+    #[serde(default)]
+    pub disable_recheck_delay: u64,
+    /// Packages protected from removal on top of `PROTECTED_PACKAGES`
+    #[serde(default)]
+    pub protected_packages: Vec<String>,
+    /// Lift the protection of `protected_packages` and `PROTECTED_PACKAGES`
+    #[serde(default)]
+    pub allow_protected_packages: bool,
+    /// `adb` binary to use, discovered when empty
+    #[serde(default)]
+    pub adb_path: String,
+    /// URL or local path of the debloat lists, the official ones when empty
+    #[serde(default)]
+    pub list_source: String,
+    /// Offer protected users (work profile, Secure Folder) in the user pick-list
+    #[serde(default)]
+    pub show_protected_users: bool,
+    /// Maximum number of adb commands of a batch running at once
+    #[serde(default = "default_adb_concurrency")]
+    pub adb_concurrency: usize,
+    /// Start devices seen for the first time in disable mode (Android 6.0+)
+    #[serde(default)]
+    pub default_disable_mode: bool,
+    /// Packages starred on any device, to find them again on the next ones
+    #[serde(default)]
+    pub bookmarked_packages: Vec<String>,
+    /// Never access the network: the cached (or embedded) lists are used,
+    /// and updates aren't checked
+    #[serde(default)]
+    pub offline_mode: bool,
+}
+
+fn default_log_level() -> String {
+    log::LevelFilter::Debug.to_string().to_lowercase()
+}
+
+const fn default_adb_concurrency() -> usize {
+    GeneralSettings::DEFAULT_ADB_CONCURRENCY
 }
 
 #[derive(Default, Debug, Clone)]
@@ -38,18 +90,153 @@ pub struct BackupSettings {
 pub struct DeviceSettings {
     pub device_id: String,
     pub disable_mode: bool,
+    /// Select packages for all the non-protected users at once
     pub multi_user_mode: bool,
+    /// Users package actions apply to, see [`Self::user_scope`]
+    #[serde(default, rename = "user_scope")]
+    pub user_scope_choice: Option<UserScope>,
+    /// Back up the device before applying a selection, and abort if it fails
+    #[serde(default)]
+    pub auto_backup_before_apply: bool,
+    /// Last filters used in the package list for this device
+    #[serde(default)]
+    pub filters: ListFilters,
+    /// Last time packages of this device were changed
+    #[serde(default)]
+    pub last_debloated: Option<DebloatRecord>,
+    /// Packages never touched by "Select all"
+    #[serde(default)]
+    pub kept_packages: Vec<String>,
     #[serde(skip)]
     pub backup: BackupSettings,
 }
 
+/// Users a package action applies to
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum UserScope {
+    /// Only the user selected in the package list (e.g. `pm uninstall --user 0`)
+    SelectedUser,
+    /// Every non-protected user, with the action chosen for the selected user
+    AllUsers,
+}
+
+impl UserScope {
+    pub const ALL: [Self; 2] = [Self::SelectedUser, Self::AllUsers];
+}
+
+impl fmt::Display for UserScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::SelectedUser => "Only the selected user",
+                Self::AllUsers => "All non-protected users",
+            }
+        )
+    }
+}
+
+/// When a device was last acted on, and how many packages were changed then
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct DebloatRecord {
+    /// Seconds since the Unix epoch
+    pub timestamp: i64,
+    pub packages: usize,
+}
+
+impl fmt::Display for DebloatRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let date = chrono::DateTime::from_timestamp(self.timestamp, 0).unwrap_or_default();
+        write!(
+            f,
+            "{}, {} package(s)",
+            format_diff_time_from_now(date),
+            self.packages
+        )
+    }
+}
+
+impl DeviceSettings {
+    /// Record that `packages` packages were just changed, unless there are none
+    pub fn record_debloat(&mut self, packages: usize) {
+        if packages > 0 {
+            self.last_debloated = Some(DebloatRecord {
+                timestamp: chrono::Utc::now().timestamp(),
+                packages,
+            });
+        }
+    }
+
+    /// Configs predating the user scope setting kept it in `multi_user_mode`
+    pub const fn user_scope(&self) -> UserScope {
+        match self.user_scope_choice {
+            Some(scope) => scope,
+            None if self.multi_user_mode => UserScope::AllUsers,
+            None => UserScope::SelectedUser,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct ListFilters {
+    pub list: UadList,
+    pub removal: Removal,
+    pub state: PackageState,
+    pub sort_by: SortBy,
+}
+
+impl Default for ListFilters {
+    fn default() -> Self {
+        Self {
+            list: UadList::All,
+            removal: Removal::Recommended,
+            state: PackageState::Enabled,
+            sort_by: SortBy::Name,
+        }
+    }
+}
+
 impl Default for GeneralSettings {
     fn default() -> Self {
         Self {
             theme: Theme::default().to_string(),
             expert_mode: false,
             backup_folder: CACHE_DIR.join("backups"),
+            search_history: vec![],
+            log_level: default_log_level(),
+            show_absent_packages: false,
+            disable_recheck_delay: 0,
+            protected_packages: vec![],
+            allow_protected_packages: false,
+            adb_path: String::new(),
+            list_source: String::new(),
+            show_protected_users: false,
+            adb_concurrency: default_adb_concurrency(),
+            default_disable_mode: false,
+            bookmarked_packages: vec![],
+            offline_mode: false,
+        }
+    }
+}
+
+impl GeneralSettings {
+    /// Maximum number of search terms kept in `search_history`
+    pub const SEARCH_HISTORY_LEN: usize = 10;
+
+    /// Enough to keep a batch fast without overwhelming the adb server
+    pub const DEFAULT_ADB_CONCURRENCY: usize = 4;
+
+    /// Move `term` to the front of the search history,
+    /// dropping duplicates and the oldest entries.
+    pub fn push_search_history(&mut self, term: &str) {
+        let term = term.trim();
+        if term.is_empty() {
+            return;
         }
+        self.search_history.retain(|t| t != term);
+        self.search_history.insert(0, term.to_string());
+        self.search_history.truncate(Self::SEARCH_HISTORY_LEN);
     }
 }
 
@@ -58,7 +245,12 @@ impl Default for DeviceSettings {
         Self {
             device_id: String::default(),
             multi_user_mode: get_android_sdk() > 21,
+            user_scope_choice: None,
             disable_mode: false,
+            auto_backup_before_apply: false,
+            filters: ListFilters::default(),
+            last_debloated: None,
+            kept_packages: vec![],
             backup: BackupSettings::default(),
         }
     }
@@ -134,9 +326,45 @@ mod tests {
         let mut settings = Settings::default();
         let device_id = "test_device".to_string();
         settings.device.device_id = device_id.clone();
+        settings.device.filters.removal = Removal::Expert;
         Config::save_changes(&settings, &device_id);
         let config = Config::load_configuration_file();
         assert_eq!(config.devices[0].device_id, device_id);
+        assert_eq!(config.devices[0].filters.removal, Removal::Expert);
+    }
+
+    #[test]
+    fn test_user_scope() {
+        let device = |multi_user_mode, user_scope_choice| DeviceSettings {
+            device_id: String::new(),
+            disable_mode: false,
+            multi_user_mode,
+            user_scope_choice,
+            auto_backup_before_apply: false,
+            filters: ListFilters::default(),
+            last_debloated: None,
+            kept_packages: vec![],
+            backup: BackupSettings::default(),
+        };
+        assert_eq!(device(true, None).user_scope(), UserScope::AllUsers);
+        assert_eq!(device(false, None).user_scope(), UserScope::SelectedUser);
+        assert_eq!(
+            device(true, Some(UserScope::SelectedUser)).user_scope(),
+            UserScope::SelectedUser
+        );
+    }
+
+    #[test]
+    fn test_record_debloat() {
+        let mut device = DeviceSettings::default();
+        device.record_debloat(0);
+        assert_eq!(device.last_debloated, None);
+        device.record_debloat(42);
+        let record = device
+            .last_debloated
+            .expect("a debloat of 42 packages is recorded");
+        assert_eq!(record.packages, 42);
+        assert_eq!(record.to_string(), "0 min(s) ago, 42 package(s)");
     }
 
     #[test]
@@ -148,6 +376,30 @@ mod tests {
         assert_eq!(config.general.backup_folder, CACHE_DIR.join("backups"));
     }
 
+    #[test]
+    fn test_search_history() {
+        let mut general = GeneralSettings::default();
+        for i in 0..=GeneralSettings::SEARCH_HISTORY_LEN {
+            general.push_search_history(&format!("com.vendor{i}"));
+        }
+        general.push_search_history("  ");
+        general.push_search_history("com.vendor5");
+        assert_eq!(
+            general.search_history.len(),
+            GeneralSettings::SEARCH_HISTORY_LEN
+        );
+        assert_eq!(general.search_history[0], "com.vendor5");
+        assert_eq!(
+            general
+                .search_history
+                .iter()
+                .filter(|t| *t == "com.vendor5")
+                .count(),
+            1
+        );
+        assert!(!general.search_history.contains(&"com.vendor0".to_string()));
+    }
+
     #[test]
     fn test_config_file_path() {
         assert_eq!(&*CONFIG_FILE, Path::new(&*CONFIG_DIR.join("config.toml")));
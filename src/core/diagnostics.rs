@@ -0,0 +1,132 @@
+use crate::core::config::Config;
+use crate::core::sync::{adb_binary, adb_shell_command};
+use crate::core::uad_lists::{is_url, list_source};
+
+/// Result of one environment check, with a hint on how to fix it
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub name: &'static str,
+    pub passed: bool,
+    pub detail: String,
+    pub hint: Option<&'static str>,
+}
+
+impl Check {
+    fn new(name: &'static str, result: Result<String, String>, hint: &'static str) -> Self {
+        match result {
+            Ok(detail) => Self {
+                name,
+                passed: true,
+                detail,
+                hint: None,
+            },
+            Err(detail) => Self {
+                name,
+                passed: false,
+                detail,
+                hint: Some(hint),
+            },
+        }
+    }
+}
+
+/// Serial and status of each device listed by `adb devices`
+fn parse_devices(output: &str) -> Vec<(&str, &str)> {
+    output
+        .lines()
+        .skip_while(|l| !l.starts_with("List of devices"))
+        .skip(1)
+        .filter_map(|l| {
+            let mut fields = l.split_whitespace();
+            Some((fields.next()?, fields.next()?))
+        })
+        .collect()
+}
+
+fn check_devices() -> Result<String, String> {
+    let output = adb_shell_command(false, "devices")?;
+    let devices = parse_devices(&output);
+    let summary = devices
+        .iter()
+        .map(|(serial, status)| format!("{serial} ({status})"))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    if devices.iter().any(|&(_, status)| status == "device") {
+        Ok(summary)
+    } else if devices.is_empty() {
+        Err("no device found".to_string())
+    } else {
+        Err(summary)
+    }
+}
+
+fn check_lists() -> Result<String, String> {
+    let general = Config::load_configuration_file().general;
+    let source = list_source(&general.list_source);
+    if general.offline_mode && is_url(&source) {
+        return Ok("not checked, offline mode".to_string());
+    }
+    if !is_url(&source) {
+        return std::fs::metadata(&source)
+            .map(|_| format!("{source} found"))
+            .map_err(|e| format!("{source}: {e}"));
+    }
+    ureq::head(&source)
+        .call()
+        .map(|_| "reachable".to_string())
+        .map_err(|e| e.to_string())
+}
+
+/// Check everything needed to debloat a device, in the order a user would fix it.
+pub async fn run_diagnostics() -> Vec<Check> {
+    vec![
+        Check::new(
+            "ADB installed",
+            adb_shell_command(false, "version").map(|v| {
+                format!(
+                    "{} ({})",
+                    v.lines().next().unwrap_or_default(),
+                    adb_binary().display()
+                )
+            }),
+            "Install the Android platform-tools and make sure `adb` is in your PATH, \
+            or point the ADB path setting to it",
+        ),
+        Check::new(
+            "ADB server running",
+            adb_shell_command(false, "start-server").map(|_| "running".to_string()),
+            "Run `adb kill-server` then `adb start-server` in a terminal",
+        ),
+        Check::new(
+            "Device connected and authorized",
+            check_devices(),
+            "Enable USB debugging, plug the device in and accept the prompt on its screen",
+        ),
+        Check::new(
+            "Package lists reachable",
+            check_lists(),
+            "Check your internet connection or the debloat lists setting; the cached lists are used meanwhile",
+        ),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_devices() {
+        let output = "* daemon started successfully\nList of devices attached\n\
+            R58M123 device\nemulator-5554\tunauthorized\n0123 offline\n";
+        assert_eq!(
+            parse_devices(output),
+            vec![
+                ("R58M123", "device"),
+                ("emulator-5554", "unauthorized"),
+                ("0123", "offline")
+            ]
+        );
+        assert!(parse_devices("List of devices attached\n").is_empty());
+    }
+}
@@ -0,0 +1,121 @@
+//! UI string catalog.
+//!
+//! Strings are looked up by key with [`t!`]. English is built in; a translation is a flat
+//! TOML table of `"key" = "text"` in `CONFIG_DIR/locales/<lang>.toml`, where `<lang>` comes
+//! from `UAD_LANG` or else the `LANG` of the system (`fr_FR.UTF-8` -> `fr`).
+//! Keys missing from the translation fall back to English.
+
+use crate::CONFIG_DIR;
+use static_init::dynamic;
+use std::collections::HashMap;
+use std::fs;
+
+/// Built-in English strings, the fallback of every translation
+const EN: &[(&str, &str)] = &[
+    ("nav.refresh", "Refresh apps"),
+    ("nav.reboot", "Reboot"),
+    ("nav.update", "Update"),
+    ("nav.apps", "Apps"),
+    ("nav.about", "About"),
+    ("nav.finding_devices", "Finding connected devices..."),
+    ("nav.no_devices", "No devices/emulators found"),
+    (
+        "adb.not_installed",
+        "ADB is not installed on your system, install ADB and relaunch application.",
+    ),
+    ("adb.get_started", "Read on how to get started."),
+    ("settings.theme", "Theme"),
+    ("settings.general", "General"),
+    ("settings.current_device", "Current device"),
+    ("settings.backup_restore", "Backup / Restore"),
+];
+
+#[dynamic]
+static TRANSLATION: HashMap<String, String> = load_translation();
+
+/// Look up a UI string, see [`tr`]
+macro_rules! t {
+    ($key:literal) => {
+        $crate::core::i18n::tr($key)
+    };
+}
+pub(crate) use t;
+
+/// Translated string of `key`, its English default if untranslated, or `key` itself if unknown
+pub fn tr(key: &'static str) -> &'static str {
+    lookup(&TRANSLATION, key)
+}
+
+fn lookup(translation: &'static HashMap<String, String>, key: &'static str) -> &'static str {
+    translation
+        .get(key)
+        .map(String::as_str)
+        .or_else(|| EN.iter().find(|(k, _)| *k == key).map(|(_, v)| *v))
+        .unwrap_or_else(|| {
+            warn!("Missing UI string: {key}");
+            key
+        })
+}
+
+/// Language code of the user: `UAD_LANG`, else `LANG`
+fn language() -> Option<String> {
+    std::env::var("UAD_LANG")
+        .or_else(|_| std::env::var("LANG"))
+        .ok()
+        .and_then(|lang| {
+            lang.split(['_', '.', '-'])
+                .next()
+                .map(str::to_lowercase)
+                .filter(|l| !l.is_empty() && l != "c" && l != "en")
+        })
+}
+
+fn load_translation() -> HashMap<String, String> {
+    let Some(lang) = language() else {
+        return HashMap::new();
+    };
+    let path = CONFIG_DIR.join("locales").join(format!("{lang}.toml"));
+    fs::read_to_string(&path).map_or_else(
+        |_| HashMap::new(),
+        |text| {
+            parse_translation(&text).unwrap_or_else(|e| {
+                error!("Invalid translation {}: {e}", path.display());
+                HashMap::new()
+            })
+        },
+    )
+}
+
+/// Parse a flat TOML table of strings, other values are ignored
+fn parse_translation(text: &str) -> Result<HashMap<String, String>, toml::de::Error> {
+    let table: toml::Table = toml::from_str(text)?;
+    Ok(table
+        .into_iter()
+        .filter_map(|(key, value)| match value {
+            toml::Value::String(s) => Some((key, s)),
+            _ => None,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_translation_fallback() {
+        let translation: &'static HashMap<String, String> = Box::leak(Box::new(
+            parse_translation("\"nav.apps\" = \"Applis\"\n\"nav.about\" = 1\n")
+                .expect("valid translation"),
+        ));
+        assert_eq!(lookup(translation, "nav.apps"), "Applis");
+        assert_eq!(
+            lookup(translation, "nav.about"),
+            "About",
+            "non-string values fall back to English"
+        );
+        assert_eq!(lookup(translation, "nav.reboot"), "Reboot");
+        assert_eq!(lookup(translation, "no.such.key"), "no.such.key");
+        assert!(parse_translation("not toml").is_err());
+    }
+}
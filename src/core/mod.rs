@@ -1,5 +1,8 @@
+pub mod compare;
 pub mod config;
+pub mod diagnostics;
 pub mod helpers;
+pub mod i18n;
 pub mod save;
 pub mod sync;
 pub mod theme;
@@ -1,10 +1,14 @@
 use crate::core::config::{Config, DeviceSettings};
-use crate::core::sync::{apply_pkg_state_commands, CorePackage, Phone, User};
+use crate::core::sync::{
+    apply_pkg_state_commands, normalize_package_name, CorePackage, Phone, User,
+};
+use crate::core::uad_lists::PackageState;
 use crate::core::utils::DisplayablePath;
 use crate::gui::widgets::package_row::PackageRow;
 use crate::CACHE_DIR;
 use serde::{Deserialize, Serialize};
 use static_init::dynamic;
+use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 
@@ -13,34 +17,114 @@ pub static BACKUP_DIR: PathBuf = CACHE_DIR.join("backups");
 
 #[derive(Default, Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
 pub struct PhoneBackup {
+    /// `None` for backups made before the header was added
+    #[serde(default)]
+    pub header: Option<BackupHeader>,
     pub device_id: String,
     pub users: Vec<UserBackup>,
 }
 
+impl PhoneBackup {
+    /// State of each package of the backup, per user id
+    pub fn package_states(&self) -> HashMap<u16, HashMap<String, PackageState>> {
+        self.users
+            .iter()
+            .map(|u| {
+                let states = u.packages.iter().map(|p| (p.name.clone(), p.state));
+                (u.id, states.collect())
+            })
+            .collect()
+    }
+}
+
+/// The device a backup was made on
+#[derive(Default, Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct BackupHeader {
+    pub schema_version: u32,
+    pub brand: String,
+    pub model: String,
+    pub android_sdk: u8,
+    /// RFC 3339
+    pub created_at: String,
+}
+
+impl BackupHeader {
+    /// Bumped when the format of the backup files changes
+    pub const SCHEMA_VERSION: u32 = 1;
+
+    fn new(phone: &Phone) -> Self {
+        let (brand, model) = brand_and_model(phone);
+        Self {
+            schema_version: Self::SCHEMA_VERSION,
+            brand: brand.to_string(),
+            model: model.to_string(),
+            android_sdk: phone.android_sdk,
+            created_at: chrono::Local::now().to_rfc3339(),
+        }
+    }
+}
+
+/// Parse a backup file. Backups of a newer format than [`BackupHeader::SCHEMA_VERSION`]
+/// are refused: this version can't tell what they mean.
+fn parse_backup(data: &str) -> Result<PhoneBackup, String> {
+    let backup: PhoneBackup =
+        serde_json::from_str(data).map_err(|e| format!("Invalid backup file: {e}"))?;
+    match &backup.header {
+        Some(header) if header.schema_version > BackupHeader::SCHEMA_VERSION => Err(format!(
+            "This backup was made by a newer version of uad-ng (format v{}, this one reads v{}): \
+            update uad-ng to restore it.",
+            header.schema_version,
+            BackupHeader::SCHEMA_VERSION
+        )),
+        _ => Ok(backup),
+    }
+}
+
+/// [`Phone::model`] starts with `ro.product.brand`
+fn brand_and_model(phone: &Phone) -> (&str, &str) {
+    phone.model.split_once(' ').unwrap_or(("", &phone.model))
+}
+
+/// Warning when `backup` was made on another device model than `phone`.
+/// Headerless backups can't be checked.
+pub fn backup_device_mismatch(backup: &PhoneBackup, phone: &Phone) -> Option<String> {
+    let header = backup.header.as_ref()?;
+    let (brand, model) = brand_and_model(phone);
+    (header.brand != brand || header.model != model).then(|| {
+        format!(
+            "This backup was made on a {} {} (Android SDK {}), not on this {}: \
+            packages that only exist on one of them can't be restored.",
+            header.brand, header.model, header.android_sdk, phone.model
+        )
+    })
+}
+
 #[derive(Default, Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
 pub struct UserBackup {
     pub id: u16,
     pub packages: Vec<CorePackage>,
 }
 
-/// Backup all `Uninstalled` and `Disabled` packages
+/// Backup all `Uninstalled` and `Disabled` packages.
+/// `pre_apply` marks the automatic backups made before applying a selection.
 pub async fn backup_phone(
-    users: Vec<User>,
-    device_id: String,
+    phone: Phone,
     phone_packages: Vec<Vec<PackageRow>>,
+    pre_apply: bool,
 ) -> Result<bool, String> {
     let mut backup = PhoneBackup {
-        device_id: device_id.clone(),
+        header: Some(BackupHeader::new(&phone)),
+        device_id: phone.adb_id.clone(),
         ..PhoneBackup::default()
     };
 
-    for u in users {
+    for u in phone.user_list {
         let mut user_backup = UserBackup {
             id: u.id,
             ..UserBackup::default()
         };
 
-        for p in phone_packages[u.index].clone() {
+        for p in phone_packages[u.index].iter().filter(|p| p.on_device) {
             user_backup.packages.push(CorePackage {
                 name: p.name.clone(),
                 state: p.state,
@@ -52,15 +136,18 @@ pub async fn backup_phone(
     match serde_json::to_string_pretty(&backup) {
         Ok(json) => {
             let backup_dir: PathBuf = Config::load_configuration_file().general.backup_folder;
-            let backup_path = &*backup_dir.join(device_id);
+            let backup_path = &*backup_dir.join(phone.adb_id);
 
             if let Err(e) = fs::create_dir_all(backup_path) {
                 error!("BACKUP: could not create backup dir: {}", e);
                 return Err(e.to_string());
             };
 
-            let backup_filename =
-                format!("{}.json", chrono::Local::now().format("%Y-%m-%d_%H-%M-%S"));
+            let backup_filename = format!(
+                "{}{}.json",
+                chrono::Local::now().format("%Y-%m-%d_%H-%M-%S"),
+                if pre_apply { "_pre-apply" } else { "" }
+            );
 
             match fs::write(backup_path.join(backup_filename), json) {
                 Ok(()) => Ok(true),
@@ -82,11 +169,26 @@ pub fn list_available_backups(dir: &Path) -> Vec<DisplayablePath> {
     }
 }
 
-pub fn list_available_backup_user(backup: DisplayablePath) -> Vec<User> {
+/// The most recent backup of `dir`: backup files are named after their date,
+/// so it's the last one in name order
+pub fn latest_backup(dir: &Path) -> Option<PhoneBackup> {
+    let path = list_available_backups(dir)
+        .into_iter()
+        .map(|b| b.path)
+        .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+        .max()?;
+    let backup = fs::read_to_string(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|data| parse_backup(&data));
+    backup
+        .map_err(|e| error!("[BACKUP] Can't read {}: {e}", path.display()))
+        .ok()
+}
+
+pub fn list_available_backup_user(backup: DisplayablePath) -> Result<Vec<User>, String> {
     match fs::read_to_string(backup.path) {
         Ok(data) => {
-            let phone_backup: PhoneBackup =
-                serde_json::from_str(&data).expect("Unable to parse backup file");
+            let phone_backup = parse_backup(&data)?;
 
             let mut users = vec![];
             for u in phone_backup.users {
@@ -96,26 +198,36 @@ pub fn list_available_backup_user(backup: DisplayablePath) -> Vec<User> {
                     protected: false,
                 });
             }
-            users
+            Ok(users)
         }
         Err(e) => {
             error!("[BACKUP]: Selected backup file not found: {}", e);
-            vec![]
+            Ok(vec![])
         }
     }
 }
 
+/// Commands restoring one package, which is `packages[i_user][index]`
 #[derive(Debug)]
 pub struct BackupPackage {
+    pub i_user: usize,
     pub index: usize,
     pub commands: Vec<String>,
 }
 
+/// What [`restore_backup`] is about to do
+#[derive(Debug)]
+pub struct RestorePlan {
+    pub packages: Vec<BackupPackage>,
+    /// See [`backup_device_mismatch`]. The restore goes on anyway.
+    pub warning: Option<String>,
+}
+
 pub fn restore_backup(
     selected_device: &Phone,
     packages: &[Vec<PackageRow>],
     settings: &DeviceSettings,
-) -> Result<Vec<BackupPackage>, String> {
+) -> Result<RestorePlan, String> {
     match fs::read_to_string(
         settings
             .backup
@@ -126,8 +238,11 @@ pub fn restore_backup(
             .clone(),
     ) {
         Ok(data) => {
-            let phone_backup: PhoneBackup =
-                serde_json::from_str(&data).expect("Unable to parse backup file");
+            let phone_backup = parse_backup(&data)?;
+            let warning = backup_device_mismatch(&phone_backup, selected_device);
+            if let Some(warning) = &warning {
+                warn!("[RESTORE] {warning}");
+            }
 
             let mut commands = vec![];
             for u in phone_backup.users {
@@ -136,19 +251,19 @@ pub fn restore_backup(
                     None => return Err(format!("user {} doesn't exist", u.id)),
                 };
 
-                for (i, backup_package) in u.packages.iter().enumerate() {
+                for backup_package in &u.packages {
+                    let name = normalize_package_name(&backup_package.name)?;
                     let package: CorePackage;
+                    let i_package;
                     match packages[index]
                         .iter()
-                        .find(|x| x.name == backup_package.name)
+                        .position(|x| x.on_device && x.name == name)
                     {
-                        Some(p) => package = p.into(),
-                        None => {
-                            return Err(format!(
-                                "{} not found for user {}",
-                                backup_package.name, u.id
-                            ))
+                        Some(i) => {
+                            i_package = i;
+                            package = (&packages[index][i]).into();
                         }
+                        None => return Err(format!("{name} not found for user {}", u.id)),
                     }
                     let p_commands = apply_pkg_state_commands(
                         &package,
@@ -158,23 +273,101 @@ pub fn restore_backup(
                             .selected_user
                             .ok_or("field should be Some type")?,
                         selected_device,
-                    );
+                    )?;
                     if !p_commands.is_empty() {
                         commands.push(BackupPackage {
-                            index: i,
+                            i_user: index,
+                            index: i_package,
                             commands: p_commands,
                         });
                     }
                 }
             }
-            if !commands.is_empty() {
-                commands.push(BackupPackage {
-                    index: 0,
-                    commands: vec![],
-                });
-            }
-            Ok(commands)
+            Ok(RestorePlan {
+                packages: commands,
+                warning,
+            })
         }
         Err(e) => Err(e.to_string()),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backup_header() {
+        let phone = |model: &str| Phone {
+            model: model.to_string(),
+            android_sdk: 34,
+            ..Phone::default()
+        };
+        let pixel = phone("google Pixel 8");
+        let backup = PhoneBackup {
+            header: Some(BackupHeader::new(&pixel)),
+            device_id: "R58M123".to_string(),
+            users: vec![],
+        };
+        assert_eq!(
+            backup.header.as_ref().map(|h| h.model.as_str()),
+            Some("Pixel 8")
+        );
+        assert_eq!(backup_device_mismatch(&backup, &pixel), None);
+        assert!(backup_device_mismatch(&backup, &phone("samsung SM-G991B")).is_some());
+
+        // Backups made before the header was added
+        let headerless: PhoneBackup =
+            serde_json::from_str(r#"{"device_id": "R58M123", "users": []}"#)
+                .expect("headerless backups are still valid");
+        assert_eq!(headerless.header, None);
+        assert_eq!(backup_device_mismatch(&headerless, &pixel), None);
+    }
+
+    #[test]
+    fn test_parse_backup() {
+        assert!(parse_backup(r#"{"device_id": "R58M123", "users": []}"#).is_ok());
+        assert!(parse_backup(r#"{"device_id": "R58M123""#).is_err());
+
+        let newer = PhoneBackup {
+            header: Some(BackupHeader {
+                schema_version: BackupHeader::SCHEMA_VERSION + 1,
+                ..BackupHeader::default()
+            }),
+            ..PhoneBackup::default()
+        };
+        let json = serde_json::to_string(&newer).expect("serialize backup");
+        assert!(parse_backup(&json).is_err(), "newer formats are refused");
+    }
+
+    #[test]
+    fn test_latest_backup() {
+        let dir = std::env::temp_dir().join(format!("uad_backups_test_{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("create test dir");
+        assert_eq!(latest_backup(&dir), None);
+
+        let backup = |state| PhoneBackup {
+            users: vec![UserBackup {
+                id: 0,
+                packages: vec![CorePackage {
+                    name: "com.foo".to_string(),
+                    state,
+                }],
+            }],
+            ..PhoneBackup::default()
+        };
+        for (file, state) in [
+            ("2024-01-02_10-00-00.json", PackageState::Disabled),
+            ("2024-01-01_10-00-00.json", PackageState::Enabled),
+        ] {
+            let json = serde_json::to_string(&backup(state)).expect("serialize backup");
+            fs::write(dir.join(file), json).expect("write test backup");
+        }
+        let latest = latest_backup(&dir).expect("a backup");
+        assert_eq!(
+            latest.package_states()[&0]["com.foo"],
+            PackageState::Disabled
+        );
+        fs::remove_dir_all(&dir).expect("remove test dir");
+    }
+}
@@ -2,19 +2,28 @@ use crate::core::uad_lists::PackageState;
 use crate::core::utils::ANDROID_SERIAL;
 use crate::gui::views::list::PackageInfo;
 use crate::gui::widgets::package_row::PackageRow;
+use iced::futures::channel::mpsc;
+use iced::futures::stream::{self, Stream, StreamExt};
 use regex::Regex;
 use retry::{delay::Fixed, retry, OperationResult};
 use serde::{Deserialize, Serialize};
 use static_init::dynamic;
-use std::collections::HashSet;
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::env;
-use std::process::Command;
+use std::fs;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdout, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{PoisonError, RwLock};
+use std::thread;
 
 #[cfg(target_os = "windows")]
 use std::os::windows::process::CommandExt;
 
 #[dynamic]
-static RE: Regex = Regex::new(r"\n(\S+)\s+device").unwrap();
+static RE: Regex = Regex::new(r"\n(\S+)\s+(device|unauthorized)").unwrap();
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Phone {
@@ -22,6 +31,11 @@ pub struct Phone {
     pub android_sdk: u8,
     pub user_list: Vec<User>,
     pub adb_id: String,
+    /// `su` is available and grants root (e.g. Magisk).
+    /// `None` until probed once its packages are loaded, see [`has_root_access`].
+    pub has_root: Option<bool>,
+    /// The USB debugging prompt hasn't been accepted on the device yet
+    pub unauthorized: bool,
 }
 
 impl Default for Phone {
@@ -31,13 +45,21 @@ impl Default for Phone {
             android_sdk: 0,
             user_list: vec![],
             adb_id: String::default(),
+            has_root: None,
+            unauthorized: false,
         }
     }
 }
 
 impl std::fmt::Display for Phone {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.model)
+        if self.unauthorized {
+            write!(f, "{} (unauthorized)", self.adb_id)
+        } else if self.has_root == Some(true) {
+            write!(f, "{} (root)", self.model)
+        } else {
+            write!(f, "{}", self.model)
+        }
     }
 }
 
@@ -54,15 +76,106 @@ impl std::fmt::Display for User {
     }
 }
 
-pub fn adb_shell_command(shell: bool, args: &str) -> Result<String, String> {
-    let adb_command = if shell {
-        vec!["shell", args]
+/// Error returned by [`adb_shell_command`] when the `adb` binary couldn't be run
+pub const ADB_NOT_FOUND: &str = "ADB was not found";
+
+/// Environment variable overriding the `adb` binary, like `--adb-path`
+pub const ADB_PATH: &str = "ADB_PATH";
+
+#[dynamic(lazy)]
+static DISCOVERED_ADB: PathBuf = discover_adb();
+
+/// `adb` binary picked by `--adb-path`, [`ADB_PATH`] or the `adb_path` setting
+static ADB_PATH_OVERRIDE: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+/// Whether `--adb-path` or [`ADB_PATH`] picked the `adb` binary for this session
+static ADB_PATH_FROM_LAUNCH: AtomicBool = AtomicBool::new(false);
+
+/// The `adb` binary to run: the one set with [`set_adb_path`], else `adb` from the PATH,
+/// else the first one found in the usual platform-tools install locations.
+pub fn adb_binary() -> PathBuf {
+    ADB_PATH_OVERRIDE
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+        .unwrap_or_else(|| DISCOVERED_ADB.clone())
+}
+
+/// Use the `adb` binary at `path` for this session, or go back to discovery when it's empty.
+/// `from_launch` is for `--adb-path` and [`ADB_PATH`], which the setting can't override.
+pub fn set_adb_path(path: &str, from_launch: bool) {
+    let path = path.trim();
+    *ADB_PATH_OVERRIDE
+        .write()
+        .unwrap_or_else(PoisonError::into_inner) = (!path.is_empty()).then(|| PathBuf::from(path));
+    ADB_PATH_FROM_LAUNCH.store(from_launch, Ordering::Relaxed);
+}
+
+/// Whether `--adb-path` or [`ADB_PATH`] picked the `adb` binary, see [`set_adb_path`]
+pub fn adb_path_from_launch() -> bool {
+    ADB_PATH_FROM_LAUNCH.load(Ordering::Relaxed)
+}
+
+fn discover_adb() -> PathBuf {
+    let in_path = PathBuf::from("adb");
+    let mut command = Command::new(&in_path);
+    command.arg("version");
+
+    #[cfg(target_os = "windows")]
+    let command = command.creation_flags(0x08000000); // do not open a cmd window
+
+    if command.output().is_ok() {
+        return in_path;
+    }
+    adb_candidates()
+        .into_iter()
+        .find(|path| path.is_file())
+        .unwrap_or(in_path)
+}
+
+/// Where platform-tools usually end up: Android SDKs (Android Studio) and package managers
+fn adb_candidates() -> Vec<PathBuf> {
+    let exe = if cfg!(target_os = "windows") {
+        "adb.exe"
     } else {
-        vec![args]
+        "adb"
     };
+    let sdks = [env::var_os("ANDROID_HOME"), env::var_os("ANDROID_SDK_ROOT")]
+        .into_iter()
+        .flatten()
+        .map(PathBuf::from)
+        .chain(dirs::data_local_dir().map(|d| d.join("Android").join("Sdk"))) // Windows
+        .chain(dirs::home_dir().map(|h| h.join("Library").join("Android").join("sdk"))) // macOS
+        .chain(dirs::home_dir().map(|h| h.join("Android").join("Sdk"))); // Linux
+    sdks.map(|sdk| sdk.join("platform-tools").join(exe))
+        .chain(dirs::home_dir().map(|h| h.join("platform-tools").join(exe)))
+        .chain(
+            [
+                "C:\\platform-tools\\adb.exe",
+                "/opt/homebrew/bin/adb",
+                "/usr/local/bin/adb",
+            ]
+            .map(PathBuf::from),
+        )
+        .collect()
+}
+
+pub fn adb_shell_command(shell: bool, args: &str) -> Result<String, String> {
+    if shell {
+        run_adb(&["shell", args])
+    } else {
+        run_adb(&[args])
+    }
+}
+
+/// Run a shell command on the device `serial` rather than the selected one
+pub fn adb_shell_command_on(serial: &str, args: &str) -> Result<String, String> {
+    run_adb(&["-s", serial, "shell", args])
+}
 
-    let mut command = Command::new("adb");
-    command.args(adb_command);
+fn run_adb(args: &[&str]) -> Result<String, String> {
+    let mut command = Command::new(adb_binary());
+    command.args(args);
 
     #[cfg(target_os = "windows")]
     let command = command.creation_flags(0x08000000); // do not open a cmd window
@@ -70,23 +183,14 @@ pub fn adb_shell_command(shell: bool, args: &str) -> Result<String, String> {
     match command.output() {
         Err(e) => {
             error!("ADB: {}", e);
-            Err("ADB was not found".to_string())
+            Err(ADB_NOT_FOUND.to_string())
         }
         Ok(o) => {
             if o.status.success() {
-                Ok(String::from_utf8(o.stdout)
-                    .map_err(|e| e.to_string())?
-                    .trim_end()
-                    .to_string())
+                Ok(to_trimmed_utf8(&o.stdout))
             } else {
-                let stdout = String::from_utf8(o.stdout)
-                    .map_err(|e| e.to_string())?
-                    .trim_end()
-                    .to_string();
-                let stderr = String::from_utf8(o.stderr)
-                    .map_err(|e| e.to_string())?
-                    .trim_end()
-                    .to_string();
+                let stdout = to_trimmed_utf8(&o.stdout);
+                let stderr = to_trimmed_utf8(&o.stderr);
 
                 // ADB does really weird things. Some errors are not redirected to stderr
                 let err = if stdout.is_empty() { stderr } else { stdout };
@@ -96,6 +200,28 @@ pub fn adb_shell_command(shell: bool, args: &str) -> Result<String, String> {
     }
 }
 
+/// Some OEM ROMs output text in odd locales: invalid UTF-8 is replaced
+/// rather than failing the whole command
+fn to_trimmed_utf8(output: &[u8]) -> String {
+    let text = String::from_utf8_lossy(output);
+    if let Cow::Owned(_) = text {
+        warn!("ADB output isn't valid UTF-8, some characters were replaced");
+    }
+    text.trim_end().to_string()
+}
+
+/// Run a command typed in the GUI ADB console.
+/// It's always run through `adb shell`: raw `adb` subcommands can't be used.
+pub fn adb_console_command(command: &str) -> Result<String, String> {
+    info!("[ADB console] adb shell {command}");
+    let output = adb_shell_command(true, command);
+    match &output {
+        Ok(o) => debug!("[ADB console] -> {o}"),
+        Err(e) => warn!("[ADB console] -> {e}"),
+    }
+    output
+}
+
 #[derive(Debug, Clone)]
 pub enum CommandType {
     PackageManager(PackageInfo),
@@ -103,9 +229,125 @@ pub enum CommandType {
 }
 
 /// An enum to contain different variants for errors yielded by ADB.
+/// Each variant holds the message to show and log.
 #[derive(Debug, Clone)]
 pub enum AdbError {
-    Generic(String),
+    /// The device refused to uninstall the package (`DELETE_FAILED_USER_RESTRICTED`).
+    /// It can still be removed with [`root_uninstall`] on rooted devices.
+    Restricted(PackageInfo, String),
+    /// The package isn't installed for the user (`[not installed for 10]`)
+    NotInstalledForUser(String),
+    /// `SecurityException`: the shell user lacks a permission
+    PermissionDenied(String),
+    /// A device admin (work profile, MDM) protects the package
+    DevicePolicy(String),
+    /// The package doesn't exist on the device
+    NotFound(String),
+    /// A newer version of the APK being installed is already on the device
+    Downgrade(String),
+    /// The APK being installed isn't signed with the key of the installed package
+    SignatureMismatch(String),
+    /// The device didn't answer in time (`adb: ... timed out`, slow or flaky connection)
+    Timeout(String),
+    /// The device went away while the command ran, see [`is_connection_lost`]
+    Disconnected(String),
+    Other(String),
+}
+
+impl AdbError {
+    /// Classify the `output` of a failed command
+    fn new(command_type: CommandType, output: &str, message: String) -> Self {
+        // `Unknown package: com.foo`, `Package com.foo not found`, `package com.foo does not exist`
+        #[dynamic]
+        static PACKAGE_NOT_FOUND: Regex =
+            Regex::new(r"unknown package|package \S+ (?:not found|does not exist)")
+                .expect("valid regex");
+
+        let lowercase = output.to_lowercase();
+        let has = |patterns: &[&str]| patterns.iter().any(|p| lowercase.contains(p));
+        match command_type {
+            // Checked first: `error: device '…' not found` isn't about the package
+            _ if is_connection_lost(output.trim()) => Self::Disconnected(message),
+            CommandType::PackageManager(p) if has(&["delete_failed_user_restricted"]) => {
+                Self::Restricted(p, message)
+            }
+            _ if has(&["[not installed for"]) => Self::NotInstalledForUser(message),
+            _ if has(&["device_policy_manager", "owner_blocked", "device policy"]) => {
+                Self::DevicePolicy(message)
+            }
+            _ if has(&[
+                "securityexception",
+                "permission denial",
+                "permission denied",
+            ]) =>
+            {
+                Self::PermissionDenied(message)
+            }
+            _ if has(&["install_failed_version_downgrade"]) => Self::Downgrade(message),
+            _ if has(&[
+                "install_failed_update_incompatible",
+                "install_failed_shared_user_incompatible",
+                "signatures do not match",
+            ]) =>
+            {
+                Self::SignatureMismatch(message)
+            }
+            _ if has(&["timed out", "timeout expired"]) => Self::Timeout(message),
+            _ if PACKAGE_NOT_FOUND.is_match(&lowercase) => Self::NotFound(message),
+            _ => Self::Other(message),
+        }
+    }
+
+    /// What the user can do about the error
+    pub const fn hint(&self) -> Option<&'static str> {
+        match self {
+            Self::Restricted(..) => Some(
+                "The device refuses to uninstall this package: \
+                disable it instead, or remove it with root.",
+            ),
+            Self::NotInstalledForUser(_) | Self::NotFound(_) => {
+                Some("The package list is out of date: refresh it.")
+            }
+            Self::PermissionDenied(_) => Some(
+                "ADB isn't allowed to do this. On Xiaomi devices, enable \
+                \"USB debugging (Security settings)\" in the developer options.",
+            ),
+            Self::DevicePolicy(_) => Some(
+                "A device administrator (work profile or company management app) \
+                protects this package.",
+            ),
+            Self::Downgrade(_) => Some(
+                "The device has a newer version of this app: \
+                uninstall the update first, or save the APK again.",
+            ),
+            Self::SignatureMismatch(_) => Some(
+                "This APK wasn't signed by the author of the installed app: \
+                it can't replace it. Was it saved from another device or ROM?",
+            ),
+            Self::Timeout(_) => {
+                Some("The device didn't answer in time: check its connection and try again.")
+            }
+            Self::Disconnected(_) => Some("The device got disconnected: reconnect it and retry."),
+            Self::Other(_) => None,
+        }
+    }
+}
+
+impl std::fmt::Display for AdbError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Restricted(_, message)
+            | Self::NotInstalledForUser(message)
+            | Self::PermissionDenied(message)
+            | Self::DevicePolicy(message)
+            | Self::NotFound(message)
+            | Self::Downgrade(message)
+            | Self::SignatureMismatch(message)
+            | Self::Timeout(message)
+            | Self::Disconnected(message)
+            | Self::Other(message) => write!(f, "{message}"),
+        }
+    }
 }
 
 pub async fn perform_adb_commands(
@@ -117,7 +359,13 @@ pub async fn perform_adb_commands(
         CommandType::Shell => "Shell",
     };
 
-    match adb_shell_command(true, &action) {
+    let output = match adb_shell_command(true, &action) {
+        Err(err) if is_connection_lost(&err) && reconnect_wireless() => {
+            adb_shell_command(true, &action)
+        }
+        output => output,
+    };
+    match output {
         Ok(o) => {
             // On old devices, adb commands can return the `0` exit code even if there
             // is an error. On Android 4.4, ADB doesn't check if the package exists.
@@ -125,7 +373,8 @@ pub async fn perform_adb_commands(
             // Some commands are even killed by ADB before finishing and UAD-ng can't catch
             // the output.
             if ["Error", "Failure"].iter().any(|&e| o.contains(e)) {
-                return Err(AdbError::Generic(format!("[{label}] {action} -> {o}")));
+                let message = format!("[{label}] {action} -> {o}");
+                return Err(AdbError::new(command_type, &o, message));
             }
 
             info!("[{label}] {action} -> {o}");
@@ -133,9 +382,84 @@ pub async fn perform_adb_commands(
         }
         Err(err) => {
             if !err.contains("[not installed for") {
-                return Err(AdbError::Generic(format!("[{label}] {action} -> {err}")));
+                let message = format!("[{label}] {action} -> {err}");
+                return Err(AdbError::new(command_type, &err, message));
             }
-            Err(AdbError::Generic(err))
+            Err(AdbError::NotInstalledForUser(err))
+        }
+    }
+}
+
+/// The error of a command run on a device which went away (e.g. a Wi-Fi drop)
+fn is_connection_lost(err: &str) -> bool {
+    let err = err.to_lowercase();
+    [
+        "error: device offline",
+        "error: no devices",
+        "error: device not found",
+        "error: closed",
+    ]
+    .iter()
+    .any(|p| err.starts_with(p))
+        || (err.starts_with("error: device '") && err.ends_with("not found"))
+}
+
+/// `host:port` of a device connected over Wi-Fi with `adb connect`
+fn wireless_address(serial: &str) -> Option<&str> {
+    serial
+        .rsplit_once(':')
+        .filter(|(host, port)| !host.is_empty() && port.parse::<u16>().is_ok())
+        .map(|_| serial)
+}
+
+/// `adb connect` the selected device again if it's a wireless one.
+/// Returns whether it's connected back.
+fn reconnect_wireless() -> bool {
+    let Some(serial) = env::var(ANDROID_SERIAL).ok() else {
+        return false;
+    };
+    let Some(address) = wireless_address(&serial) else {
+        return false;
+    };
+    warn!("[ADB] Connection to {address} lost, reconnecting");
+    match run_adb(&["connect", address]) {
+        // `adb connect` exits with 0 even when it fails
+        Ok(o) if o.contains("connected to") => {
+            info!("[ADB] {o}");
+            true
+        }
+        Ok(o) | Err(o) => {
+            error!("[ADB] Can't reconnect to {address}: {o}");
+            false
+        }
+    }
+}
+
+/// Mode the device reboots into
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RebootTarget {
+    System,
+    Recovery,
+    Bootloader,
+}
+
+impl RebootTarget {
+    /// Run with `adb shell`
+    pub const fn command(self) -> &'static str {
+        match self {
+            Self::System => "reboot",
+            Self::Recovery => "reboot recovery",
+            Self::Bootloader => "reboot bootloader",
+        }
+    }
+}
+
+impl std::fmt::Display for RebootTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::System => write!(f, "system"),
+            Self::Recovery => write!(f, "recovery"),
+            Self::Bootloader => write!(f, "bootloader"),
         }
     }
 }
@@ -170,6 +494,594 @@ pub fn hashset_system_packages(state: PackageState, user_id: Option<&User>) -> H
         .collect()
 }
 
+/// Packages the device can't do without right now: the default launcher
+/// and the default keyboard, if they can be found.
+pub fn active_system_packages() -> Vec<String> {
+    let launcher = adb_shell_command(
+        true,
+        "cmd package resolve-activity --brief -a android.intent.action.MAIN -c android.intent.category.HOME",
+    )
+    .ok()
+    .and_then(|o| o.lines().last().map(String::from));
+    let keyboard = adb_shell_command(true, "settings get secure default_input_method").ok();
+
+    // Both are given as `package/component`
+    [launcher, keyboard]
+        .into_iter()
+        .flatten()
+        .filter_map(|c| c.split_once('/').map(|(package, _)| package.to_string()))
+        .collect()
+}
+
+/// Query the device `serial` to know if `package` is disabled for `user_id`.
+pub fn is_package_disabled(serial: &str, package: &str, user_id: Option<&User>) -> bool {
+    let action = format!("pm list packages -s -d{}", user_flag(user_id));
+    adb_shell_command_on(serial, &action).is_ok_and(|list| {
+        list.lines()
+            .any(|l| l.strip_prefix("package:") == Some(package))
+    })
+}
+
+/// Query the device for the state of every system package of `user_id`.
+fn system_package_states(user_id: Option<&User>) -> HashMap<String, PackageState> {
+    query_package_states(|args| adb_shell_command(true, args), user_id)
+}
+
+/// Query `device` for the state of every system package of `user_id`,
+/// whether it's the selected device or not.
+pub fn device_package_states(
+    device: &Phone,
+    user_id: Option<&User>,
+) -> HashMap<String, PackageState> {
+    query_package_states(|args| adb_shell_command_on(&device.adb_id, args), user_id)
+}
+
+/// `adb_shell` runs `pm` on the device to query
+fn query_package_states(
+    adb_shell: impl Fn(&str) -> Result<String, String>,
+    user_id: Option<&User>,
+) -> HashMap<String, PackageState> {
+    let user = user_flag(user_id);
+    let list = |flags: &str| -> Vec<String> {
+        adb_shell(&format!("pm list packages -s {flags}{user}"))
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|l| l.strip_prefix("package:"))
+            .map(String::from)
+            .collect()
+    };
+    let enabled: HashSet<String> = list("-e").into_iter().collect();
+    let disabled: HashSet<String> = list("-d").into_iter().collect();
+
+    list("-u")
+        .into_iter()
+        .map(|p| {
+            let state = if enabled.contains(&p) {
+                PackageState::Enabled
+            } else if disabled.contains(&p) {
+                PackageState::Disabled
+            } else {
+                PackageState::Uninstalled
+            };
+            (p, state)
+        })
+        .collect()
+}
+
+/// Query the device for every system package of `user_id`
+/// that is currently disabled or uninstalled.
+pub fn list_restorable_packages(user_id: Option<&User>) -> Vec<CorePackage> {
+    system_package_states(user_id)
+        .into_iter()
+        .filter(|(_, state)| *state != PackageState::Enabled)
+        .map(|(name, state)| CorePackage { name, state })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ApplyOptions {
+    pub user: User,
+    /// Query the device again afterwards and report packages
+    /// that didn't end up in the wanted state as failed
+    pub verify: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct ActionOutcome {
+    pub package: String,
+    pub result: Result<(), String>,
+}
+
+/// Bring every package of `profile` to its wanted state, one after the other.
+/// The current state of each package is read from the device first,
+/// so packages that are already in the wanted state are left alone.
+/// Returns the outcome of each package, in the same order as `profile`.
+pub fn apply_profile(
+    device: &Phone,
+    profile: &[(String, PackageState)],
+    opts: ApplyOptions,
+) -> Vec<ActionOutcome> {
+    let user_id = (device.android_sdk >= 21).then_some(&opts.user);
+    let states = device_package_states(device, user_id);
+
+    let mut outcomes: Vec<ActionOutcome> = profile
+        .iter()
+        .map(|(name, wanted)| {
+            let result = match states.get(name) {
+                None => Err("not installed on the device".to_string()),
+                Some(state) if state == wanted => Ok(()),
+                Some(&state) => {
+                    let package = CorePackage {
+                        name: name.clone(),
+                        state,
+                    };
+                    match apply_pkg_state_commands(&package, *wanted, &opts.user, device) {
+                        Err(e) => Err(e),
+                        Ok(commands) if commands.is_empty() => {
+                            Err(format!("can't go from {state} to {wanted} on this device"))
+                        }
+                        Ok(commands) => commands.iter().try_for_each(|command| {
+                            adb_shell_command_on(&device.adb_id, command).map(|output| {
+                                info!("[APPLY] {command} -> {output}");
+                            })
+                        }),
+                    }
+                }
+            };
+            ActionOutcome {
+                package: name.clone(),
+                result,
+            }
+        })
+        .collect();
+
+    if opts.verify {
+        let new_states = device_package_states(device, user_id);
+        for (outcome, (_, wanted)) in outcomes.iter_mut().zip(profile) {
+            match new_states.get(&outcome.package) {
+                Some(state) if outcome.result.is_ok() && state != wanted => {
+                    outcome.result = Err(format!("still {state}"));
+                }
+                _ => {}
+            }
+        }
+    }
+    for outcome in &outcomes {
+        if let Err(e) = &outcome.result {
+            error!("[APPLY] {}: {e}", outcome.package);
+        }
+    }
+    outcomes
+}
+
+/// Enable all `packages` for `user`, one after the other, see [`apply_profile`].
+/// Returns the outcome of each package, in the same order.
+pub async fn restore_all_packages(
+    packages: Vec<CorePackage>,
+    user: User,
+    phone: Phone,
+) -> Vec<(String, Result<(), String>)> {
+    let profile: Vec<_> = packages
+        .into_iter()
+        .map(|p| (p.name, PackageState::Enabled))
+        .collect();
+    let opts = ApplyOptions { user, verify: true };
+    apply_profile(&phone, &profile, opts)
+        .into_iter()
+        .map(|o| (o.package, o.result))
+        .collect()
+}
+
+/// List the APK files backing `package` by querying `pm path`.
+/// Split packages report one line per part (`base.apk`, `split_config.*.apk`, ...).
+pub fn list_apk_paths(package: &str, user_id: Option<&User>) -> Vec<String> {
+    let Ok(package) = normalize_package_name(package) else {
+        return vec![];
+    };
+    let action = format!("pm path{} {package}", user_flag(user_id));
+
+    adb_shell_command(true, &action)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|l| l.strip_prefix("package:"))
+        .map(String::from)
+        .collect()
+}
+
+/// Names of the split parts among `apk_paths` (`split_config.en.apk` is `config.en`).
+/// Empty for packages made of a single APK.
+fn split_names(apk_paths: &[String]) -> Vec<String> {
+    apk_paths
+        .iter()
+        .filter_map(|path| {
+            let file = path.rsplit('/').next().unwrap_or(path);
+            file.strip_prefix("split_")?.strip_suffix(".apk")
+        })
+        .map(String::from)
+        .collect()
+}
+
+/// Split-aware retry of a failed `pm uninstall`: the split parts of `package` are
+/// uninstalled on their own first, then the base package.
+/// Empty when `package` isn't split.
+pub fn split_uninstall_commands(package: &str, user_id: Option<&User>) -> Vec<String> {
+    let Ok(package) = normalize_package_name(package) else {
+        return vec![];
+    };
+    let splits = split_names(&list_apk_paths(package, user_id));
+    if splits.is_empty() {
+        return vec![];
+    }
+    let user = user_flag(user_id);
+    vec![
+        format!("pm uninstall{user} {package} {}", splits.join(" ")),
+        format!("pm uninstall{user} {package}"),
+    ]
+}
+
+/// Stub system apps (e.g. `ChromeStub.apk`, `GoogleCamera-Stub.apk`) are placeholders
+/// that download the real app on first run: `install-existing` only brings back the stub.
+pub fn is_stub_apk(apk_paths: &[String]) -> bool {
+    apk_paths.iter().any(|path| {
+        let file = path.rsplit('/').next().unwrap_or(path).to_lowercase();
+        SYSTEM_PARTITIONS.iter().any(|p| path.starts_with(p)) && file.ends_with("stub.apk")
+    })
+}
+
+/// Pull the APK files of `package` into `dest/<package>`, so it can be sideloaded back
+/// after a removal `install-existing` can't undo (e.g. an updated or third-party app).
+/// Split packages get every part pulled.
+pub fn pull_apks(
+    package: &str,
+    user_id: Option<&User>,
+    dest: &Path,
+) -> Result<Vec<PathBuf>, String> {
+    let package = normalize_package_name(package)?;
+    let apk_paths = list_apk_paths(package, user_id);
+    if apk_paths.is_empty() {
+        return Err(format!("No APK of {package} found on the device"));
+    }
+    let dir = dest.join(package);
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    apk_paths
+        .iter()
+        .map(|path| {
+            let file = dir.join(path.rsplit('/').next().unwrap_or(path));
+            run_adb(&["pull", path, &file.to_string_lossy()])?;
+            info!("[APK] {path} -> {}", file.display());
+            Ok(file)
+        })
+        .collect()
+}
+
+/// `adb install` arguments for `apks`: split APKs go through `install-multiple`
+fn install_args(apks: &[PathBuf], user_id: Option<&User>) -> Vec<String> {
+    let command = if apks.len() > 1 {
+        "install-multiple"
+    } else {
+        "install"
+    };
+    [command, "-r"]
+        .into_iter()
+        .map(String::from)
+        .chain(
+            user_id
+                .into_iter()
+                .flat_map(|u| ["--user".to_string(), u.id.to_string()]),
+        )
+        .chain(apks.iter().map(|apk| apk.to_string_lossy().into_owned()))
+        .collect()
+}
+
+/// Install the APK files of `dir`, e.g. saved by [`pull_apks`], back onto the device.
+/// This is for packages `install-existing` can't restore, because their APK is gone.
+pub fn install_apks(dir: &Path, user_id: Option<&User>) -> Result<String, AdbError> {
+    let mut apks: Vec<PathBuf> = fs::read_dir(dir)
+        .map_err(|e| AdbError::Other(format!("Can't read {}: {e}", dir.display())))?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "apk"))
+        .collect();
+    if apks.is_empty() {
+        return Err(AdbError::Other(format!(
+            "No APK found in {}",
+            dir.display()
+        )));
+    }
+    apks.sort();
+    let args = install_args(&apks, user_id);
+    let action = args.join(" ");
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    match run_adb(&args) {
+        // Like `pm`, old devices exit with 0 on failure
+        Ok(o) if !o.contains("Failure") => {
+            info!("[APK] adb {action} -> {o}");
+            Ok(o)
+        }
+        Ok(err) | Err(err) => Err(AdbError::new(
+            CommandType::Shell,
+            &err,
+            format!("[APK] adb {action} -> {err}"),
+        )),
+    }
+}
+
+/// Bytes uninstalling each of `packages` would free, as measured by `du` on its APK folder.
+///
+/// Only APKs installed in `/data` (updates of system apps, user apps) count:
+/// an uninstalled system package stays on its read-only partition.
+/// App data isn't counted either. Packages that can't be measured are left out.
+pub fn reclaimable_apk_sizes(packages: &[String], user_id: Option<&User>) -> HashMap<String, u64> {
+    let Ok(listing) =
+        adb_shell_command(true, &format!("pm list packages -f{}", user_flag(user_id)))
+    else {
+        return HashMap::new();
+    };
+    let base_apks = parse_package_paths(&listing);
+    let mut dirs: HashMap<String, &String> = HashMap::new();
+    let mut sizes = HashMap::new();
+    for package in packages {
+        match base_apks.get(package.as_str()) {
+            Some(path) if path.starts_with("/data/") => {
+                if let Some((dir, _)) = path.rsplit_once('/') {
+                    dirs.insert(dir.to_string(), package);
+                }
+            }
+            Some(_) => {
+                sizes.insert(package.clone(), 0);
+            }
+            None => {}
+        }
+    }
+    if dirs.is_empty() {
+        return sizes;
+    }
+    let quoted: Vec<String> = dirs.keys().map(|d| format!("'{d}'")).collect();
+    // `du` exits with an error as soon as one folder isn't readable, but still measures the others
+    let output = adb_shell_command(true, &format!("du -sk {} 2>/dev/null", quoted.join(" ")))
+        .unwrap_or_else(|output| output);
+    for (dir, kib) in parse_du(&output) {
+        if let Some(&package) = dirs.get(dir) {
+            sizes.insert(package.clone(), kib * 1024);
+        }
+    }
+    sizes
+}
+
+/// `package -> base APK path` from the `package:<path>=<package>` lines of `pm list packages -f`
+fn parse_package_paths(listing: &str) -> HashMap<&str, &str> {
+    listing
+        .lines()
+        .filter_map(|l| l.trim().strip_prefix("package:"))
+        .filter_map(|l| l.rsplit_once('='))
+        .map(|(path, package)| (package, path))
+        .collect()
+}
+
+/// `(folder, KiB)` from the `<KiB>\t<folder>` lines of `du -sk`
+fn parse_du(output: &str) -> impl Iterator<Item = (&str, u64)> {
+    output.lines().filter_map(|l| {
+        let (kib, dir) = l.split_once(char::is_whitespace)?;
+        Some((dir.trim(), kib.parse().ok()?))
+    })
+}
+
+/// PID of the running process of `package`, if any (`pidof` requires Android 7.0+)
+pub fn package_pid(package: &str) -> Option<u32> {
+    let package = normalize_package_name(package).ok()?;
+    adb_shell_command(true, &format!("pidof {package}"))
+        .ok()?
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()
+}
+
+/// Stream the `adb logcat` lines of the process `pid`.
+pub fn logcat_stream(pid: u32) -> impl Stream<Item = String> {
+    adb_stream(
+        vec!["logcat".into(), "--pid".into(), pid.to_string()],
+        |stdout, sender| {
+            for line in BufReader::new(stdout).split(b'\n').map_while(Result::ok) {
+                if sender.unbounded_send(to_trimmed_utf8(&line)).is_err() {
+                    break; // nobody is listening anymore
+                }
+            }
+        },
+    )
+}
+
+/// Stream the list of devices (`<serial>\t<status>` lines) each time a device is
+/// connected, disconnected or changes status, from `adb track-devices`.
+/// The first item is the list at the time the stream is polled.
+pub fn device_list_changes() -> impl Stream<Item = String> {
+    adb_stream(vec!["track-devices".into()], |mut stdout, sender| {
+        // Each list is prefixed by its length, as 4 hex digits
+        let mut len = [0; 4];
+        while stdout.read_exact(&mut len).is_ok() {
+            let Some(len) = std::str::from_utf8(&len)
+                .ok()
+                .and_then(|l| usize::from_str_radix(l, 16).ok())
+            else {
+                break;
+            };
+            let mut devices = vec![0; len];
+            if stdout.read_exact(&mut devices).is_err()
+                || sender
+                    .unbounded_send(String::from_utf8_lossy(&devices).into_owned())
+                    .is_err()
+            {
+                break;
+            }
+        }
+    })
+}
+
+/// Kills the `adb` process it holds when dropped
+struct KillOnDrop(Child);
+
+impl Drop for KillOnDrop {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+/// Run `adb <args>` once the stream is polled, and stream what `read` gets out of
+/// its output in a thread. `adb` is killed as soon as the stream is dropped, which
+/// ends `read`.
+fn adb_stream(
+    args: Vec<String>,
+    read: fn(ChildStdout, &mpsc::UnboundedSender<String>),
+) -> impl Stream<Item = String> {
+    let (sender, receiver) = mpsc::unbounded();
+    stream::once(async move {
+        let mut command = Command::new(adb_binary());
+        command
+            .args(&args)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null());
+
+        #[cfg(target_os = "windows")]
+        let command = command.creation_flags(0x08000000); // do not open a cmd window
+
+        let child = match command.spawn() {
+            Ok(mut child) => {
+                if let Some(stdout) = child.stdout.take() {
+                    thread::spawn(move || read(stdout, &sender));
+                }
+                Some(KillOnDrop(child))
+            }
+            Err(e) => {
+                error!("Can't run adb {}: {e}", args.join(" "));
+                None
+            }
+        };
+        // The stream owns the child
+        receiver.map(move |line| {
+            let _ = &child;
+            line
+        })
+    })
+    .flatten()
+}
+
+/// Check for root access by running `id` through `su`.
+/// Root managers may ask for confirmation on the device.
+pub fn has_root_access() -> bool {
+    adb_shell_command(true, "su -c id").is_ok_and(|id| id.contains("uid=0"))
+}
+
+/// Read-only facts about the selected device, shown in the settings
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeviceInfo {
+    /// `(total, free)` bytes of the `/data` partition
+    pub storage: Option<(u64, u64)>,
+    /// Percentage
+    pub battery: Option<u8>,
+    /// e.g. `2024-05-01`, empty when unknown
+    pub security_patch: String,
+}
+
+/// `(total, free)` bytes of the partition of `df -k` output
+fn parse_df(output: &str) -> Option<(u64, u64)> {
+    let fields: Vec<&str> = output.lines().last()?.split_whitespace().collect();
+    // Filesystem 1K-blocks Used Available Use% Mounted on
+    let total: u64 = fields.get(1)?.parse().ok()?;
+    let free: u64 = fields.get(3)?.parse().ok()?;
+    Some((total * 1024, free * 1024))
+}
+
+/// Battery level of `dumpsys battery` output
+fn parse_battery_level(output: &str) -> Option<u8> {
+    output
+        .lines()
+        .find_map(|l| l.trim().strip_prefix("level:"))
+        .and_then(|level| level.trim().parse().ok())
+}
+
+/// Storage, battery and security patch of the selected device.
+/// Each one is left empty if the device doesn't report it.
+pub async fn get_device_info() -> DeviceInfo {
+    DeviceInfo {
+        storage: adb_shell_command(true, "df -k /data")
+            .ok()
+            .as_deref()
+            .and_then(parse_df),
+        battery: adb_shell_command(true, "dumpsys battery")
+            .ok()
+            .as_deref()
+            .and_then(parse_battery_level),
+        security_patch: adb_shell_command(true, "getprop ro.build.version.security_patch")
+            .unwrap_or_default(),
+    }
+}
+
+/// Partitions [`root_uninstall`] is allowed to delete APKs from
+const SYSTEM_PARTITIONS: [&str; 4] = ["/system/", "/system_ext/", "/product/", "/vendor/"];
+
+/// Remove a system package by deleting its APK directory from the partition it's on,
+/// remounted read-write. This is a last resort for packages the device refuses
+/// to uninstall, only to be run after an explicit confirmation.
+/// The package is gone after a reboot, and can only be restored by reflashing.
+pub fn root_uninstall(package: &str, user_id: Option<&User>) -> Result<(), String> {
+    let package = normalize_package_name(package)?;
+    let mut apk_dirs: Vec<String> = list_apk_paths(package, user_id)
+        .iter()
+        .filter(|path| SYSTEM_PARTITIONS.iter().any(|p| path.starts_with(p)))
+        .filter_map(|path| path.rsplit_once('/').map(|(dir, _)| dir.to_string()))
+        // Never delete a whole partition or app folder (e.g. `/system/app`)
+        .filter(|dir| dir.matches('/').count() >= 3)
+        .collect();
+    apk_dirs.dedup();
+
+    if apk_dirs.is_empty() {
+        return Err(format!("No APK of {package} found on a system partition"));
+    }
+
+    let mut partitions: Vec<&str> = apk_dirs
+        .iter()
+        .filter_map(|dir| partition_of(dir))
+        .collect();
+    partitions.sort_unstable();
+    partitions.dedup();
+
+    for partition in &partitions {
+        adb_shell_command(true, &remount_command(partition, "rw"))?;
+    }
+    for dir in &apk_dirs {
+        let action = format!("su -c \"rm -rf '{dir}'\"");
+        let output = adb_shell_command(true, &action)?;
+        info!("[Root] {action} -> {output}");
+    }
+    // Best-effort: the partitions are remounted read-only on reboot anyway
+    for partition in &partitions {
+        let _ = adb_shell_command(true, &remount_command(partition, "ro"));
+    }
+    Ok(())
+}
+
+/// Mount point of the [`SYSTEM_PARTITIONS`] `dir` is on (e.g. `/product`)
+fn partition_of(dir: &str) -> Option<&str> {
+    SYSTEM_PARTITIONS
+        .iter()
+        .find(|p| dir.starts_with(*p))
+        .map(|p| p.trim_end_matches('/'))
+}
+
+/// Remount `partition` as `mode` (`rw` or `ro`) through `su`.
+/// The partition may not be a mount point of its own (e.g. `/product` inside `/system`)
+/// and `/system` is `/` on system-as-root devices: the parents are tried next.
+fn remount_command(partition: &str, mode: &str) -> String {
+    let mut targets = vec![partition, "/system", "/"];
+    targets.dedup();
+    let mounts: Vec<String> = targets
+        .iter()
+        .map(|target| format!("mount -o {mode},remount {target}"))
+        .collect();
+    format!("su -c '{}'", mounts.join(" || "))
+}
+
 // Minimum information for processing adb commands
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
 pub struct CorePackage {
@@ -203,12 +1115,39 @@ impl From<&PackageRow> for CorePackage {
     }
 }
 
+/// Android package name: at least two `.`-separated segments,
+/// each made of ASCII letters, digits and `_` and starting with a letter.
+/// `android` (the framework) is the only exception.
+pub fn is_valid_package_name(name: &str) -> bool {
+    let valid_segment = |s: &str| {
+        s.starts_with(|c: char| c.is_ascii_alphabetic())
+            && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    };
+    name == "android" || (name.contains('.') && name.split('.').all(valid_segment))
+}
+
+/// Trim `name` and strip the `package:` prefix of `pm list packages`,
+/// then check it with [`is_valid_package_name`].
+pub fn normalize_package_name(name: &str) -> Result<&str, String> {
+    let trimmed = name.trim();
+    let normalized = trimmed.strip_prefix("package:").unwrap_or(trimmed);
+    if is_valid_package_name(normalized) {
+        Ok(normalized)
+    } else {
+        Err(format!("Invalid package name: `{name}`"))
+    }
+}
+
+/// Commands bringing `package` to `wanted_state`.
+/// A malformed package name is rejected before any command is built,
+/// as it ends up in a shell command line.
 pub fn apply_pkg_state_commands(
     package: &CorePackage,
     wanted_state: PackageState,
     selected_user: &User,
     phone: &Phone,
-) -> Vec<String> {
+) -> Result<Vec<String>, String> {
+    let name = normalize_package_name(&package.name)?;
     // https://github.com/Universal-Debloater-Alliance/universal-android-debloater/wiki/ADB-reference
     // ALWAYS PUT THE COMMAND THAT CHANGES THE PACKAGE STATE FIRST!
     let commands = match wanted_state {
@@ -219,7 +1158,9 @@ pub fn apply_pkg_state_commands(
                     _ => vec!["pm enable"],
                 },
                 PackageState::Uninstalled => match phone.android_sdk {
-                    i if i >= 23 => vec!["cmd package install-existing"],
+                    // `cmd` only exists since Android Nougat (7.0)
+                    i if i >= 24 => vec!["cmd package install-existing"],
+                    23 => vec!["pm install-existing"],
                     21 | 22 => vec!["pm unhide"],
                     19 | 20 => vec!["pm unblock", "pm clear"],
                     _ => vec![], // Impossible action already prevented by the GUI
@@ -246,7 +1187,150 @@ pub fn apply_pkg_state_commands(
         PackageState::All => vec![],
     };
     let user = (phone.android_sdk >= 21).then_some(selected_user);
-    request_builder(&commands, &package.name, user)
+    Ok(request_builder(&commands, name, user))
+}
+
+/// `pm suspend` or `pm unsuspend` for `package`: a reversible alternative to disabling,
+/// the app stays installed but can't run. Requires Android 7.0+.
+pub fn suspend_pkg_commands(
+    package: &str,
+    suspend: bool,
+    selected_user: &User,
+    phone: &Phone,
+) -> Result<Vec<String>, String> {
+    let name = normalize_package_name(package)?;
+    if phone.android_sdk < 24 {
+        return Err("Suspending apps requires Android 7.0 or later".to_string());
+    }
+    let command = if suspend {
+        "pm suspend"
+    } else {
+        "pm unsuspend"
+    };
+    Ok(request_builder(&[command], name, Some(selected_user)))
+}
+
+/// `pm clear` for `package`: wipe its data and cache, leaving it installed and in the same state
+pub fn clear_data_commands(
+    package: &str,
+    selected_user: &User,
+    phone: &Phone,
+) -> Result<Vec<String>, String> {
+    let name = normalize_package_name(package)?;
+    let user = (phone.android_sdk >= 21).then_some(selected_user);
+    Ok(request_builder(&["pm clear"], name, user))
+}
+
+/// Whether `user_id` has the package suspended, from the per-user lines of `dumpsys package`
+/// (`User 0: ceDataInode=... installed=true hidden=false suspended=true ...`).
+fn parse_suspended(dumpsys: &str, user_id: u16) -> bool {
+    let prefix = format!("User {user_id}:");
+    dumpsys
+        .lines()
+        .map(str::trim_start)
+        .filter(|l| l.starts_with(&prefix))
+        .any(|l| l.split_whitespace().any(|field| field == "suspended=true"))
+}
+
+/// Query the device to know if `package` is suspended for `user`.
+pub fn is_package_suspended(package: &str, user: &User) -> bool {
+    let Ok(package) = normalize_package_name(package) else {
+        return false;
+    };
+    adb_shell_command(true, &format!("dumpsys package {package}"))
+        .is_ok_and(|dumpsys| parse_suspended(&dumpsys, user.id))
+}
+
+/// An activity, service, receiver or provider of a package
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackageComponent {
+    /// `package/class`, as taken by `pm enable` and `pm disable`
+    pub name: String,
+    pub enabled: bool,
+}
+
+/// `pm enable` or `pm disable` for a single `package/class` component,
+/// to neuter part of a package (e.g. a tracking service) instead of the whole of it.
+pub fn component_state_commands(
+    component: &str,
+    enable: bool,
+    selected_user: &User,
+    phone: &Phone,
+) -> Result<Vec<String>, String> {
+    let invalid = || format!("Invalid component name: `{component}`");
+    let (package, class) = component.trim().split_once('/').ok_or_else(invalid)?;
+    let package = normalize_package_name(package).map_err(|_| invalid())?;
+    if !is_class_name(class) {
+        return Err(invalid());
+    }
+    let command = if enable { "pm enable" } else { "pm disable" };
+    let user = (phone.android_sdk >= 21).then_some(selected_user);
+    // Nested classes contain a `$`: the component is quoted for the shell
+    Ok(request_builder(
+        &[command],
+        &format!("'{package}/{class}'"),
+        user,
+    ))
+}
+
+/// Java class name, either fully qualified or relative to the package (`.MainActivity`)
+fn is_class_name(class: &str) -> bool {
+    !class.is_empty()
+        && class
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '$' | '.'))
+}
+
+/// Components of `package` listed by `dumpsys package`, sorted by name.
+///
+/// Only components with an intent filter appear in the resolver tables:
+/// the others are only listed once disabled for `user_id` (`disabledComponents:`).
+fn parse_components(dumpsys: &str, package: &str, user_id: u16) -> Vec<PackageComponent> {
+    let full_class = |class: &str| match class.strip_prefix('.') {
+        Some(_) => format!("{package}{class}"),
+        None => class.to_string(),
+    };
+    let prefix = format!("{package}/");
+    let user_prefix = format!("User {user_id}:");
+    let mut classes = BTreeSet::new();
+    let mut disabled = HashSet::new();
+    let mut in_user = false;
+    let mut section = "";
+    for line in dumpsys.lines().map(str::trim) {
+        if line.starts_with("User ") && line.contains(':') {
+            in_user = line.starts_with(&user_prefix);
+            section = "";
+        } else if line.ends_with(':') {
+            section = line;
+        } else if in_user && section == "disabledComponents:" && is_class_name(line) {
+            disabled.insert(full_class(line));
+            classes.insert(full_class(line));
+        }
+        classes.extend(
+            line.split_whitespace()
+                .filter_map(|token| token.strip_prefix(&prefix))
+                .map(|class| class.trim_end_matches([':', '}']))
+                .filter(|class| is_class_name(class))
+                .map(full_class),
+        );
+    }
+    classes
+        .into_iter()
+        .map(|class| PackageComponent {
+            name: format!("{package}/{class}"),
+            enabled: !disabled.contains(&class),
+        })
+        .collect()
+}
+
+/// Query the device for the components of `package` and whether `user` has them enabled.
+pub fn package_components(package: &str, user: &User) -> Vec<PackageComponent> {
+    let Ok(package) = normalize_package_name(package) else {
+        return vec![];
+    };
+    adb_shell_command(true, &format!("dumpsys package {package}"))
+        .map(|dumpsys| parse_components(&dumpsys, package, user.id))
+        .unwrap_or_default()
 }
 
 /// Build a command request to be sent via ADB to a device.
@@ -312,40 +1396,626 @@ pub fn get_user_list() -> Vec<User> {
         .unwrap_or_default()
 }
 
-// getprop ro.serialno
-pub async fn get_devices_list() -> Vec<Phone> {
-    retry(
-        Fixed::from_millis(500).take(120),
-        || match adb_shell_command(false, "devices") {
-            Ok(devices) => {
-                let mut device_list: Vec<Phone> = vec![];
-                if !RE.is_match(&devices) {
-                    return OperationResult::Retry(vec![]);
-                }
-                for device in RE.captures_iter(&devices) {
-                    env::set_var(ANDROID_SERIAL, &device[1]);
+fn scan_devices() -> OperationResult<Vec<Phone>, Vec<Phone>> {
+    match adb_shell_command(false, "devices") {
+        Ok(devices) => {
+            let mut device_list: Vec<Phone> = vec![];
+            if !RE.is_match(&devices) {
+                return OperationResult::Retry(vec![]);
+            }
+            for device in RE.captures_iter(&devices) {
+                // Nothing can be queried until the USB debugging prompt is accepted
+                if &device[2] == "unauthorized" {
                     device_list.push(Phone {
-                        model: get_phone_brand(),
-                        android_sdk: get_android_sdk(),
-                        user_list: get_user_list(),
+                        model: "Unauthorized device".to_string(),
                         adb_id: device[1].to_string(),
+                        unauthorized: true,
+                        ..Phone::default()
                     });
+                    continue;
                 }
-                OperationResult::Ok(device_list)
+                env::set_var(ANDROID_SERIAL, &device[1]);
+                device_list.push(Phone {
+                    model: get_phone_brand(),
+                    android_sdk: get_android_sdk(),
+                    user_list: get_user_list(),
+                    adb_id: device[1].to_string(),
+                    has_root: None,
+                    unauthorized: false,
+                });
             }
-            Err(err) => {
-                error!("get_device_list() -> {}", err);
-                let test: Vec<Phone> = vec![];
-                OperationResult::Retry(test)
+            OperationResult::Ok(device_list)
+        }
+        Err(err) => {
+            error!("get_device_list() -> {}", err);
+            let test: Vec<Phone> = vec![];
+            OperationResult::Retry(test)
+        }
+    }
+}
+
+/// Devices connected right now, without waiting for one to show up like [`get_devices_list`]
+pub fn connected_devices() -> Vec<Phone> {
+    match scan_devices() {
+        OperationResult::Ok(devices)
+        | OperationResult::Retry(devices)
+        | OperationResult::Err(devices) => devices,
+    }
+}
+
+/// Device to select among `devices`: the one with the `requested` serial,
+/// or else the first authorized one.
+/// A requested serial that isn't connected is an error,
+/// so that another device is never acted upon by mistake.
+pub fn get_target_device<'a>(
+    devices: &'a [Phone],
+    requested: Option<&str>,
+) -> Result<Option<&'a Phone>, String> {
+    match requested {
+        Some(serial) => devices
+            .iter()
+            .find(|phone| phone.adb_id == serial)
+            .map(Some)
+            .ok_or_else(|| {
+                let connected = devices
+                    .iter()
+                    .map(|phone| phone.adb_id.as_str())
+                    .collect::<Vec<_>>();
+                if connected.is_empty() {
+                    format!("Device {serial} is not connected, and no other device is")
+                } else {
+                    format!(
+                        "Device {serial} is not connected. Connected devices: {}",
+                        connected.join(", ")
+                    )
+                }
+            }),
+        None => Ok(devices
+            .iter()
+            .find(|phone| !phone.unauthorized)
+            .or_else(|| devices.first())),
+    }
+}
+
+// getprop ro.serialno
+pub async fn get_devices_list() -> Vec<Phone> {
+    retry(Fixed::from_millis(500).take(120), scan_devices).unwrap_or_default()
+}
+
+/// Keep polling while every connected device is unauthorized,
+/// so they show up as soon as the USB debugging prompt is accepted.
+pub async fn get_authorized_devices_list() -> Vec<Phone> {
+    retry(Fixed::from_millis(1000).take(300), || {
+        match scan_devices() {
+            OperationResult::Ok(devices) if devices.iter().all(|d| d.unauthorized) => {
+                OperationResult::Retry(devices)
             }
+            res => res,
+        }
+    })
+    .unwrap_or_else(|e| e.error)
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub enum AdbStatus {
+    /// Not checked yet
+    #[default]
+    Unknown,
+    /// `adb` couldn't be run: it's most likely not in the PATH
+    NotFound,
+    /// `adb` runs but can't list devices (stale install, server failing to start, ...)
+    Broken {
+        version: Option<String>,
+        error: String,
+    },
+    NoDevices {
+        version: String,
+    },
+    DevicesPresent {
+        version: String,
+    },
+}
+
+impl AdbStatus {
+    pub fn version(&self) -> Option<&str> {
+        match self {
+            Self::Broken { version, .. } => version.as_deref(),
+            Self::NoDevices { version } | Self::DevicesPresent { version } => Some(version),
+            Self::Unknown | Self::NotFound => None,
+        }
+    }
+}
+
+/// `1.0.41` from the first line of `adb version`
+/// (`Android Debug Bridge version 1.0.41`).
+fn parse_adb_version(output: &str) -> Option<String> {
+    output
+        .lines()
+        .next()?
+        .split_whitespace()
+        .last()
+        .map(String::from)
+}
+
+pub async fn check_adb() -> AdbStatus {
+    info!("Using adb: {}", adb_binary().display());
+    let version = match adb_shell_command(false, "version") {
+        Err(e) if e == ADB_NOT_FOUND => return AdbStatus::NotFound,
+        Err(e) => {
+            return AdbStatus::Broken {
+                version: None,
+                error: e,
+            }
+        }
+        Ok(output) => parse_adb_version(&output),
+    };
+    match (adb_shell_command(false, "devices"), version) {
+        (Err(error), version) => AdbStatus::Broken { version, error },
+        (Ok(devices), Some(version)) if RE.is_match(&devices) => {
+            AdbStatus::DevicesPresent { version }
+        }
+        (Ok(_), Some(version)) => AdbStatus::NoDevices { version },
+        (Ok(_), None) => AdbStatus::Broken {
+            version: None,
+            error: "unexpected `adb version` output".to_string(),
         },
-    )
-    .unwrap_or_default()
+    }
 }
 
-pub async fn initial_load() -> bool {
-    match adb_shell_command(false, "devices") {
-        Ok(_devices) => true,
-        Err(_err) => false,
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_adb_version() {
+        let output = "Android Debug Bridge version 1.0.41\nVersion 34.0.5-10900879\n\
+            Installed as /usr/bin/adb";
+        assert_eq!(parse_adb_version(output), Some("1.0.41".to_string()));
+        assert_eq!(parse_adb_version(""), None);
+    }
+
+    #[test]
+    fn test_is_valid_package_name() {
+        for valid in [
+            "android",
+            "com.android.systemui",
+            "com.sec.android.app.sbrowser",
+            "a.b_2",
+        ] {
+            assert!(is_valid_package_name(valid), "{valid}");
+        }
+        for invalid in [
+            "",
+            "null",
+            "com.",
+            ".com.app",
+            "com..app",
+            "com.1app",
+            "com.app; reboot",
+            "com.app name",
+            "com.app/.Main",
+            "--user",
+        ] {
+            assert!(!is_valid_package_name(invalid), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_apply_pkg_state_commands_normalizes_name() {
+        let phone = Phone {
+            android_sdk: 30,
+            ..Phone::default()
+        };
+        let commands = |name: &str| {
+            let package = CorePackage {
+                name: name.to_string(),
+                state: PackageState::Enabled,
+            };
+            apply_pkg_state_commands(
+                &package,
+                PackageState::Uninstalled,
+                &User::default(),
+                &phone,
+            )
+        };
+        assert_eq!(
+            commands(" package:com.facebook.katana\r\n"),
+            Ok(vec!["pm uninstall --user 0 com.facebook.katana".to_string()])
+        );
+        for invalid in ["", "   ", "package:", "com.app name"] {
+            assert!(commands(invalid).is_err(), "{invalid:?}");
+        }
+    }
+
+    #[test]
+    fn test_apply_pkg_state_commands_by_sdk() {
+        use PackageState::{Disabled, Enabled, Uninstalled};
+
+        let commands = |sdk, from, to| {
+            let package = CorePackage {
+                name: "com.app".to_string(),
+                state: from,
+            };
+            let phone = Phone {
+                android_sdk: sdk,
+                ..Phone::default()
+            };
+            apply_pkg_state_commands(&package, to, &User::default(), &phone)
+                .unwrap_or_default()
+                .join("; ")
+        };
+
+        // (sdk, uninstall, restore, disable, enable)
+        let matrix = [
+            (19, "pm block com.app; pm clear com.app", "pm unblock com.app; pm clear com.app", "", "pm enable com.app"),
+            (21, "pm hide --user 0 com.app; pm clear --user 0 com.app", "pm unhide --user 0 com.app", "", "pm enable --user 0 com.app"),
+            (23, "pm uninstall --user 0 com.app", "pm install-existing --user 0 com.app", "pm disable-user --user 0 com.app; am force-stop --user 0 com.app; pm clear --user 0 com.app", "pm enable --user 0 com.app"),
+            (30, "pm uninstall --user 0 com.app", "cmd package install-existing --user 0 com.app", "pm disable-user --user 0 com.app; am force-stop --user 0 com.app; pm clear --user 0 com.app", "pm enable --user 0 com.app"),
+            (34, "pm uninstall --user 0 com.app", "cmd package install-existing --user 0 com.app", "pm disable-user --user 0 com.app; am force-stop --user 0 com.app; pm clear --user 0 com.app", "pm enable --user 0 com.app"),
+        ];
+        for (sdk, uninstall, restore, disable, enable) in matrix {
+            assert_eq!(commands(sdk, Enabled, Uninstalled), uninstall, "SDK {sdk}");
+            assert_eq!(commands(sdk, Uninstalled, Enabled), restore, "SDK {sdk}");
+            assert_eq!(commands(sdk, Enabled, Disabled), disable, "SDK {sdk}");
+            assert_eq!(commands(sdk, Disabled, Enabled), enable, "SDK {sdk}");
+        }
+    }
+
+    #[test]
+    fn test_suspend_pkg_commands() {
+        let user = User::default();
+        let phone = |android_sdk| Phone {
+            android_sdk,
+            ..Phone::default()
+        };
+        assert_eq!(
+            suspend_pkg_commands("com.facebook.katana", true, &user, &phone(30)),
+            Ok(vec!["pm suspend --user 0 com.facebook.katana".to_string()])
+        );
+        assert_eq!(
+            suspend_pkg_commands("com.facebook.katana", false, &user, &phone(24)),
+            Ok(vec!["pm unsuspend --user 0 com.facebook.katana".to_string()])
+        );
+        assert!(suspend_pkg_commands("com.facebook.katana", true, &user, &phone(23)).is_err());
+    }
+
+    #[test]
+    fn test_is_stub_apk() {
+        let paths = |p: &[&str]| p.iter().map(ToString::to_string).collect::<Vec<_>>();
+        assert!(is_stub_apk(&paths(&[
+            "/product/app/ChromeStub/ChromeStub.apk"
+        ])));
+        assert!(is_stub_apk(&paths(&[
+            "/system/app/GoogleCamera/GoogleCamera-Stub.apk"
+        ])));
+        assert!(!is_stub_apk(&paths(&[
+            "/data/app/~~x==/com.android.chrome-y==/base.apk",
+            "/data/app/~~x==/com.android.chrome-y==/split_chrome.apk",
+        ])));
+        assert!(!is_stub_apk(&paths(&["/system/app/Stubborn/Stubborn.apk"])));
+        assert!(!is_stub_apk(&[]));
+    }
+
+    #[test]
+    fn test_remount_command() {
+        assert_eq!(partition_of("/product/app/Foo"), Some("/product"));
+        assert_eq!(
+            partition_of("/system_ext/priv-app/Bar"),
+            Some("/system_ext")
+        );
+        assert_eq!(partition_of("/data/app/Baz"), None);
+        assert_eq!(
+            remount_command("/vendor", "rw"),
+            "su -c 'mount -o rw,remount /vendor || mount -o rw,remount /system || mount -o rw,remount /'"
+        );
+        assert_eq!(
+            remount_command("/system", "ro"),
+            "su -c 'mount -o ro,remount /system || mount -o ro,remount /'"
+        );
+    }
+
+    #[test]
+    fn test_split_names() {
+        let paths = |p: &[&str]| p.iter().map(ToString::to_string).collect::<Vec<_>>();
+        assert_eq!(
+            split_names(&paths(&[
+                "/data/app/~~x==/com.android.chrome-y==/base.apk",
+                "/data/app/~~x==/com.android.chrome-y==/split_chrome.apk",
+                "/data/app/~~x==/com.android.chrome-y==/split_config.arm64_v8a.apk",
+            ])),
+            vec!["chrome", "config.arm64_v8a"]
+        );
+        assert!(split_names(&paths(&["/system/app/Stk/Stk.apk"])).is_empty());
+    }
+
+    #[test]
+    fn test_parse_apk_sizes() {
+        let listing = "package:/system/app/Stk/Stk.apk=com.android.stk\n\
+            package:/data/app/~~Yx3Q==/com.facebook.katana-Bf9a==/base.apk=com.facebook.katana\n\
+            package:/product/priv-app/Phonesky/Phonesky.apk=com.android.vending\n";
+        let paths = parse_package_paths(listing);
+        assert_eq!(paths.len(), 3);
+        assert_eq!(
+            paths.get("com.facebook.katana"),
+            Some(&"/data/app/~~Yx3Q==/com.facebook.katana-Bf9a==/base.apk")
+        );
+        assert_eq!(
+            paths.get("com.android.stk"),
+            Some(&"/system/app/Stk/Stk.apk")
+        );
+
+        let du = "148212\t/data/app/~~Yx3Q==/com.facebook.katana-Bf9a==\n\
+            du: /data/app/gone: No such file or directory\n";
+        assert_eq!(
+            parse_du(du).collect::<Vec<_>>(),
+            vec![("/data/app/~~Yx3Q==/com.facebook.katana-Bf9a==", 148_212)]
+        );
+    }
+
+    #[test]
+    fn test_clear_data_commands() {
+        let user = User::default();
+        let phone = |android_sdk| Phone {
+            android_sdk,
+            ..Phone::default()
+        };
+        assert_eq!(
+            clear_data_commands("package:com.facebook.katana", &user, &phone(30)),
+            Ok(vec!["pm clear --user 0 com.facebook.katana".to_string()])
+        );
+        assert_eq!(
+            clear_data_commands("com.facebook.katana", &user, &phone(19)),
+            Ok(vec!["pm clear com.facebook.katana".to_string()])
+        );
+        assert!(clear_data_commands("com.facebook.katana; reboot", &user, &phone(30)).is_err());
+    }
+
+    #[test]
+    fn test_parse_suspended() {
+        let dumpsys = "Packages:\n  Package [com.facebook.katana] (3c1e2f0):\n    \
+            User 0: ceDataInode=4212 installed=true hidden=false suspended=true stopped=false\n    \
+            User 10: ceDataInode=0 installed=true hidden=false suspended=false stopped=true\n";
+        assert!(parse_suspended(dumpsys, 0));
+        assert!(!parse_suspended(dumpsys, 10));
+        assert!(!parse_suspended(dumpsys, 11));
+    }
+
+    #[test]
+    fn test_parse_device_info() {
+        let df = "Filesystem      1K-blocks     Used Available Use% Mounted on\n\
+            /dev/block/dm-5 115249236 4567567 110681669   4% /data";
+        assert_eq!(parse_df(df), Some((115_249_236 * 1024, 110_681_669 * 1024)));
+        // Android 6 and older `df` have no `-k` and print human sizes
+        assert_eq!(parse_df("/data 25.1G 3.2G 21.9G 4096"), None);
+
+        let battery = "Current Battery Service state:\n  AC powered: false\n  \
+            USB powered: true\n  level: 85\n  scale: 100\n";
+        assert_eq!(parse_battery_level(battery), Some(85));
+        assert_eq!(parse_battery_level("Can't find service: battery"), None);
+    }
+
+    #[test]
+    fn test_adb_error_classification() {
+        let pm = || {
+            CommandType::PackageManager(PackageInfo {
+                i_user: 0,
+                index: 3,
+                removal: "Recommended".to_string(),
+            })
+        };
+        let classify =
+            |command_type, output: &str| AdbError::new(command_type, output, output.to_string());
+        assert!(matches!(
+            classify(pm(), "Failure [DELETE_FAILED_USER_RESTRICTED]"),
+            AdbError::Restricted(PackageInfo { index: 3, .. }, _)
+        ));
+        assert!(matches!(
+            classify(
+                CommandType::Shell,
+                "Failure [DELETE_FAILED_USER_RESTRICTED]"
+            ),
+            AdbError::Other(_)
+        ));
+        assert!(matches!(
+            classify(pm(), "Failure [not installed for 10]"),
+            AdbError::NotInstalledForUser(_)
+        ));
+        assert!(matches!(
+            classify(pm(), "Failure [DELETE_FAILED_DEVICE_POLICY_MANAGER]"),
+            AdbError::DevicePolicy(_)
+        ));
+        assert!(matches!(
+            classify(
+                pm(),
+                "java.lang.SecurityException: Shell cannot change component state for com.miui.msa.global"
+            ),
+            AdbError::PermissionDenied(_)
+        ));
+        assert!(matches!(
+            classify(
+                pm(),
+                "Error: java.lang.IllegalArgumentException: Unknown package: com.foo"
+            ),
+            AdbError::NotFound(_)
+        ));
+        assert!(matches!(
+            classify(
+                CommandType::Shell,
+                "Failure [INSTALL_FAILED_VERSION_DOWNGRADE: Downgrade detected]"
+            ),
+            AdbError::Downgrade(_)
+        ));
+        assert!(matches!(
+            classify(
+                CommandType::Shell,
+                "Failure [INSTALL_FAILED_UPDATE_INCOMPATIBLE: Package com.foo signatures do not match]"
+            ),
+            AdbError::SignatureMismatch(_)
+        ));
+        assert!(matches!(
+            classify(
+                pm(),
+                "Failure [DELETE_FAILED_INTERNAL_ERROR]: Package com.foo not found"
+            ),
+            AdbError::NotFound(_)
+        ));
+        assert!(matches!(
+            classify(pm(), "error: device 'R58M123' not found"),
+            AdbError::Disconnected(_)
+        ));
+        assert!(matches!(
+            classify(pm(), "adb: connect error for write: timed out"),
+            AdbError::Timeout(_)
+        ));
+        let other = classify(pm(), "Failure [DELETE_FAILED_INTERNAL_ERROR]");
+        assert!(matches!(other, AdbError::Other(_)));
+        assert!(other.hint().is_none());
+        assert_eq!(other.to_string(), "Failure [DELETE_FAILED_INTERNAL_ERROR]");
+    }
+
+    #[test]
+    fn test_component_state_commands() {
+        let user = User::default();
+        let phone = Phone {
+            android_sdk: 30,
+            ..Phone::default()
+        };
+        assert_eq!(
+            component_state_commands(
+                "com.samsung.android.game.gos/.service.GameService",
+                false,
+                &user,
+                &phone
+            ),
+            Ok(vec![
+                "pm disable --user 0 'com.samsung.android.game.gos/.service.GameService'"
+                    .to_string()
+            ])
+        );
+        assert_eq!(
+            component_state_commands(
+                "com.facebook.katana/com.facebook.Foo$Bar",
+                true,
+                &user,
+                &phone
+            ),
+            Ok(vec![
+                "pm enable --user 0 'com.facebook.katana/com.facebook.Foo$Bar'".to_string()
+            ])
+        );
+        assert!(component_state_commands("com.facebook.katana", true, &user, &phone).is_err());
+        assert!(component_state_commands("com.facebook.katana/", true, &user, &phone).is_err());
+        assert!(component_state_commands(
+            "com.facebook.katana/.A' && reboot '",
+            true,
+            &user,
+            &phone
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_parse_components() {
+        let dumpsys = "Activity Resolver Table:\n  Non-Data Actions:\n      \
+            android.intent.action.MAIN:\n        5a3e2b1 com.foo/.MainActivity filter 8c2d\n\
+            Service Resolver Table:\n  Non-Data Actions:\n      com.foo.SYNC:\n        \
+            1b2c3d4 com.foo/com.foo.sync.SyncService filter 9e8f\n\
+            Registered ContentProviders:\n  com.foo/.data.Provider:\n    \
+            Provider{4f5e6d7 com.foo/.data.Provider}\n\
+            Packages:\n  Package [com.foo] (3c1e2f0):\n    \
+            codePath=/data/app/com.foo-1\n    \
+            User 0: ceDataInode=4212 installed=true hidden=false suspended=false\n      \
+            disabledComponents:\n        com.foo.sync.SyncService\n        com.foo.Tracker\n    \
+            User 10: ceDataInode=0 installed=true hidden=false suspended=false\n      \
+            disabledComponents:\n        com.foo.MainActivity\n";
+        let component = |name: &str, enabled| PackageComponent {
+            name: name.to_string(),
+            enabled,
+        };
+        assert_eq!(
+            parse_components(dumpsys, "com.foo", 0),
+            vec![
+                component("com.foo/com.foo.MainActivity", true),
+                component("com.foo/com.foo.Tracker", false),
+                component("com.foo/com.foo.data.Provider", true),
+                component("com.foo/com.foo.sync.SyncService", false),
+            ]
+        );
+        assert!(parse_components(dumpsys, "com.foo", 10)
+            .iter()
+            .all(|c| c.enabled != (c.name == "com.foo/com.foo.MainActivity")));
+    }
+
+    #[test]
+    fn test_to_trimmed_utf8() {
+        assert_eq!(to_trimmed_utf8(b"package:com.foo\r\n"), "package:com.foo");
+        assert_eq!(
+            to_trimmed_utf8(b"Galaxy \xff\xfe"),
+            "Galaxy \u{FFFD}\u{FFFD}"
+        );
+    }
+
+    #[test]
+    fn test_install_args() {
+        let apks = |names: &[&str]| names.iter().map(PathBuf::from).collect::<Vec<_>>();
+        assert_eq!(
+            install_args(&apks(&["base.apk"]), None),
+            ["install", "-r", "base.apk"]
+        );
+        assert_eq!(
+            install_args(
+                &apks(&["base.apk", "split_config.en.apk"]),
+                Some(&User {
+                    id: 10,
+                    index: 1,
+                    protected: false
+                })
+            ),
+            [
+                "install-multiple",
+                "-r",
+                "--user",
+                "10",
+                "base.apk",
+                "split_config.en.apk"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_wireless_reconnect_detection() {
+        assert!(is_connection_lost("error: device offline"));
+        assert!(is_connection_lost(
+            "error: device '192.168.1.5:5555' not found"
+        ));
+        assert!(is_connection_lost("error: no devices/emulators found"));
+        assert!(!is_connection_lost("Failure [not installed for 0]"));
+        assert!(!is_connection_lost("Error: package com.foo not found"));
+
+        assert_eq!(
+            wireless_address("192.168.1.5:5555"),
+            Some("192.168.1.5:5555")
+        );
+        assert_eq!(wireless_address("phone.lan:37123"), Some("phone.lan:37123"));
+        assert_eq!(wireless_address("R58M123"), None);
+        assert_eq!(wireless_address("emulator-5554"), None);
+        assert_eq!(wireless_address(":5555"), None);
+    }
+
+    #[test]
+    fn test_get_target_device() {
+        let phone = |id: &str, unauthorized| Phone {
+            adb_id: id.to_string(),
+            unauthorized,
+            ..Phone::default()
+        };
+        let devices = [phone("emulator-5554", true), phone("R58M123", false)];
+
+        let target = |serial| get_target_device(&devices, serial).map(|p| p.map(|p| &p.adb_id));
+        assert_eq!(target(None), Ok(Some(&devices[1].adb_id)));
+        assert_eq!(target(Some("emulator-5554")), Ok(Some(&devices[0].adb_id)));
+        let err = target(Some("0123")).expect_err("0123 isn't connected");
+        assert!(err.contains("emulator-5554, R58M123"));
+        assert_eq!(get_target_device(&[], None), Ok(None));
     }
 }
@@ -1,6 +1,10 @@
+use crate::CONFIG_DIR;
 use dark_light;
 use iced::{color, Color};
+use serde::{Deserialize, Serialize};
 use static_init::dynamic;
+use std::fs;
+use std::path::PathBuf;
 
 /*
 In-memory caching.
@@ -14,6 +18,13 @@ at the cost of requiring a restart to update the palette.
 #[dynamic(lazy)]
 pub static OS_COLOR_SCHEME: dark_light::Mode = dark_light::detect();
 
+/// User-defined palette, read once from `CONFIG_DIR/theme.toml`
+#[dynamic(lazy)]
+pub static CUSTOM_PALETTE: Option<ColorPalette> = load_custom_palette();
+
+pub const CUSTOM_THEME_FILE: &str = "theme.toml";
+pub const EXPORTED_THEME_FILE: &str = "theme_export.toml";
+
 #[derive(Default, Debug, PartialEq, Eq, Copy, Clone)]
 /// Color scheme
 pub enum Theme {
@@ -26,32 +37,48 @@ pub enum Theme {
     Dark,
     /// black on white
     Light,
+    /// black background, blue/orange instead of green/red
+    /// (distinguishable with common color vision deficiencies)
+    HighContrast,
+    /// [`CUSTOM_PALETTE`], only available when `theme.toml` is valid
+    Custom,
 }
 
-#[derive(Debug, Clone, Copy)]
+// Colors are written as `"#RRGGBB"` in theme files
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct BaseColors {
+    #[serde(with = "hex_color")]
     pub background: Color,
+    #[serde(with = "hex_color")]
     pub foreground: Color,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct NormalColors {
+    #[serde(with = "hex_color")]
     pub primary: Color,
     #[allow(dead_code)]
+    #[serde(with = "hex_color")]
     pub secondary: Color,
+    #[serde(with = "hex_color")]
     pub surface: Color,
+    #[serde(with = "hex_color")]
     pub error: Color,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct BrightColors {
+    #[serde(with = "hex_color")]
     pub primary: Color,
+    #[serde(with = "hex_color")]
     pub secondary: Color,
+    #[serde(with = "hex_color")]
     pub surface: Color,
+    #[serde(with = "hex_color")]
     pub error: Color,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct ColorPalette {
     pub base: BaseColors,
     pub normal: NormalColors,
@@ -59,7 +86,22 @@ pub struct ColorPalette {
 }
 
 impl Theme {
-    pub const ALL: [Self; 4] = [Self::Auto, Self::Lupin, Self::Dark, Self::Light];
+    pub const ALL: [Self; 5] = [
+        Self::Auto,
+        Self::Lupin,
+        Self::Dark,
+        Self::Light,
+        Self::HighContrast,
+    ];
+
+    /// [`Self::ALL`], plus [`Self::Custom`] if a valid custom theme was loaded
+    pub fn available() -> Vec<Self> {
+        let mut themes = Self::ALL.to_vec();
+        if CUSTOM_PALETTE.is_some() {
+            themes.push(Self::Custom);
+        }
+        themes
+    }
 
     #[allow(
         clippy::unreadable_literal,
@@ -123,14 +165,36 @@ impl Theme {
                 error: color!(0xE63E6D),
             },
         };
+        // Okabe-Ito colors
+        const HIGH_CONTRAST: ColorPalette = ColorPalette {
+            base: BaseColors {
+                background: color!(0x000000),
+                // Light enough to tell panels apart, dark enough for the white text
+                foreground: color!(0x4D4D4D),
+            },
+            normal: NormalColors {
+                primary: color!(0x005A8C),
+                secondary: color!(0x0072B2),
+                surface: color!(0xBFBFBF),
+                error: color!(0xD55E00),
+            },
+            bright: BrightColors {
+                primary: color!(0xF0E442),
+                secondary: color!(0x56B4E9),
+                surface: color!(0xFFFFFF),
+                error: color!(0xE69F00),
+            },
+        };
         match self {
             Self::Dark => DARK,
+            Self::HighContrast => HIGH_CONTRAST,
             Self::Light => LIGHT,
             Self::Lupin => LUPIN,
             Self::Auto => match *OS_COLOR_SCHEME {
                 dark_light::Mode::Light => LIGHT,
                 dark_light::Mode::Dark | dark_light::Mode::Default => DARK,
             },
+            Self::Custom => CUSTOM_PALETTE.unwrap_or_else(|| Self::Auto.palette()),
         }
     }
 }
@@ -144,8 +208,78 @@ impl std::fmt::Display for Theme {
                 Self::Dark => "Dark",
                 Self::Light => "Light",
                 Self::Lupin => "Lupin",
+                Self::HighContrast => "High contrast",
                 Self::Auto => "Auto (follow system theme)",
+                Self::Custom => "Custom",
             }
         )
     }
 }
+
+/// Read `CONFIG_DIR/theme.toml`, if any.
+/// Invalid files are ignored (with a warning) so the default theme is used instead.
+fn load_custom_palette() -> Option<ColorPalette> {
+    let path = CONFIG_DIR.join(CUSTOM_THEME_FILE);
+    let data = fs::read_to_string(&path).ok()?;
+    match toml::from_str(&data) {
+        Ok(palette) => {
+            info!("Custom theme loaded from {}", path.display());
+            Some(palette)
+        }
+        Err(e) => {
+            warn!("Ignoring invalid custom theme {}: {e}", path.display());
+            None
+        }
+    }
+}
+
+/// Write the palette of `theme` to `CONFIG_DIR/theme_export.toml`.
+/// Rename it to `theme.toml` to use it as a custom theme.
+pub fn export_theme(theme: Theme) -> Result<PathBuf, String> {
+    let toml = toml::to_string(&theme.palette()).map_err(|e| e.to_string())?;
+    let path = CONFIG_DIR.join(EXPORTED_THEME_FILE);
+    fs::write(&path, toml).map_err(|e| e.to_string())?;
+    Ok(path)
+}
+
+mod hex_color {
+    use iced::Color;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(color: &Color, serializer: S) -> Result<S::Ok, S::Error> {
+        let [r, g, b, _] = color.into_rgba8();
+        serializer.serialize_str(&format!("#{r:02X}{g:02X}{b:02X}"))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        parse(&hex).ok_or_else(|| serde::de::Error::custom(format!("invalid color `{hex}`")))
+    }
+
+    /// Parse `#RRGGBB`
+    pub fn parse(hex: &str) -> Option<Color> {
+        let hex = hex.strip_prefix('#')?;
+        if hex.len() != 6 || !hex.is_ascii() {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+        Some(Color::from_rgb8(channel(0)?, channel(2)?, channel(4)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_palette_toml_roundtrip() {
+        let toml = toml::to_string(&Theme::Lupin.palette()).expect("palette serializes");
+        assert!(toml.contains("background = \"#282A36\""));
+        let palette: ColorPalette = toml::from_str(&toml).expect("exported palette parses");
+        assert_eq!(palette.bright.primary, Color::from_rgb8(0xBD, 0x94, 0xF9));
+
+        let invalid = toml.replace("#282A36", "#28ZZ36");
+        assert!(toml::from_str::<ColorPalette>(&invalid).is_err());
+        assert!(hex_color::parse("282A36").is_none());
+    }
+}
@@ -1,11 +1,15 @@
-use crate::core::utils::{format_diff_time_from_now, last_modified_date};
+use crate::core::utils::{
+    format_diff_time_from_now, last_modified_date, matches_search, SearchField,
+};
 use crate::CACHE_DIR;
+use chrono::{DateTime, Utc};
 use retry::{delay::Fixed, retry, OperationResult};
 use serde::{Deserialize, Serialize};
 use serde_json;
 use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::Path;
+use std::str::FromStr;
 
 pub const LIST_FNAME: &str = "uad_lists.json";
 
@@ -28,7 +32,7 @@ pub struct Package {
     pub removal: Removal,
 }
 
-#[derive(Default, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Default, Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum UadList {
     #[default]
     All,
@@ -41,6 +45,14 @@ pub enum UadList {
     Unlisted,
 }
 
+impl FromStr for UadList {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_variant(s, &Self::ALL, "list")
+    }
+}
+
 #[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum UadListState {
     #[default]
@@ -93,7 +105,7 @@ impl std::fmt::Display for UadList {
     }
 }
 
-#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum PackageState {
     All,
     #[default]
@@ -121,6 +133,14 @@ impl std::fmt::Display for PackageState {
     }
 }
 
+impl FromStr for PackageState {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_variant(s, &Self::ALL, "package state")
+    }
+}
+
 pub trait Opposite {
     fn opposite(&self, disable: bool) -> PackageState;
 }
@@ -142,7 +162,9 @@ impl Opposite for PackageState {
 }
 
 // Bad names. To be changed!
-#[derive(Default, Debug, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(
+    Default, Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash,
+)]
 pub enum Removal {
     #[default]
     Recommended,
@@ -188,51 +210,329 @@ impl std::fmt::Display for Removal {
     }
 }
 
+impl FromStr for Removal {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_variant(s, &Self::ALL, "removal category")
+    }
+}
+
+/// Case-insensitive parsing of the [`Display`](std::fmt::Display) form of one of `values`.
+/// `all` is accepted for the `All...` variant, which comes first in `values`.
+fn parse_variant<T: Copy + std::fmt::Display>(
+    s: &str,
+    values: &[T],
+    what: &str,
+) -> Result<T, String> {
+    let s = s.trim();
+    if s.eq_ignore_ascii_case("all") {
+        return values.first().copied().ok_or_else(|| format!("no {what}"));
+    }
+    values
+        .iter()
+        .find(|v| v.to_string().eq_ignore_ascii_case(s))
+        .copied()
+        .ok_or_else(|| {
+            let valid = values
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!("invalid {what} \"{s}\", expected one of: {valid}")
+        })
+}
+
+/// Manufacturer whose pre-installed packages can be told apart by their name
+#[derive(Debug, PartialEq, Eq)]
+pub struct Oem {
+    pub name: &'static str,
+    /// Lowercase values of `ro.product.brand`
+    brands: &'static [&'static str],
+    prefixes: &'static [&'static str],
+}
+
+impl Oem {
+    /// `package` is one of this manufacturer's own packages
+    pub fn owns(&self, package: &str) -> bool {
+        self.prefixes
+            .iter()
+            .any(|prefix| package.starts_with(prefix))
+    }
+}
+
+/// Vendors whose pre-installed apps are often bloatware, by package name prefix.
+/// Used to give a hint about packages that aren't documented in the lists yet,
+/// and to tell the manufacturer of a device from its brand.
+pub const VENDOR_PREFIXES: [Oem; 8] = [
+    Oem {
+        name: "Samsung",
+        brands: &["samsung"],
+        prefixes: &["com.samsung.", "com.sec."],
+    },
+    Oem {
+        name: "Xiaomi",
+        brands: &["xiaomi", "redmi", "poco"],
+        prefixes: &["com.miui.", "com.xiaomi."],
+    },
+    Oem {
+        name: "Huawei",
+        brands: &["huawei"],
+        prefixes: &["com.huawei."],
+    },
+    Oem {
+        name: "Honor",
+        brands: &["honor"],
+        prefixes: &["com.hihonor.", "com.huawei."],
+    },
+    Oem {
+        name: "Oppo/OnePlus/Realme",
+        brands: &["oppo", "oneplus", "realme"],
+        prefixes: &["com.oppo.", "com.coloros.", "com.heytap.", "com.oneplus."],
+    },
+    Oem {
+        name: "Vivo",
+        brands: &["vivo", "iqoo"],
+        prefixes: &["com.vivo.", "com.iqoo."],
+    },
+    Oem {
+        name: "Transsion",
+        brands: &["tecno", "infinix", "itel"],
+        prefixes: &["com.transsion."],
+    },
+    Oem {
+        name: "LG",
+        brands: &["lge", "lg"],
+        prefixes: &["com.lge."],
+    },
+];
+
+/// Heuristic for packages missing from the lists:
+/// returns the vendor if `package` matches a well-known vendor prefix.
+pub fn heuristic_vendor(package: &str) -> Option<&'static str> {
+    VENDOR_PREFIXES
+        .iter()
+        .find(|oem| oem.owns(package))
+        .map(|oem| oem.name)
+}
+
+/// Manufacturer of a device from its `ro.product.brand`.
+/// `None` for unknown brands and AOSP-like ones (Google, emulators, ...).
+pub fn detect_oem(brand: &str) -> Option<&'static Oem> {
+    let brand = brand.trim().to_lowercase();
+    VENDOR_PREFIXES
+        .iter()
+        .find(|oem| oem.brands.contains(&brand.as_str()))
+}
+
+/// Where the latest lists are downloaded from
+pub fn list_url() -> String {
+    format!(
+        "https://raw.githubusercontent.com/Universal-Debloater-Alliance/universal-android-debloater/\
+        main/resources/assets/{LIST_FNAME}"
+    )
+}
+
+/// Packages that can't be removed, whatever their removal category:
+/// removing them makes the device unusable.
+/// An entry ending with `.` protects every package starting with it.
+pub const PROTECTED_PACKAGES: [&str; 5] = [
+    "android",
+    "com.android.systemui",
+    "com.android.settings",
+    "com.android.phone",
+    "com.android.providers.settings",
+];
+
+/// Packages providing a component (library, provider, service) that unrelated apps rely on,
+/// following [`PROTECTED_PACKAGES`] syntax
+pub const SHARED_COMPONENTS: [(&str, &str); 8] = [
+    (
+        "com.google.android.gms",
+        "Google Play services, used by most Google and many other apps",
+    ),
+    (
+        "com.google.android.gsf",
+        "the Google Services Framework, which Google Play services needs",
+    ),
+    (
+        "com.android.webview",
+        "WebView, which apps use to show web pages",
+    ),
+    (
+        "com.google.android.webview",
+        "WebView, which apps use to show web pages",
+    ),
+    (
+        "com.google.android.trichromelibrary",
+        "the library shared by Chrome and WebView",
+    ),
+    (
+        "com.android.providers.",
+        "a content provider (contacts, calls, media...) the dialer, messages or settings read from",
+    ),
+    (
+        "com.android.ext.services",
+        "services of the Android framework (notifications, autofill...)",
+    ),
+    (
+        "com.google.android.ext.services",
+        "services of the Android framework (notifications, autofill...)",
+    ),
+];
+
+/// Why removing `name` may break other apps, if it may.
+///
+/// Best-effort: the `neededBy` of the lists, then [`SHARED_COMPONENTS`] unless the lists
+/// recommend the removal.
+pub fn dependency_warning(name: &str, package: Option<&Package>) -> Option<String> {
+    if let Some(p) = package.filter(|p| !p.needed_by.is_empty()) {
+        return Some(format!("needed by {}", p.needed_by.join(", ")));
+    }
+    if package.is_some_and(|p| p.removal == Removal::Recommended) {
+        return None;
+    }
+    SHARED_COMPONENTS
+        .iter()
+        .find(|(entry, _)| matches_entry(name, entry))
+        .map(|(_, what)| format!("provides {what}"))
+}
+
+/// `package` matches an entry of `protected`, following [`PROTECTED_PACKAGES`] syntax
+pub fn is_protected(package: &str, protected: &[String]) -> bool {
+    protected.iter().any(|entry| matches_entry(package, entry))
+}
+
+/// `package` is `entry`, or starts with it when it ends with `.`
+fn matches_entry(package: &str, entry: &str) -> bool {
+    package == entry || (entry.ends_with('.') && package.starts_with(entry))
+}
+
 pub type PackageHashMap = HashMap<String, Package>;
-pub fn load_debloat_lists(remote: bool) -> Result<PackageHashMap, PackageHashMap> {
-    let cached_uad_lists: PathBuf = CACHE_DIR.join(LIST_FNAME);
-    let mut error = false;
-    let list: PackageHashMap = if remote {
+
+/// Lists to load: the `list_source` setting (a URL or the path of a local file),
+/// or the official lists when it's empty
+pub fn list_source(setting: &str) -> String {
+    let setting = setting.trim();
+    if setting.is_empty() {
+        list_url()
+    } else {
+        setting.to_string()
+    }
+}
+
+pub fn is_url(source: &str) -> bool {
+    source.starts_with("https://") || source.starts_with("http://")
+}
+
+/// Load the lists of `source` (see [`list_source`]), caching the official ones.
+/// A local file is read even when `remote` is off.
+/// When they can't be fetched or don't match the lists format,
+/// the embedded lists are returned as an error.
+pub fn load_debloat_lists(remote: bool, source: &str) -> Result<PackageHashMap, PackageHashMap> {
+    let source = list_source(source);
+    if !remote && is_url(&source) {
+        warn!("Could not load remote debloat list");
+        return Ok(get_local_lists());
+    }
+    let text = if is_url(&source) {
         retry(Fixed::from_millis(1000).take(60), || {
-            match ureq::get(
-                &format!("https://raw.githubusercontent.com/Universal-Debloater-Alliance/universal-android-debloater/\
-           main/resources/assets/{LIST_FNAME}"),
-            )
-            .call()
-            {
-                Ok(data) => {
-                    // TODO: max resp size is 10MB, list is ~1.3MB;
-                    // TODO: https://github.com/Universal-Debloater-Alliance/universal-android-debloater-next-generation/discussions/608
-                    #[warn(clippy::expect_used, reason = "this will panic if GH servers rate-limit the user, or many other reasons.")]
-                    let text = data.into_string().expect("response should be Ok type");
-                    fs::write(cached_uad_lists.clone(), &text).expect("Unable to write file");
-                    let list: PackageHashMap = serde_json::from_str(&text).expect("Unable to parse");
-                    OperationResult::Ok(list)
-                }
+            match ureq::get(&source).call() {
+                // TODO: max resp size is 10MB, list is ~1.3MB;
+                // TODO: https://github.com/Universal-Debloater-Alliance/universal-android-debloater-next-generation/discussions/608
+                Ok(data) => data
+                    .into_string()
+                    .map_or_else(|e| OperationResult::Err(e.to_string()), OperationResult::Ok),
                 Err(e) => {
                     warn!("Could not load remote debloat list: {}", e);
-                    error = true;
-                    OperationResult::Retry(PackageHashMap::new())
+                    OperationResult::Retry(e.to_string())
                 }
             }
         })
-        .unwrap_or_else(|_| get_local_lists())
+        .map_err(|e| e.error)
     } else {
-        warn!("Could not load remote debloat list");
-        get_local_lists()
+        fs::read_to_string(&source).map_err(|e| e.to_string())
     };
 
-    (if error { Err } else { Ok })(list)
+    match text.and_then(|text| parse_lists(&text).map(|list| (text, list))) {
+        Ok((text, list)) => {
+            // The cache is the offline fallback of the official lists only
+            if source == list_url() {
+                if let Err(e) = write_atomically(&CACHE_DIR.join(LIST_FNAME), &text) {
+                    error!("Can't cache the debloat lists: {e}");
+                }
+            }
+            Ok(list)
+        }
+        Err(e) => {
+            error!("Can't load the debloat lists from {source}: {e}");
+            Err(embedded_lists())
+        }
+    }
 }
 
-fn get_local_lists() -> PackageHashMap {
-    let cached_uad_lists = CACHE_DIR.join(LIST_FNAME);
-    serde_json::from_str(
-        fs::read_to_string(cached_uad_lists)
-            .as_deref()
-            .unwrap_or(DATA),
-    )
-    .expect("Unable to parse")
+/// Packages of `lists` whose name or description contains `term`, sorted by name
+pub fn search_lists<'a>(lists: &'a PackageHashMap, term: &str) -> Vec<(&'a str, &'a Package)> {
+    let mut matches: Vec<(&str, &Package)> = lists
+        .iter()
+        .filter(|(name, p)| matches_search(name, &p.description, term, SearchField::Both))
+        .map(|(name, p)| (name.as_str(), p))
+        .collect();
+    matches.sort_unstable_by_key(|&(name, _)| name);
+    matches
+}
+
+/// Lists fetched longer ago than this likely miss recent recategorizations
+pub const STALE_LISTS_DAYS: i64 = 30;
+
+/// When the lists were last fetched, i.e. cached. `None` if they never were.
+pub fn lists_updated_at() -> Option<DateTime<Utc>> {
+    fs::metadata(CACHE_DIR.join(LIST_FNAME))
+        .and_then(|m| m.modified())
+        .ok()
+        .map(Into::into)
+}
+
+pub fn is_stale(updated_at: DateTime<Utc>, now: DateTime<Utc>) -> bool {
+    (now - updated_at).num_days() >= STALE_LISTS_DAYS
+}
+
+/// Parse lists, rejecting anything that doesn't follow the format of [`Package`]
+fn parse_lists(text: &str) -> Result<PackageHashMap, String> {
+    let list: PackageHashMap = serde_json::from_str(text).map_err(|e| e.to_string())?;
+    if list.is_empty() {
+        return Err("no package in the lists".to_string());
+    }
+    Ok(list)
+}
+
+/// Write `text` to a temporary file renamed to `path` once complete,
+/// so that an interrupted write never leaves a truncated `path` behind
+fn write_atomically(path: &Path, text: &str) -> std::io::Result<()> {
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, text)?;
+    fs::rename(&tmp, path)
+}
+
+/// The cached lists, or the embedded ones if the cache can't be read
+pub fn get_local_lists() -> PackageHashMap {
+    read_lists(&CACHE_DIR.join(LIST_FNAME))
+}
+
+/// The lists of `path`, or the embedded ones if it's missing or corrupt
+/// (e.g. truncated by a crash). A corrupt cache is replaced on the next update.
+fn read_lists(path: &Path) -> PackageHashMap {
+    fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|text| parse_lists(&text))
+        .unwrap_or_else(|e| {
+            warn!("Using the embedded debloat lists: {e}");
+            embedded_lists()
+        })
+}
+
+fn embedded_lists() -> PackageHashMap {
+    parse_lists(DATA).expect("embedded lists should be valid")
 }
 
 #[cfg(test)]
@@ -242,4 +542,169 @@ mod tests {
     fn test_parse_json() {
         let _: PackageHashMap = serde_json::from_str(DATA).expect("Unable to parse");
     }
+
+    #[test]
+    fn test_parse_lists() {
+        assert!(parse_lists(DATA).is_ok());
+        assert!(parse_lists("{}").is_err());
+        assert!(parse_lists("<html>rate limited</html>").is_err());
+        // Entries must follow the format of `Package`
+        assert!(parse_lists(r#"{"com.foo": {"list": "Oem"}}"#).is_err());
+        assert_eq!(list_source("  "), list_url());
+        assert_eq!(list_source("/srv/lists.json"), "/srv/lists.json");
+    }
+
+    #[test]
+    fn test_dependency_warning() {
+        let package = |removal, needed_by: &[&str]| Package {
+            list: UadList::Aosp,
+            description: String::new(),
+            dependencies: vec![],
+            needed_by: needed_by.iter().map(ToString::to_string).collect(),
+            labels: vec![],
+            removal,
+        };
+        assert_eq!(
+            dependency_warning(
+                "com.foo",
+                Some(&package(Removal::Recommended, &["com.bar", "com.baz"]))
+            ),
+            Some("needed by com.bar, com.baz".to_string())
+        );
+        assert!(dependency_warning(
+            "com.android.providers.contacts",
+            Some(&package(Removal::Expert, &[]))
+        )
+        .is_some_and(|w| w.starts_with("provides a content provider")));
+        assert!(dependency_warning("com.google.android.gms", None).is_some());
+        assert_eq!(
+            dependency_warning(
+                "com.android.providers.partnerbookmarks",
+                Some(&package(Removal::Recommended, &[]))
+            ),
+            None,
+            "the lists know better"
+        );
+        assert_eq!(
+            dependency_warning("com.google.android.gmsx", None),
+            None,
+            "exact names only"
+        );
+        assert_eq!(dependency_warning("com.foo", None), None);
+    }
+
+    #[test]
+    fn test_from_str() {
+        for list in UadList::ALL {
+            assert_eq!(list.to_string().parse(), Ok(list));
+        }
+        for state in PackageState::ALL {
+            assert_eq!(state.to_string().parse(), Ok(state));
+        }
+        for removal in Removal::ALL {
+            assert_eq!(removal.to_string().parse(), Ok(removal));
+        }
+        assert_eq!(" OEM ".parse(), Ok(UadList::Oem));
+        assert_eq!("disabled".parse(), Ok(PackageState::Disabled));
+        assert_eq!("all".parse(), Ok(PackageState::All));
+        assert_eq!("ALL".parse(), Ok(UadList::All));
+        let err = "safe".parse::<Removal>().expect_err("not a category");
+        assert!(err.contains("\"safe\""));
+        assert!(err.contains("Recommended, Advanced, Expert, Unsafe, Unlisted"));
+    }
+
+    #[test]
+    fn test_search_lists() {
+        let lists = parse_lists(DATA).expect("embedded lists are valid");
+        let names = |term| {
+            search_lists(&lists, term)
+                .into_iter()
+                .map(|(name, _)| name)
+                .collect::<Vec<_>>()
+        };
+        let facebook = names("com.facebook.");
+        assert!(facebook.contains(&"com.facebook.katana"));
+        assert!(facebook.windows(2).all(|w| w[0] < w[1]), "sorted by name");
+        assert!(
+            names("FACEBOOK").len() >= facebook.len(),
+            "case-insensitive"
+        );
+        assert!(names("no package is named like this").is_empty());
+    }
+
+    #[test]
+    fn test_corrupt_cache() {
+        let path = std::env::temp_dir().join(format!("uad_lists_test_{}.json", std::process::id()));
+        let embedded = parse_lists(DATA).expect("embedded lists are valid");
+
+        // Truncated by an interrupted download
+        fs::write(&path, &DATA.as_bytes()[..DATA.len() / 2]).expect("write test cache");
+        assert_eq!(read_lists(&path).len(), embedded.len());
+
+        let one = r#"{"com.foo": {"list": "Oem", "description": "", "dependencies": [], "neededBy": [], "labels": [], "removal": "Recommended"}}"#;
+        write_atomically(&path, one).expect("write test cache");
+        assert_eq!(read_lists(&path).len(), 1);
+        assert!(!path.with_extension("tmp").exists());
+
+        fs::remove_file(&path).expect("remove test cache");
+        assert_eq!(read_lists(&path).len(), embedded.len());
+    }
+
+    #[test]
+    fn test_local_list_source() {
+        let path =
+            std::env::temp_dir().join(format!("uad_lists_source_{}.json", std::process::id()));
+        let source = path.to_string_lossy();
+        let embedded = parse_lists(DATA).expect("embedded lists are valid");
+
+        let one = r#"{"com.foo": {"list": "Oem", "description": "", "dependencies": [], "neededBy": [], "labels": [], "removal": "Recommended"}}"#;
+        fs::write(&path, one).expect("write test source");
+        // Read even when offline
+        assert_eq!(load_debloat_lists(false, &source).map(|l| l.len()), Ok(1));
+
+        fs::write(&path, "<html>not found</html>").expect("write test source");
+        assert_eq!(
+            load_debloat_lists(true, &source).map_err(|l| l.len()),
+            Err(embedded.len())
+        );
+        fs::remove_file(&path).expect("remove test source");
+    }
+
+    #[test]
+    fn test_is_stale() {
+        let now = Utc::now();
+        assert!(!is_stale(now, now));
+        assert!(!is_stale(now - chrono::Duration::days(29), now));
+        assert!(is_stale(
+            now - chrono::Duration::days(STALE_LISTS_DAYS),
+            now
+        ));
+    }
+
+    #[test]
+    fn test_heuristic_vendor() {
+        assert_eq!(heuristic_vendor("com.miui.weather2"), Some("Xiaomi"));
+        assert_eq!(heuristic_vendor("com.huawei.himovie"), Some("Huawei"));
+        assert_eq!(heuristic_vendor("com.miuix.app"), None);
+        assert_eq!(heuristic_vendor("org.example.app"), None);
+    }
+
+    #[test]
+    fn test_is_protected() {
+        let protected = ["android".to_string(), "com.vendor.".to_string()];
+        assert!(is_protected("android", &protected));
+        assert!(!is_protected("android.ext.services", &protected));
+        assert!(is_protected("com.vendor.launcher", &protected));
+        assert!(!is_protected("com.vendorx.app", &protected));
+    }
+
+    #[test]
+    fn test_detect_oem() {
+        let xiaomi = detect_oem("Redmi").expect("Redmi is a Xiaomi brand");
+        assert_eq!(xiaomi.name, "Xiaomi");
+        assert!(xiaomi.owns("com.miui.weather2"));
+        assert!(!xiaomi.owns("com.google.android.gm"));
+        assert_eq!(detect_oem("google"), None);
+        assert_eq!(detect_oem(""), None);
+    }
 }
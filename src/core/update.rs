@@ -5,6 +5,8 @@ use serde::Deserialize;
 #[cfg(feature = "self-update")]
 use {
     retry::{delay::Fibonacci, retry, OperationResult},
+    ring::digest,
+    std::fmt::Write,
     std::fs,
     std::io,
     std::io::copy,
@@ -12,6 +14,10 @@ use {
     std::path::PathBuf,
 };
 
+/// Number of attempts of a download on transient network errors
+#[cfg(feature = "self-update")]
+const DOWNLOAD_ATTEMPTS: usize = 4;
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct Release {
     pub tag_name: String,
@@ -29,6 +35,43 @@ pub struct ReleaseAsset {
 pub struct SelfUpdateState {
     pub latest_release: Option<Release>,
     pub status: SelfUpdateStatus,
+    /// Why the last update attempt failed
+    pub error: Option<UpdateError>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum UpdateError {
+    Network(String),
+    /// GitHub rate limit, with the delay in seconds before retrying if given
+    /// (`Retry-After` or `X-RateLimit-Reset`)
+    RateLimited(Option<u64>),
+    Io(String),
+    /// The release has no asset for this platform
+    MissingAsset(String),
+    /// The downloaded file is truncated or empty
+    Corrupted(String),
+}
+
+impl std::fmt::Display for UpdateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Network(e) => write!(f, "network error ({e})"),
+            Self::RateLimited(Some(secs)) => {
+                write!(f, "rate-limited by GitHub, retry in {secs} seconds")
+            }
+            Self::RateLimited(None) => write!(f, "rate-limited by GitHub, retry later"),
+            Self::Io(e) => write!(f, "file error ({e})"),
+            Self::MissingAsset(name) => write!(f, "no `{name}` in the release"),
+            Self::Corrupted(e) => write!(f, "invalid download ({e})"),
+        }
+    }
+}
+
+#[cfg(feature = "self-update")]
+impl From<io::Error> for UpdateError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e.to_string())
+    }
 }
 
 #[derive(Default, Debug, PartialEq, Eq, Clone)]
@@ -38,6 +81,8 @@ pub enum SelfUpdateStatus {
     Checking,
     Done,
     Failed,
+    /// Not checked, see `offline_mode`
+    Offline,
 }
 
 impl std::fmt::Display for SelfUpdateStatus {
@@ -47,29 +92,120 @@ impl std::fmt::Display for SelfUpdateStatus {
             Self::Updating => "Updating...",
             Self::Failed => "Failed to check update!",
             Self::Done => "Done",
+            Self::Offline => "Offline mode",
         };
         write!(f, "{s}")
     }
 }
 
-/// Download a file from the internet
+/// Download a file from the internet, retrying on transient network errors.
+/// The size of the file is checked against `Content-Length` when available.
 #[cfg(feature = "self-update")]
 #[allow(clippy::unused_async, reason = "`.call` is equivalent to `.await`")]
-pub async fn download_file<T: ToString + Send>(url: T, dest_file: PathBuf) -> Result<(), String> {
+pub async fn download_file<T: ToString + Send>(
+    url: T,
+    dest_file: PathBuf,
+) -> Result<(), UpdateError> {
     let url = url.to_string();
     debug!("downloading file from {}", &url);
 
-    match ureq::get(&url).call() {
-        Ok(res) => {
-            let mut file = fs::File::create(dest_file).map_err(|e| e.to_string())?;
-
-            if let Err(e) = copy(&mut res.into_reader(), &mut file) {
-                return Err(e.to_string());
+    let res = retry(
+        Fibonacci::from_millis(1000).take(DOWNLOAD_ATTEMPTS),
+        || match ureq::get(&url).call() {
+            Ok(res) => OperationResult::Ok(res),
+            Err(ureq::Error::Status(code, res))
+                if is_rate_limited(code, |name| res.header(name)) =>
+            {
+                OperationResult::Err(UpdateError::RateLimited(rate_limit_delay(
+                    |name| res.header(name),
+                    chrono::Utc::now().timestamp(),
+                )))
             }
-        }
-        Err(e) => return Err(e.to_string()),
+            Err(ureq::Error::Status(code, _)) if code >= 500 => {
+                OperationResult::Retry(UpdateError::Network(format!("HTTP {code}")))
+            }
+            Err(ureq::Error::Status(code, _)) => {
+                OperationResult::Err(UpdateError::Network(format!("HTTP {code}")))
+            }
+            Err(e @ ureq::Error::Transport(_)) => {
+                warn!("Download failed, retrying: {e}");
+                OperationResult::Retry(UpdateError::Network(e.to_string()))
+            }
+        },
+    )
+    .map_err(|e| e.error)?;
+
+    let expected_len: Option<u64> = res.header("Content-Length").and_then(|s| s.parse().ok());
+    let mut file = fs::File::create(dest_file)?;
+    let len = copy(&mut res.into_reader(), &mut file)?;
+    match expected_len {
+        Some(expected) if expected != len => Err(UpdateError::Corrupted(format!(
+            "got {len} of {expected} bytes"
+        ))),
+        _ if len == 0 => Err(UpdateError::Corrupted("empty file".to_string())),
+        _ => Ok(()),
     }
-    Ok(())
+}
+
+/// GitHub answers 429, or 403 with no request left for its primary rate limit
+#[cfg(feature = "self-update")]
+fn is_rate_limited<'a>(code: u16, header: impl Fn(&str) -> Option<&'a str>) -> bool {
+    code == 429 || (code == 403 && header("X-RateLimit-Remaining") == Some("0"))
+}
+
+/// Seconds to wait before retrying: `Retry-After`, else until the
+/// `X-RateLimit-Reset` epoch timestamp, given the current one (`now`)
+#[cfg(feature = "self-update")]
+fn rate_limit_delay<'a>(header: impl Fn(&str) -> Option<&'a str>, now: i64) -> Option<u64> {
+    header("Retry-After")
+        .and_then(|s| s.parse().ok())
+        .or_else(|| {
+            let reset: i64 = header("X-RateLimit-Reset")?.parse().ok()?;
+            u64::try_from(reset - now).ok()
+        })
+}
+
+/// Check `file` against the `<asset_name>.sha256` asset of `release`, if it has one.
+/// It follows the `sha256sum` format: the hex digest, then optionally the file name.
+#[cfg(feature = "self-update")]
+async fn verify_checksum(
+    release: &Release,
+    asset_name: &str,
+    file: &Path,
+) -> Result<(), UpdateError> {
+    let checksum_name = format!("{asset_name}.sha256");
+    let Some(checksum) = release.assets.iter().find(|a| a.name == checksum_name) else {
+        info!("[SelfUpdate] The release has no {checksum_name}, checksum not verified");
+        return Ok(());
+    };
+    let checksum_path = file.with_file_name(&checksum_name);
+    download_file(&checksum.download_url, checksum_path.clone()).await?;
+    let expected = fs::read_to_string(&checksum_path)?;
+    fs::remove_file(&checksum_path)?;
+    let expected = expected
+        .split_whitespace()
+        .next()
+        .unwrap_or_default()
+        .to_lowercase();
+    let actual = sha256_hex(&fs::read(file)?);
+    if expected == actual {
+        Ok(())
+    } else {
+        Err(UpdateError::Corrupted(format!(
+            "SHA-256 is {actual}, expected {expected}"
+        )))
+    }
+}
+
+#[cfg(feature = "self-update")]
+fn sha256_hex(data: &[u8]) -> String {
+    digest::digest(&digest::SHA256, data)
+        .as_ref()
+        .iter()
+        .fold(String::new(), |mut hex, byte| {
+            let _ = write!(hex, "{byte:02x}");
+            hex
+        })
 }
 
 /// Downloads the latest release file that matches `bin_name`, renames the current
@@ -79,22 +215,19 @@ pub async fn download_file<T: ToString + Send>(url: T, dest_file: PathBuf) -> Re
 pub async fn download_update_to_temp_file(
     bin_name: String,
     release: Release,
-) -> Result<(PathBuf, PathBuf), ()> {
-    let current_bin_path = std::env::current_exe().map_err(|_| ())?;
+) -> Result<(PathBuf, PathBuf), UpdateError> {
+    let current_bin_path = std::env::current_exe()?;
+    let bin_dir = current_bin_path
+        .parent()
+        .ok_or_else(|| UpdateError::Io("the executable has no parent directory".to_string()))?;
 
     // Path to download the new version to
-    let download_path = current_bin_path
-        .parent()
-        .ok_or(())?
-        .join(format!("tmp_{bin_name}"));
+    let download_path = bin_dir.join(format!("tmp_{bin_name}"));
 
     // Path to temporarily force rename current process to, se we can then
     // rename `download_path` to `current_bin_path` and then launch new version
     // cleanly as `current_bin_path`
-    let tmp_path = current_bin_path
-        .parent()
-        .ok_or(())?
-        .join(format!("tmp2_{bin_name}"));
+    let tmp_path = bin_dir.join(format!("tmp2_{bin_name}"));
 
     // MacOS and Linux release are gziped tarball
     #[cfg(not(target_os = "windows"))]
@@ -106,21 +239,27 @@ pub async fn download_update_to_temp_file(
             .iter()
             .find(|a| a.name == asset_name)
             .cloned()
-            .ok_or(())?;
+            .ok_or_else(|| UpdateError::MissingAsset(asset_name.clone()))?;
 
-        let archive_path = current_bin_path.parent().ok_or(())?.join(&asset_name);
+        let archive_path = bin_dir.join(&asset_name);
 
         if let Err(e) = download_file(asset.download_url, archive_path.clone()).await {
             error!("Couldn't download {NAME} update: {}", e);
-            return Err(());
+            return Err(e);
+        }
+
+        if let Err(e) = verify_checksum(&release, &asset_name, &archive_path).await {
+            error!("Couldn't verify {NAME} update: {}", e);
+            std::fs::remove_file(&archive_path)?;
+            return Err(e);
         }
 
-        if extract_binary_from_tar(&archive_path, &download_path).is_err() {
+        if let Err(e) = extract_binary_from_tar(&archive_path, &download_path) {
             error!("Couldn't extract {NAME} release tarball");
-            return Err(());
+            return Err(UpdateError::Corrupted(e.to_string()));
         }
 
-        std::fs::remove_file(&archive_path).map_err(|_| ())?;
+        std::fs::remove_file(&archive_path)?;
     }
 
     // For Windows we download the new binary directly
@@ -131,12 +270,24 @@ pub async fn download_update_to_temp_file(
             .iter()
             .find(|a| a.name == bin_name)
             .cloned()
-            .ok_or(())?;
+            .ok_or_else(|| UpdateError::MissingAsset(bin_name.clone()))?;
 
         if let Err(e) = download_file(asset.download_url, download_path.clone()).await {
             error!("Couldn't download {NAME} update: {}", e);
-            return Err(());
+            return Err(e);
         }
+
+        if let Err(e) = verify_checksum(&release, &bin_name, &download_path).await {
+            error!("Couldn't verify {NAME} update: {}", e);
+            std::fs::remove_file(&download_path)?;
+            return Err(e);
+        }
+    }
+
+    // Never swap the running binary for an empty file
+    if fs::metadata(&download_path)?.len() == 0 {
+        error!("[SelfUpdate] The downloaded binary is empty");
+        return Err(UpdateError::Corrupted("empty binary".to_string()));
     }
 
     // Make the file executable
@@ -144,11 +295,11 @@ pub async fn download_update_to_temp_file(
     {
         use std::os::unix::fs::PermissionsExt;
 
-        let mut permissions = fs::metadata(&download_path).map_err(|_| ())?.permissions();
+        let mut permissions = fs::metadata(&download_path)?.permissions();
         permissions.set_mode(0o755);
         if let Err(e) = fs::set_permissions(&download_path, permissions) {
             error!("[SelfUpdate] Couldn't set permission to temp file: {}", e);
-            return Err(());
+            return Err(e.into());
         }
     }
 
@@ -157,14 +308,14 @@ pub async fn download_update_to_temp_file(
             "[SelfUpdate] Couldn't rename from current to temporary binary path: {}",
             e
         );
-        return Err(());
+        return Err(UpdateError::Io(e));
     }
     if let Err(e) = rename(&download_path, &current_bin_path) {
         error!(
             "[SelfUpdate] Couldn't rename from downloaded to current binary path: {}",
             e
         );
-        return Err(());
+        return Err(UpdateError::Io(e));
     }
 
     Ok((current_bin_path, tmp_path))
@@ -303,3 +454,40 @@ where
     )
     .map_err(|e| e.to_string())
 }
+
+#[cfg(all(test, feature = "self-update"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limit() {
+        let headers = |list: &'static [(&str, &'static str)]| {
+            move |name: &str| list.iter().find(|(n, _)| *n == name).map(|(_, v)| *v)
+        };
+        let primary = headers(&[
+            ("X-RateLimit-Remaining", "0"),
+            ("X-RateLimit-Reset", "1700000060"),
+        ]);
+        assert!(is_rate_limited(403, primary));
+        assert_eq!(rate_limit_delay(primary, 1_700_000_000), Some(60));
+        // Forbidden for another reason
+        assert!(!is_rate_limited(
+            403,
+            headers(&[("X-RateLimit-Remaining", "12")])
+        ));
+        assert!(is_rate_limited(429, headers(&[])));
+        assert_eq!(
+            rate_limit_delay(headers(&[("Retry-After", "30")]), 1_700_000_000),
+            Some(30)
+        );
+        assert_eq!(rate_limit_delay(headers(&[]), 1_700_000_000), None);
+    }
+
+    #[test]
+    fn test_sha256_hex() {
+        assert_eq!(
+            sha256_hex(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+}
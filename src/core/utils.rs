@@ -1,12 +1,23 @@
-use crate::core::sync::{hashset_system_packages, list_all_system_packages, User};
-use crate::core::theme::Theme;
-use crate::core::uad_lists::{PackageHashMap, PackageState, Removal, UadList};
+use crate::core::config::Config;
+use crate::core::sync::{hashset_system_packages, list_all_system_packages, Phone, User};
+use crate::core::theme::{Theme, CUSTOM_PALETTE};
+use crate::core::uad_lists::{
+    heuristic_vendor, PackageHashMap, PackageState, Removal, UadList, LIST_FNAME,
+};
 use crate::gui::widgets::package_row::PackageRow;
+use crate::CACHE_DIR;
 use chrono::offset::Utc;
 use chrono::{DateTime, Local};
 use csv::Writer;
-use std::path::PathBuf;
+use iced::futures::channel::mpsc;
+use iced::futures::stream::{self, Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::thread;
+use std::time::{Duration, Instant};
 use std::{fmt, fs};
 
 /// Canonical shortened name of the application
@@ -18,13 +29,20 @@ pub const NAME: &str = "UAD-ng";
 pub const ANDROID_SERIAL: &str = "ANDROID_SERIAL";
 pub const EXPORT_FILE_NAME: &str = "selection_export.txt";
 pub const UNINSTALLED_PACKAGES_FILE_NAME: &str = "uninstalled_packages";
+pub const BUG_REPORT_FILE_NAME: &str = "bug_report";
 
 #[derive(Debug, Clone)]
 pub enum Error {
     DialogClosed,
 }
 
-pub fn fetch_packages(uad_lists: &PackageHashMap, user_id: Option<&User>) -> Vec<PackageRow> {
+/// `show_absent` also lists the packages of `uad_lists` that aren't on the device,
+/// with [`PackageRow::on_device`] unset.
+pub fn fetch_packages(
+    uad_lists: &PackageHashMap,
+    user_id: Option<&User>,
+    show_absent: bool,
+) -> Vec<PackageRow> {
     let all_system_packages = list_all_system_packages(user_id); // installed and uninstalled packages
     let enabled_system_packages = hashset_system_packages(PackageState::Enabled, user_id);
     let disabled_system_packages = hashset_system_packages(PackageState::Disabled, user_id);
@@ -32,20 +50,31 @@ pub fn fetch_packages(uad_lists: &PackageHashMap, user_id: Option<&User>) -> Vec
     let mut uad_list;
     let mut state;
     let mut removal;
+    let mut heuristic;
     let mut user_package: Vec<PackageRow> = Vec::new();
 
-    for p_name in all_system_packages.lines() {
+    for (device_order, p_name) in all_system_packages.lines().enumerate() {
         state = PackageState::Uninstalled;
-        description = "[No description]: CONTRIBUTION WELCOMED";
+        description = "[No description]: CONTRIBUTION WELCOMED".to_string();
         uad_list = UadList::Unlisted;
         removal = Removal::Unlisted;
+        heuristic = false;
 
         if let Some(package) = uad_lists.get(p_name) {
             if !package.description.is_empty() {
-                description = &package.description;
+                description.clone_from(&package.description);
             }
             uad_list = package.list;
             removal = package.removal;
+        } else if let Some(vendor) = heuristic_vendor(p_name) {
+            // Not curated: the category stays `Unlisted`, only a hint is given
+            heuristic = true;
+            description = format!(
+                "[Heuristic, not curated]: likely removable.\n\
+                This package comes from {vendor}, whose pre-installed apps are often bloatware, \
+                but it isn't documented in the {NAME} lists yet. \
+                Research it before removing it. CONTRIBUTION WELCOMED"
+            );
         }
 
         if enabled_system_packages.contains(p_name) {
@@ -54,19 +83,55 @@ pub fn fetch_packages(uad_lists: &PackageHashMap, user_id: Option<&User>) -> Vec
             state = PackageState::Disabled;
         }
 
-        let package_row =
-            PackageRow::new(p_name, state, description, uad_list, removal, false, false);
+        let mut package_row =
+            PackageRow::new(p_name, state, &description, uad_list, removal, false, false);
+        package_row.heuristic = heuristic;
+        package_row.device_order = device_order;
         user_package.push(package_row);
     }
-    user_package.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
+
+    if show_absent {
+        let on_device: HashSet<&str> = all_system_packages.lines().collect();
+        for (p_name, package) in uad_lists
+            .iter()
+            .filter(|(name, _)| !on_device.contains(name.as_str()))
+        {
+            let mut package_row = PackageRow::new(
+                p_name,
+                PackageState::Uninstalled,
+                &package.description,
+                package.list,
+                package.removal,
+                false,
+                false,
+            );
+            package_row.on_device = false;
+            user_package.push(package_row);
+        }
+    }
+    sort_packages(&mut user_package);
     user_package
 }
 
+/// Sort by name, case-insensitively, whatever order `pm` and the lists gave.
+/// Names only differing by case are ordered too: the order must be the same
+/// across refreshes, and across users since rows of a package share their index.
+fn sort_packages(packages: &mut [PackageRow]) {
+    packages.sort_unstable_by(|a, b| {
+        a.name
+            .to_lowercase()
+            .cmp(&b.name.to_lowercase())
+            .then_with(|| a.name.cmp(&b.name))
+    });
+}
+
 pub fn string_to_theme(theme: &str) -> Theme {
     match theme {
         "Dark" => Theme::Dark,
         "Light" => Theme::Light,
         "Lupin" => Theme::Lupin,
+        "High contrast" => Theme::HighContrast,
+        "Custom" if CUSTOM_PALETTE.is_some() => Theme::Custom,
         // Auto uses `Display`, so it doesn't have a canonical repr
         t if t.starts_with("Auto") => Theme::Auto,
         _ => Theme::default(),
@@ -82,6 +147,23 @@ pub fn setup_uad_dir(dir: &PathBuf) -> PathBuf {
     dir
 }
 
+/// Stream the time every `interval`, the first time once `interval` has passed.
+/// The executor of iced has no timer, so this sleeps in a thread of its own:
+/// it stops at the first tick after the stream got dropped.
+pub fn ticks(interval: Duration) -> impl Stream<Item = Instant> {
+    let (sender, receiver) = mpsc::unbounded();
+    stream::once(async move {
+        thread::spawn(move || loop {
+            thread::sleep(interval);
+            if sender.unbounded_send(Instant::now()).is_err() {
+                break; // nobody is listening anymore
+            }
+        });
+        receiver
+    })
+    .flatten()
+}
+
 pub fn open_url(dir: PathBuf) {
     #[cfg(target_os = "windows")]
     let output = Command::new("explorer").args([dir]).output();
@@ -95,7 +177,7 @@ pub fn open_url(dir: PathBuf) {
     match output {
         Ok(o) => {
             if !o.status.success() {
-                let stderr = String::from_utf8(o.stderr).unwrap().trim_end().to_string();
+                let stderr = String::from_utf8_lossy(&o.stderr).trim_end().to_string();
                 error!("Can't open the following URL: {}", stderr);
             }
         }
@@ -197,7 +279,7 @@ pub async fn export_packages(
 
     let uninstalled_packages: Vec<&PackageRow> = phone_packages[user.index]
         .iter()
-        .filter(|p| p.state == PackageState::Uninstalled)
+        .filter(|p| p.on_device && p.state == PackageState::Uninstalled)
         .collect();
 
     for package in uninstalled_packages {
@@ -209,3 +291,388 @@ pub async fn export_packages(
 
     Ok(true)
 }
+
+/// Package fields a search term is matched against
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SearchField {
+    Name,
+    Description,
+    #[default]
+    Both,
+}
+
+impl SearchField {
+    pub const ALL: [Self; 3] = [Self::Both, Self::Name, Self::Description];
+}
+
+impl fmt::Display for SearchField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Name => "Name",
+                Self::Description => "Description",
+                Self::Both => "Name + description",
+            }
+        )
+    }
+}
+
+/// Order of the package list. Ties keep the default order, by name.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SortBy {
+    #[default]
+    Name,
+    /// Order of `pm list packages`
+    DeviceOrder,
+    Removal,
+    State,
+}
+
+impl SortBy {
+    pub const ALL: [Self; 4] = [Self::Name, Self::DeviceOrder, Self::Removal, Self::State];
+}
+
+impl fmt::Display for SortBy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::DeviceOrder => "Device order",
+                Self::Name => "Sort by name",
+                Self::Removal => "Sort by removal",
+                Self::State => "Sort by state",
+            }
+        )
+    }
+}
+
+/// Human-readable size, in powers of 1024 (e.g. `342 MB`, `1.4 GB`)
+#[allow(clippy::cast_precision_loss, reason = "Only displayed with 1 decimal")]
+pub fn format_size(bytes: u64) -> String {
+    const KB: u64 = 1024;
+    const MB: u64 = KB * 1024;
+    const GB: u64 = MB * 1024;
+    match bytes {
+        b if b >= GB => format!("{:.1} GB", b as f64 / GB as f64),
+        b if b >= MB => format!("{} MB", b / MB),
+        b => format!("{} KB", b / KB),
+    }
+}
+
+/// Case-insensitive search. An empty `term` matches everything.
+pub fn matches_search(name: &str, description: &str, term: &str, field: SearchField) -> bool {
+    let term = term.to_lowercase();
+    let in_name = || name.to_lowercase().contains(&term);
+    let in_description = || description.to_lowercase().contains(&term);
+    match field {
+        SearchField::Name => in_name(),
+        SearchField::Description => in_description(),
+        SearchField::Both => in_name() || in_description(),
+    }
+}
+
+/// `0, 10 (protected)`
+fn format_users(phone: &Phone) -> String {
+    phone
+        .user_list
+        .iter()
+        .map(|u| {
+            if u.protected {
+                format!("{} (protected)", u.id)
+            } else {
+                u.id.to_string()
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+/// Build a markdown report of the device and the state of its recommended packages,
+/// ready to be pasted in a GitHub issue.
+pub fn device_report(phone: &Phone, phone_packages: &[Vec<PackageRow>]) -> String {
+    let mut report = format!(
+        "## Device report\n\n\
+        - **{NAME} version:** v{}\n\
+        - **Device:** {}\n\
+        - **Android SDK:** {}\n\
+        - **Users:** {}\n\n",
+        env!("CARGO_PKG_VERSION"),
+        phone.model,
+        phone.android_sdk,
+        format_users(phone),
+    );
+
+    // `phone_packages` has a single entry when the device has a single user
+    let users: Vec<User> = if phone.user_list.len() > 1 {
+        phone.user_list.clone()
+    } else {
+        vec![phone.user_list.first().copied().unwrap_or_default()]
+    };
+
+    report.push_str("### Recommended packages\n\n| Package |");
+    for u in &users {
+        let _ = write!(report, " {u} |");
+    }
+    report.push_str("\n|---|");
+    report.push_str(&"---|".repeat(users.len()));
+    report.push('\n');
+
+    let Some(first_user) = phone_packages.first() else {
+        return report;
+    };
+    for (i, package) in first_user
+        .iter()
+        .enumerate()
+        .filter(|(_, p)| p.on_device && p.removal == Removal::Recommended)
+    {
+        let _ = write!(report, "| `{}` |", package.name);
+        for u in &users {
+            let state = phone_packages
+                .get(if users.len() > 1 { u.index } else { 0 })
+                .and_then(|packages| packages.get(i))
+                .map_or_else(|| "?".to_string(), |p| p.state.to_string());
+            let _ = write!(report, " {state} |");
+        }
+        report.push('\n');
+    }
+    report
+}
+
+/// Bundle everything maintainers ask for in an issue: versions, device details,
+/// the effective config and the logs.
+/// With `redact_serial`, the serials of the device and of the devices of the config
+/// are replaced everywhere, logs included.
+pub fn bug_report(
+    phone: &Phone,
+    adb_version: Option<&str>,
+    list_date: DateTime<Utc>,
+    config: &Config,
+    logs: &str,
+    redact_serial: bool,
+) -> String {
+    let config_toml = toml::to_string_pretty(config)
+        .unwrap_or_else(|e| format!("Can't serialize the config: {e}"));
+    let report = format!(
+        "{NAME} bug report\n\n\
+        {NAME} version: v{}\n\
+        Package list: v{}\n\
+        ADB version: {}\n\
+        OS: {}\n\
+        Device: {}\n\
+        Serial: {}\n\
+        Android SDK: {}\n\
+        Users: {}\n\n\
+        ===== Config =====\n{config_toml}\n\
+        ===== Logs =====\n{logs}",
+        env!("CARGO_PKG_VERSION"),
+        list_date.format("%Y%m%d"),
+        adb_version.unwrap_or("unknown"),
+        std::env::consts::OS,
+        phone.model,
+        phone.adb_id,
+        phone.android_sdk,
+        format_users(phone),
+    );
+    if !redact_serial {
+        return report;
+    }
+    std::iter::once(&phone.adb_id)
+        .chain(config.devices.iter().map(|d| &d.device_id))
+        .filter(|serial| !serial.is_empty())
+        .fold(report, |report, serial| {
+            report.replace(serial, "<redacted>")
+        })
+}
+
+/// [`bug_report`] with today's logs and the config file, for the About view and `--diagnose`.
+/// The log file can be big: not to be called from the UI thread.
+pub fn collect_bug_report(phone: &Phone, adb_version: Option<&str>, redact_serial: bool) -> String {
+    let logs = fs::read_to_string(crate::log_path())
+        .unwrap_or_else(|e| format!("Can't read the log file: {e}"));
+    bug_report(
+        phone,
+        adb_version,
+        last_modified_date(CACHE_DIR.join(LIST_FNAME)),
+        &Config::load_configuration_file(),
+        &logs,
+        redact_serial,
+    )
+}
+
+/// Write `report` in `folder`, named after the current time
+pub fn write_bug_report(folder: &Path, report: &str) -> Result<PathBuf, String> {
+    let path = folder.join(format!(
+        "{NAME}_{BUG_REPORT_FILE_NAME}_{}.txt",
+        Local::now().format("%Y%m%d_%H%M%S")
+    ));
+    fs::write(&path, report).map_err(|e| e.to_string())?;
+    Ok(path)
+}
+
+/// Save `report` in a folder picked by the user
+pub async fn save_bug_report(report: String) -> Result<PathBuf, String> {
+    let folder = open_folder()
+        .await
+        .map_err(|_| "no folder selected".to_string())?;
+    write_bug_report(&folder, &report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::config::DeviceSettings;
+
+    #[test]
+    fn test_sort_packages() {
+        let rows = |names: &[&str]| {
+            names
+                .iter()
+                .map(|name| {
+                    PackageRow::new(
+                        name,
+                        PackageState::Enabled,
+                        "",
+                        UadList::Unlisted,
+                        Removal::Unlisted,
+                        false,
+                        false,
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        let names =
+            |packages: &[PackageRow]| packages.iter().map(|p| p.name.clone()).collect::<Vec<_>>();
+        let mut first = rows(&["com.b", "com.A", "com.a", "android", "Com.a"]);
+        let mut second = rows(&["com.a", "android", "Com.a", "com.b", "com.A"]);
+        sort_packages(&mut first);
+        sort_packages(&mut second);
+        assert_eq!(names(&first), names(&second));
+        assert_eq!(
+            names(&first),
+            ["android", "Com.a", "com.A", "com.a", "com.b"]
+        );
+    }
+
+    #[test]
+    fn test_device_report() {
+        let phone = Phone {
+            model: "Google Pixel".to_string(),
+            android_sdk: 34,
+            user_list: vec![
+                User {
+                    id: 0,
+                    index: 0,
+                    protected: false,
+                },
+                User {
+                    id: 10,
+                    index: 1,
+                    protected: true,
+                },
+            ],
+            adb_id: "serial".to_string(),
+            has_root: None,
+            unauthorized: false,
+        };
+        let row = |state| {
+            PackageRow::new(
+                "com.example",
+                state,
+                "",
+                UadList::Google,
+                Removal::Recommended,
+                false,
+                false,
+            )
+        };
+        let report = device_report(
+            &phone,
+            &[
+                vec![row(PackageState::Uninstalled)],
+                vec![row(PackageState::Enabled)],
+            ],
+        );
+        assert!(report.contains("- **Android SDK:** 34"));
+        assert!(report.contains("- **Users:** 0, 10 (protected)"));
+        assert!(report.contains("| `com.example` | Uninstalled | Enabled |"));
+        assert!(!report.contains("serial"));
+    }
+
+    #[test]
+    fn test_bug_report() {
+        let phone = Phone {
+            model: "Google Pixel".to_string(),
+            android_sdk: 34,
+            user_list: vec![User::default()],
+            adb_id: "R58M123".to_string(),
+            has_root: None,
+            unauthorized: false,
+        };
+        let logs = "DEBUG [sync.rs:42] ANDROID_SERIAL=R58M123 adb shell pm list packages";
+        let config = Config {
+            devices: vec![DeviceSettings {
+                device_id: "emulator-5554".to_string(),
+                ..DeviceSettings::default()
+            }],
+            ..Config::default()
+        };
+        let report = bug_report(&phone, Some("1.0.41"), Utc::now(), &config, logs, false);
+        assert!(report.contains("ADB version: 1.0.41"));
+        assert!(report.contains("Android SDK: 34"));
+        assert!(report.contains("Users: 0"));
+        assert!(report.contains("===== Config =====\n[general]"));
+        assert!(report.contains("emulator-5554"));
+        assert!(report.ends_with(logs));
+
+        let redacted = bug_report(&phone, None, Utc::now(), &config, logs, true);
+        assert!(redacted.contains("ADB version: unknown"));
+        assert!(redacted.contains("Serial: <redacted>"));
+        assert!(!redacted.contains("R58M123"));
+        assert!(!redacted.contains("emulator-5554"));
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(0), "0 KB");
+        assert_eq!(format_size(148_212 * 1024), "144 MB");
+        assert_eq!(format_size(1536 * 1024 * 1024), "1.5 GB");
+    }
+
+    #[test]
+    fn test_matches_search() {
+        let desc = "Samsung Bixby voice assistant";
+        assert!(matches_search(
+            "com.samsung.bixby",
+            desc,
+            "",
+            SearchField::Name
+        ));
+        assert!(matches_search(
+            "com.samsung.bixby",
+            desc,
+            "Bixby",
+            SearchField::Name
+        ));
+        assert!(matches_search(
+            "com.samsung.bixby",
+            desc,
+            "voice",
+            SearchField::Both
+        ));
+        assert!(!matches_search(
+            "com.samsung.bixby",
+            desc,
+            "voice",
+            SearchField::Name
+        ));
+        assert!(!matches_search(
+            "com.samsung.bixby",
+            desc,
+            "com.",
+            SearchField::Description
+        ));
+    }
+}
@@ -2,15 +2,25 @@ pub mod style;
 pub mod views;
 pub mod widgets;
 
-use crate::core::sync::{get_devices_list, initial_load, perform_adb_commands, CommandType, Phone};
+use crate::core::diagnostics::run_diagnostics;
+use crate::core::sync::{
+    check_adb, device_list_changes, get_authorized_devices_list, get_devices_list,
+    get_target_device, perform_adb_commands, AdbStatus, CommandType, Phone, RebootTarget,
+};
 use crate::core::theme::{Theme, OS_COLOR_SCHEME};
 use crate::core::uad_lists::UadListState;
-use crate::core::update::{get_latest_release, Release, SelfUpdateState, SelfUpdateStatus};
-use crate::core::utils::{string_to_theme, ANDROID_SERIAL, NAME};
+use crate::core::update::{
+    get_latest_release, Release, SelfUpdateState, SelfUpdateStatus, UpdateError,
+};
+use crate::core::utils::{
+    collect_bug_report, device_report, save_bug_report, string_to_theme, ticks, ANDROID_SERIAL,
+    NAME,
+};
 
 use iced::advanced::graphics::image::image_rs::ImageFormat;
-use iced::font;
+use iced::futures::StreamExt;
 use iced::window::icon;
+use iced::{clipboard, font, subscription};
 use views::about::{About as AboutView, Message as AboutMessage};
 use views::list::{List as AppsView, LoadingState as ListLoadingState, Message as AppsMessage};
 use views::settings::{Message as SettingsMessage, Settings as SettingsView};
@@ -19,11 +29,12 @@ use widgets::navigation_menu::nav_menu;
 use iced::widget::column;
 use iced::{
     window::Settings as Window, Alignment, Application, Command, Element, Length, Renderer,
-    Settings,
+    Settings, Subscription,
 };
 use std::env;
 #[cfg(feature = "self-update")]
 use std::path::PathBuf;
+use std::time::Duration;
 
 #[cfg(feature = "self-update")]
 use crate::core::update::{bin_name, download_update_to_temp_file, remove_file};
@@ -53,7 +64,18 @@ pub struct UadGui {
     selected_device: Option<Phone>,
     update_state: UpdateState,
     nb_running_async_adb_commands: u32,
-    adb_satisfied: bool,
+    /// Number of packages of the backup being restored
+    restore_total: u32,
+    adb_status: AdbStatus,
+    /// Serial given with `--device`, selected instead of the first device
+    requested_device: Option<String>,
+    /// Device list changes seen so far, to only act on the last one of a burst
+    device_list_changes: u32,
+    /// Whether the last device list change is waiting for [`DEVICE_LIST_DEBOUNCE`]
+    device_list_settling: bool,
+    /// Serial of the selected device while it's disconnected,
+    /// with the loading state of the list to go back to when it returns
+    disconnected_device: Option<(String, ListLoadingState)>,
 }
 
 #[derive(Debug, Clone)]
@@ -69,33 +91,43 @@ pub enum Message {
     RefreshButtonPressed,
     RebootButtonPressed,
     LoadDevices(Vec<Phone>),
+    /// A device was connected, disconnected or changed status
+    DeviceListChanged,
+    /// No other change came within [`DEVICE_LIST_DEBOUNCE`] of this one
+    DeviceListSettled(u32),
+    DevicesWatched(Vec<Phone>),
     #[cfg(feature = "self-update")]
-    _NewReleaseDownloaded(Result<(PathBuf, PathBuf), ()>),
+    _NewReleaseDownloaded(Result<(PathBuf, PathBuf), UpdateError>),
     GetLatestRelease(Result<Option<Release>, ()>),
     FontLoaded(Result<(), iced::font::Error>),
     Nothing,
-    ADBSatisfied(bool),
+    ADBSatisfied(AdbStatus),
 }
 
+/// Authorizing a device makes it go from `unauthorized` to `device` in quick succession
+const DEVICE_LIST_DEBOUNCE: Duration = Duration::from_secs(1);
+
 impl Application for UadGui {
     type Theme = Theme;
     type Executor = iced::executor::Default;
     type Message = Message;
-    type Flags = ();
+    type Flags = Option<String>;
 
-    fn new(_flags: ()) -> (Self, Command<Message>) {
+    fn new(requested_device: Option<String>) -> (Self, Command<Message>) {
+        let mut gui = Self {
+            requested_device,
+            ..Self::default()
+        };
+        let check_release = gui.check_latest_release();
         (
-            Self::default(),
+            gui,
             Command::batch([
                 // Used in crate::gui::widgets::navigation_menu::ICONS. Name is `icomoon`.
                 font::load(include_bytes!("../../resources/assets/icons.ttf").as_slice())
                     .map(Message::FontLoaded),
-                Command::perform(initial_load(), Message::ADBSatisfied),
+                Command::perform(check_adb(), Message::ADBSatisfied),
                 Command::perform(get_devices_list(), Message::LoadDevices),
-                Command::perform(
-                    async move { get_latest_release() },
-                    Message::GetLatestRelease,
-                ),
+                check_release,
             ]),
         )
     }
@@ -107,6 +139,24 @@ impl Application for UadGui {
     fn title(&self) -> String {
         String::from("Universal Android Debloater Next Generation")
     }
+
+    fn subscription(&self) -> Subscription<Message> {
+        // Restarted by each change, so it only fires once the list stopped changing
+        let change = self.device_list_changes;
+        let settle = if self.device_list_settling {
+            subscription::run_with_id(("device-list-settle", change), ticks(DEVICE_LIST_DEBOUNCE))
+                .map(move |_| Message::DeviceListSettled(change))
+        } else {
+            Subscription::none()
+        };
+        Subscription::batch([
+            self.apps_view.subscription().map(Message::AppsAction),
+            // The first list is skipped: devices are loaded on startup
+            subscription::run_with_id("device-list-changes", device_list_changes().skip(1))
+                .map(|_| Message::DeviceListChanged),
+            settle,
+        ])
+    }
     // TODO: refactor later
     #[allow(clippy::too_many_lines)]
     fn update(&mut self, msg: Message) -> Command<Message> {
@@ -121,10 +171,29 @@ impl Application for UadGui {
                             .find(|phone| phone.adb_id == s_device.adb_id)
                             .cloned()
                     }
-                    None => devices_list.first().cloned(),
+                    None => {
+                        match get_target_device(&devices_list, self.requested_device.as_deref()) {
+                            Ok(phone) => phone.cloned(),
+                            Err(e) => {
+                                error!("{e}");
+                                self.devices_list = devices_list;
+                                self.apps_view.loading_state = ListLoadingState::DeviceNotFound(e);
+                                return Command::none();
+                            }
+                        }
+                    }
                 };
                 self.devices_list = devices_list;
 
+                if self
+                    .selected_device
+                    .as_ref()
+                    .is_some_and(|d| d.unauthorized)
+                {
+                    self.apps_view.loading_state = ListLoadingState::FindingPhones;
+                    return Command::perform(get_authorized_devices_list(), Message::LoadDevices);
+                }
+
                 #[allow(unused_must_use)]
                 {
                     self.update(Message::SettingsAction(SettingsMessage::LoadDeviceSettings));
@@ -132,6 +201,66 @@ impl Application for UadGui {
 
                 self.update(Message::AppsAction(AppsMessage::LoadUadList(true)))
             }
+            Message::DeviceListChanged => {
+                self.device_list_changes += 1;
+                self.device_list_settling = true;
+                Command::none()
+            }
+            Message::DeviceListSettled(change)
+                if self.device_list_settling && change == self.device_list_changes =>
+            {
+                self.device_list_settling = false;
+                Command::perform(get_devices_list(), Message::DevicesWatched)
+            }
+            Message::DeviceListSettled(_) => Command::none(),
+            Message::DevicesWatched(devices_list) => {
+                if let Some((serial, _)) = &self.disconnected_device {
+                    let Some(phone) = devices_list.iter().find(|d| d.adb_id == *serial) else {
+                        self.devices_list = devices_list;
+                        return Command::none();
+                    };
+                    info!("Device {serial} is back, selecting it again");
+                    self.selected_device = Some(phone.clone());
+                    // The list is as it was: selection, batch and failed changes included
+                    if let Some((_, list_state @ ListLoadingState::Ready)) =
+                        self.disconnected_device.take()
+                    {
+                        self.apps_view.loading_state = list_state;
+                        self.devices_list = devices_list;
+                        return Command::none();
+                    }
+                    // It went away before its packages were loaded
+                    self.apps_view = AppsView::default();
+                    return self.update(Message::LoadDevices(devices_list));
+                }
+                if let Some(gone) = self
+                    .selected_device
+                    .take_if(|s| devices_list.iter().all(|d| d.adb_id != s.adb_id))
+                {
+                    warn!("Device {} disconnected", gone.adb_id);
+                    // Only the loading state changes, the list is kept for when it returns
+                    let list_state = std::mem::replace(
+                        &mut self.apps_view.loading_state,
+                        ListLoadingState::DeviceDisconnected(gone.adb_id.clone()),
+                    );
+                    self.disconnected_device = Some((gone.adb_id, list_state));
+                    self.devices_list = devices_list;
+                    return Command::none();
+                }
+                let still_there = self.selected_device.as_ref().is_some_and(|selected| {
+                    devices_list.iter().any(|d| {
+                        d.adb_id == selected.adb_id && d.unauthorized == selected.unauthorized
+                    })
+                });
+                if still_there || (self.selected_device.is_none() && devices_list.is_empty()) {
+                    self.devices_list = devices_list;
+                    return Command::none();
+                }
+                info!("Device list changed, reloading");
+                // The selected device is kept when it's still there (e.g. it got authorized),
+                // else the first (or `--device`) device is selected
+                self.update(Message::LoadDevices(devices_list))
+            }
             Message::AppsPress => {
                 self.view = View::List;
                 Command::none()
@@ -139,45 +268,72 @@ impl Application for UadGui {
             Message::AboutPressed => {
                 self.view = View::About;
                 self.update_state.self_update = SelfUpdateState::default();
-                Command::perform(
-                    async move { get_latest_release() },
-                    Message::GetLatestRelease,
-                )
+                self.check_latest_release()
             }
             Message::SettingsPressed => {
                 self.view = View::Settings;
-                Command::none()
+                self.update(Message::SettingsAction(SettingsMessage::FetchDeviceInfo))
             }
             Message::RefreshButtonPressed => {
                 self.apps_view = AppsView::default();
-                #[allow(unused_must_use)]
-                {
-                    self.update(Message::AppsAction(AppsMessage::ADBSatisfied(
-                        self.adb_satisfied,
-                    )));
-                }
-                Command::perform(get_devices_list(), Message::LoadDevices)
+                self.disconnected_device = None;
+                // adb may have been installed or fixed in the meantime
+                Command::batch([
+                    Command::perform(check_adb(), Message::ADBSatisfied),
+                    Command::perform(get_devices_list(), Message::LoadDevices),
+                ])
             }
-            Message::RebootButtonPressed => {
-                self.apps_view = AppsView::default();
-                self.selected_device = None;
-                self.devices_list = vec![];
-                Command::perform(
-                    perform_adb_commands("reboot".to_string(), CommandType::Shell),
-                    |_| Message::Nothing,
-                )
+            Message::RebootButtonPressed => self.reboot(RebootTarget::System),
+            Message::AppsAction(msg) => {
+                // The list view records what it learns about the device (e.g. root access)
+                let mut no_device = Phone::default();
+                self.apps_view
+                    .update(
+                        &mut self.settings_view,
+                        self.selected_device.as_mut().unwrap_or(&mut no_device),
+                        &mut self.update_state.uad_list,
+                        msg,
+                    )
+                    .map(Message::AppsAction)
             }
-            Message::AppsAction(msg) => self
-                .apps_view
-                .update(
-                    &mut self.settings_view,
-                    &mut self.selected_device.clone().unwrap_or_default(),
-                    &mut self.update_state.uad_list,
-                    msg,
-                )
-                .map(Message::AppsAction),
             Message::SettingsAction(msg) => {
                 match msg {
+                    SettingsMessage::RebootConfirmed(target) => {
+                        #[allow(unused_must_use)]
+                        {
+                            self.settings_view.update(
+                                &self.selected_device.clone().unwrap_or_default(),
+                                &self.apps_view.phone_packages,
+                                &mut self.nb_running_async_adb_commands,
+                                msg,
+                                self.apps_view.selected_user,
+                            );
+                        }
+                        self.view = View::List;
+                        return self.reboot(target);
+                    }
+                    SettingsMessage::RestoreDevice => {
+                        let restore = self
+                            .settings_view
+                            .update(
+                                &self.selected_device.clone().unwrap_or_default(),
+                                &self.apps_view.phone_packages,
+                                &mut self.nb_running_async_adb_commands,
+                                msg,
+                                self.apps_view.selected_user,
+                            )
+                            .map(Message::SettingsAction);
+                        self.restore_total = self.nb_running_async_adb_commands;
+                        if self.restore_total > 0 {
+                            self.view = View::List;
+                            self.apps_view.loading_state = ListLoadingState::RestoringDevice {
+                                package: "starting".to_string(),
+                                done: 0,
+                                total: self.restore_total,
+                            };
+                        }
+                        return restore;
+                    }
                     SettingsMessage::RestoringDevice(ref output) => {
                         self.nb_running_async_adb_commands -= 1;
                         self.view = View::List;
@@ -188,13 +344,37 @@ impl Application for UadGui {
                                 &mut self.settings_view,
                                 &mut self.selected_device.clone().unwrap_or_default(),
                                 &mut self.update_state.uad_list,
-                                AppsMessage::RestoringDevice(output.clone()),
+                                AppsMessage::RestoringDevice {
+                                    output: output.clone(),
+                                    done: self.restore_total - self.nb_running_async_adb_commands,
+                                    total: self.restore_total,
+                                },
                             );
                         }
                         if self.nb_running_async_adb_commands == 0 {
                             return self.update(Message::RefreshButtonPressed);
                         }
                     }
+                    SettingsMessage::ShowAbsentPackages(_) | SettingsMessage::RestoreAllDone(_) => {
+                        // The package list is reloaded to add or remove absent packages,
+                        // or to show the new state of restored ones
+                        let settings_cmd = self
+                            .settings_view
+                            .update(
+                                &self.selected_device.clone().unwrap_or_default(),
+                                &self.apps_view.phone_packages,
+                                &mut self.nb_running_async_adb_commands,
+                                msg,
+                                self.apps_view.selected_user,
+                            )
+                            .map(Message::SettingsAction);
+                        let reload =
+                            self.update(Message::AppsAction(AppsMessage::LoadPhonePackages((
+                                self.apps_view.uad_lists.clone(),
+                                self.update_state.uad_list,
+                            ))));
+                        return Command::batch([settings_cmd, reload]);
+                    }
                     SettingsMessage::MultiUserMode(toggled) if toggled => {
                         for user in self.apps_view.phone_packages.clone() {
                             for (i, _) in user.iter().filter(|&pkg| pkg.selected).enumerate() {
@@ -237,6 +417,7 @@ impl Application for UadGui {
                         if let Some(release) = self.update_state.self_update.latest_release.as_ref()
                         {
                             self.update_state.self_update.status = SelfUpdateStatus::Updating;
+                            self.update_state.self_update.error = None;
                             self.apps_view.loading_state = ListLoadingState::_UpdatingUad;
                             let bin_name = bin_name().to_owned();
                             Command::perform(
@@ -249,12 +430,44 @@ impl Application for UadGui {
                         #[cfg(not(feature = "self-update"))]
                         Command::none()
                     }
-                    AboutMessage::UrlPressed(_) => Command::none(),
+                    AboutMessage::CopyDeviceReport => {
+                        let phone = self.selected_device.clone().unwrap_or_default();
+                        clipboard::write(device_report(&phone, &self.apps_view.phone_packages))
+                    }
+                    AboutMessage::RunDiagnostics => Command::perform(run_diagnostics(), |c| {
+                        Message::AboutAction(AboutMessage::DiagnosticsDone(c))
+                    }),
+                    AboutMessage::GenerateBugReport => {
+                        let phone = self.selected_device.clone().unwrap_or_default();
+                        let adb_version = self.adb_status.version().map(str::to_string);
+                        let redact_serial = self.about_view.redact_serial;
+                        // The log file can be big: read it out of the UI thread
+                        Command::perform(
+                            async move {
+                                let report = collect_bug_report(
+                                    &phone,
+                                    adb_version.as_deref(),
+                                    redact_serial,
+                                );
+                                save_bug_report(report).await
+                            },
+                            |r| Message::AboutAction(AboutMessage::BugReportSaved(r)),
+                        )
+                    }
+                    AboutMessage::UrlPressed(_)
+                    | AboutMessage::DiagnosticsDone(_)
+                    | AboutMessage::RedactSerial(_)
+                    | AboutMessage::BugReportSaved(_) => Command::none(),
                 }
             }
             Message::DeviceSelected(s_device) => {
+                self.disconnected_device = None;
                 self.selected_device = Some(s_device.clone());
                 self.view = View::List;
+                if s_device.unauthorized {
+                    self.apps_view.loading_state = ListLoadingState::FindingPhones;
+                    return Command::perform(get_authorized_devices_list(), Message::LoadDevices);
+                }
                 env::set_var(ANDROID_SERIAL, s_device.adb_id);
                 info!("{:-^65}", "-");
                 info!(
@@ -279,43 +492,47 @@ impl Application for UadGui {
             Message::_NewReleaseDownloaded(res) => {
                 debug!("{NAME} update has been downloaded!");
 
-                if let Ok((relaunch_path, cleanup_path)) = res {
-                    let mut args: Vec<_> = std::env::args().skip(1).collect();
+                match res {
+                    Ok((relaunch_path, cleanup_path)) => {
+                        let mut args: Vec<_> = std::env::args().skip(1).collect();
 
-                    // Remove the `--self-update-temp` arg from args if it exists,
-                    // since we need to pass it cleanly. Otherwise new process will
-                    // fail during arg parsing.
-                    if let Some(idx) = args.iter().position(|a| a == "--self-update-temp") {
-                        args.remove(idx);
-                        // Remove path passed after this arg
-                        args.remove(idx);
-                    }
+                        // Remove the `--self-update-temp` arg from args if it exists,
+                        // since we need to pass it cleanly. Otherwise new process will
+                        // fail during arg parsing.
+                        if let Some(idx) = args.iter().position(|a| a == "--self-update-temp") {
+                            args.remove(idx);
+                            // Remove path passed after this arg
+                            args.remove(idx);
+                        }
 
-                    match std::process::Command::new(relaunch_path)
-                        .args(args)
-                        .arg("--self-update-temp")
-                        .arg(&cleanup_path)
-                        .spawn()
-                    {
-                        Ok(_) => {
-                            if let Err(e) = remove_file(cleanup_path) {
-                                error!("Could not remove temp update file: {}", e);
+                        match std::process::Command::new(relaunch_path)
+                            .args(args)
+                            .arg("--self-update-temp")
+                            .arg(&cleanup_path)
+                            .spawn()
+                        {
+                            Ok(_) => {
+                                if let Err(e) = remove_file(cleanup_path) {
+                                    error!("Could not remove temp update file: {}", e);
+                                }
+                                std::process::exit(0)
                             }
-                            std::process::exit(0)
-                        }
-                        Err(error) => {
-                            if let Err(e) = remove_file(cleanup_path) {
-                                error!("Could not remove temp update file: {}", e);
+                            Err(error) => {
+                                if let Err(e) = remove_file(cleanup_path) {
+                                    error!("Could not remove temp update file: {}", e);
+                                }
+                                error!("Failed to update {NAME}: {}", error);
                             }
-                            error!("Failed to update {NAME}: {}", error);
                         }
                     }
-                } else {
-                    error!("Failed to update {NAME}!");
-                    #[allow(unused_must_use)]
-                    {
-                        self.update(Message::AppsAction(AppsMessage::UpdateFailed));
-                        self.update_state.self_update.status = SelfUpdateStatus::Failed;
+                    Err(e) => {
+                        error!("Failed to update {NAME}: {e}");
+                        #[allow(unused_must_use)]
+                        {
+                            self.update(Message::AppsAction(AppsMessage::UpdateFailed));
+                            self.update_state.self_update.status = SelfUpdateStatus::Failed;
+                            self.update_state.self_update.error = Some(e);
+                        }
                     }
                 }
                 Command::none()
@@ -337,10 +554,10 @@ impl Application for UadGui {
 
                 Command::none()
             }
-            Message::ADBSatisfied(result) => {
-                self.adb_satisfied = result;
+            Message::ADBSatisfied(status) => {
+                self.adb_status = status;
                 self.update(Message::AppsAction(AppsMessage::ADBSatisfied(
-                    self.adb_satisfied,
+                    self.adb_status.clone(),
                 )))
             }
             Message::Nothing => Command::none(),
@@ -363,11 +580,16 @@ impl Application for UadGui {
                 .map(Message::AppsAction),
             View::About => self
                 .about_view
-                .view(&self.update_state)
+                .view(
+                    &self.update_state,
+                    self.selected_device.is_some(),
+                    &self.settings_view.general.list_source,
+                    self.settings_view.general.offline_mode,
+                )
                 .map(Message::AboutAction),
             View::Settings => self
                 .settings_view
-                .view(&selected_device, &self.apps_view)
+                .view(&selected_device, &self.devices_list, &self.apps_view)
                 .map(Message::SettingsAction),
         };
 
@@ -379,7 +601,31 @@ impl Application for UadGui {
 }
 
 impl UadGui {
-    pub fn start() -> iced::Result {
+    /// Look for a new release, unless in offline mode
+    fn check_latest_release(&mut self) -> Command<Message> {
+        if self.settings_view.general.offline_mode {
+            self.update_state.self_update.status = SelfUpdateStatus::Offline;
+            return Command::none();
+        }
+        Command::perform(
+            async move { get_latest_release() },
+            Message::GetLatestRelease,
+        )
+    }
+
+    /// The device disappears while rebooting: forget it until it's back
+    fn reboot(&mut self, target: RebootTarget) -> Command<Message> {
+        info!("Rebooting into {target}");
+        self.apps_view = AppsView::default();
+        self.selected_device = None;
+        self.devices_list = vec![];
+        Command::perform(
+            perform_adb_commands(target.command().to_string(), CommandType::Shell),
+            |_| Message::Nothing,
+        )
+    }
+
+    pub fn start(requested_device: Option<String>) -> iced::Result {
         let logo: &[u8] = match *OS_COLOR_SCHEME {
             // remember to keep `Default` in sync with `src/core/theme`
             dark_light::Mode::Dark | dark_light::Mode::Default => {
@@ -403,6 +649,7 @@ impl UadGui {
                 ..iced::window::Settings::default()
             },
             default_text_size: iced::Pixels(16.0),
+            flags: requested_device,
             ..Settings::default()
         })
     }
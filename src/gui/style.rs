@@ -604,5 +604,10 @@ mod tests {
         //assert_ne!(palette.bright.surface, Color::BLACK);
         assert_ne!(palette.normal.error, Color::BLACK);
         assert_ne!(palette.bright.error, Color::BLACK);
+
+        let high_contrast = Theme::HighContrast.palette();
+        assert_ne!(high_contrast.bright.error, high_contrast.bright.secondary);
+        // Panels must stand out from the background
+        assert!(high_contrast.base.foreground.r - high_contrast.base.background.r > 0.25);
     }
 }
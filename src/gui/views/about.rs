@@ -1,10 +1,13 @@
+use crate::core::diagnostics::Check;
 use crate::core::helpers::button_primary;
 use crate::core::theme::Theme;
-use crate::core::uad_lists::LIST_FNAME;
-use crate::core::utils::{last_modified_date, open_url, NAME};
+use crate::core::uad_lists::{
+    is_stale, list_source, lists_updated_at, UadListState, LIST_FNAME, STALE_LISTS_DAYS,
+};
+use crate::core::utils::{format_diff_time_from_now, last_modified_date, open_url, NAME};
 use crate::gui::{style, UpdateState};
 use crate::CACHE_DIR;
-use iced::widget::{column, container, row, text, Space};
+use iced::widget::{checkbox, column, container, row, text, Column, Space, Text};
 use iced::{Alignment, Element, Length, Renderer};
 use std::path::PathBuf;
 
@@ -12,23 +15,56 @@ use std::path::PathBuf;
 use crate::core::update::SelfUpdateStatus;
 
 #[derive(Default, Debug, Clone)]
-pub struct About {}
+pub struct About {
+    diagnostics: Vec<Check>,
+    running_diagnostics: bool,
+    pub redact_serial: bool,
+    /// Outcome of the last "Generate bug report"
+    bug_report_status: Option<Result<PathBuf, String>>,
+}
 
 #[derive(Debug, Clone)]
 pub enum Message {
     UrlPressed(PathBuf),
     UpdateUadLists,
     DoSelfUpdate,
+    CopyDeviceReport,
+    RunDiagnostics,
+    DiagnosticsDone(Vec<Check>),
+    RedactSerial(bool),
+    GenerateBugReport,
+    BugReportSaved(Result<PathBuf, String>),
 }
 
 impl About {
     pub fn update(&mut self, msg: Message) {
-        if let Message::UrlPressed(url) = msg {
-            open_url(url);
+        match msg {
+            Message::UrlPressed(url) => open_url(url),
+            Message::RunDiagnostics => self.running_diagnostics = true,
+            Message::DiagnosticsDone(checks) => {
+                self.running_diagnostics = false;
+                self.diagnostics = checks;
+            }
+            Message::RedactSerial(redact) => self.redact_serial = redact,
+            Message::GenerateBugReport => self.bug_report_status = None,
+            Message::BugReportSaved(result) => {
+                match &result {
+                    Ok(path) => info!("Bug report saved to {}", path.display()),
+                    Err(e) => error!("Can't save the bug report: {e}"),
+                }
+                self.bug_report_status = Some(result);
+            }
+            _ => {}
         }
         // other events are handled by UadGui update()
     }
-    pub fn view(&self, update_state: &UpdateState) -> Element<Message, Theme, Renderer> {
+    pub fn view(
+        &self,
+        update_state: &UpdateState,
+        has_device: bool,
+        list_source_setting: &str,
+        offline: bool,
+    ) -> Element<Message, Theme, Renderer> {
         let about_text = text(format!("Universal Android Debloater Next Generation ({NAME}) is a free and open-source community project \naiming at simplifying the removal of pre-installed apps on any Android device."));
 
         let descr_container = container(about_text)
@@ -36,14 +72,11 @@ impl About {
             .padding(25)
             .style(style::Container::Frame);
 
-        let date = last_modified_date(CACHE_DIR.join(LIST_FNAME));
-        let uad_list_text =
-            text(format!("{NAME} package list: v{}", date.format("%Y%m%d"))).width(250);
-        let last_update_text = text(update_state.uad_list.to_string());
-        let uad_lists_btn = button_primary("Update").on_press(Message::UpdateUadLists);
-
         #[cfg(feature = "self-update")]
-        let self_update_btn = button_primary("Update").on_press(Message::DoSelfUpdate);
+        let self_update_btn = button_primary("Update").on_press_maybe(
+            (update_state.self_update.status != SelfUpdateStatus::Offline)
+                .then_some(Message::DoSelfUpdate),
+        );
 
         #[cfg(feature = "self-update")]
         let uad_version_text =
@@ -71,20 +104,15 @@ impl About {
             .spacing(10)
             .width(550);
 
-        let uad_list_row = row![uad_list_text, uad_lists_btn, last_update_text,]
-            .align_items(Alignment::Center)
-            .spacing(10)
-            .width(550);
+        let uad_lists = uad_lists_view(update_state.uad_list, list_source_setting, offline);
 
         #[cfg(feature = "self-update")]
-        let update_column = column![uad_list_row, self_update_row]
+        let update_column = column![uad_lists, self_update_row]
             .align_items(Alignment::Center)
             .spacing(10);
 
         #[cfg(not(feature = "self-update"))]
-        let update_column = column![uad_list_row]
-            .align_items(Alignment::Center)
-            .spacing(10);
+        let update_column = uad_lists;
 
         let update_container = container(update_column)
             .width(Length::Fill)
@@ -109,14 +137,36 @@ impl About {
             "https://github.com/Universal-Debloater-Alliance/universal-android-debloater/wiki",
         )));
 
-        let row = row![website_btn, wiki_btn, issue_btn, log_btn,].spacing(20);
+        let report_btn = if has_device {
+            button_primary("Copy device report").on_press(Message::CopyDeviceReport)
+        } else {
+            button_primary("Copy device report")
+        };
+
+        let diagnostics_btn = if self.running_diagnostics {
+            button_primary("Checking...")
+        } else {
+            button_primary("Run diagnostics").on_press(Message::RunDiagnostics)
+        };
+
+        let row = row![
+            website_btn,
+            wiki_btn,
+            issue_btn,
+            log_btn,
+            report_btn,
+            diagnostics_btn
+        ]
+        .spacing(20);
 
         let content = column![
             Space::new(Length::Fill, Length::Shrink),
             descr_container,
             update_container,
             row,
+            self.bug_report_view(),
         ]
+        .push_maybe(self.diagnostics_view())
         .width(Length::Fill)
         .spacing(20)
         .align_items(Alignment::Center);
@@ -127,4 +177,109 @@ impl About {
             .padding(10)
             .into()
     }
+
+    fn bug_report_view(&self) -> Element<'_, Message, Theme, Renderer> {
+        let bug_report_status = self.bug_report_status.as_ref().map(|status| match status {
+            Ok(path) => text(format!("Saved to {}", path.display())).style(style::Text::Ok),
+            Err(e) => text(format!("Bug report not saved: {e}")).style(style::Text::Danger),
+        });
+
+        row![
+            button_primary("Generate bug report").on_press(Message::GenerateBugReport),
+            checkbox("Redact the device serial", self.redact_serial)
+                .on_toggle(Message::RedactSerial)
+                .style(style::CheckBox::SettingsEnabled),
+        ]
+        .push_maybe(bug_report_status)
+        .spacing(20)
+        .align_items(Alignment::Center)
+        .into()
+    }
+
+    fn diagnostics_view(&self) -> Option<Element<'_, Message, Theme, Renderer>> {
+        if self.diagnostics.is_empty() {
+            return None;
+        }
+        let checks = self
+            .diagnostics
+            .iter()
+            .fold(Column::new().spacing(6), |col, c| {
+                let status = if c.passed {
+                    text("✓ PASS").style(style::Text::Ok)
+                } else {
+                    text("✗ FAIL").style(style::Text::Danger)
+                };
+                col.push(row![status, text(format!("{}: {}", c.name, c.detail))].spacing(10))
+                    .push_maybe(
+                        c.hint
+                            .map(|h| text(format!("    → {h}")).style(style::Text::Commentary)),
+                    )
+            });
+        Some(
+            container(checks)
+                .width(Length::Fill)
+                .padding(10)
+                .style(style::Container::Frame)
+                .into(),
+        )
+    }
+}
+
+/// Version, freshness and source of the debloat lists, with the "Update" button
+fn uad_lists_view<'a>(
+    state: UadListState,
+    list_source_setting: &str,
+    offline: bool,
+) -> Column<'a, Message, Theme, Renderer> {
+    let date = last_modified_date(CACHE_DIR.join(LIST_FNAME));
+    let uad_list_text = text(format!("{NAME} package list: v{}", date.format("%Y%m%d"))).width(250);
+    let last_update_text = text(if offline {
+        "Offline mode".to_string()
+    } else {
+        state.to_string()
+    });
+    let uad_lists_btn =
+        button_primary("Update").on_press_maybe((!offline).then_some(Message::UpdateUadLists));
+
+    let uad_list_row = row![uad_list_text, uad_lists_btn, last_update_text,]
+        .align_items(Alignment::Center)
+        .spacing(10)
+        .width(550);
+
+    column![
+        uad_list_row,
+        lists_age_view(state),
+        list_source_view(list_source_setting)
+    ]
+    .align_items(Alignment::Center)
+    .spacing(10)
+}
+
+/// Where the lists come from, highlighted when it isn't the official source
+fn list_source_view<'a>(setting: &str) -> Text<'a, Theme, Renderer> {
+    let source = list_source(setting);
+    if setting.trim().is_empty() {
+        text(format!("Source: {source}")).style(style::Text::Commentary)
+    } else {
+        text(format!("Source (custom, see the settings): {source}")).style(style::Text::Danger)
+    }
+    .size(12)
+}
+
+/// How old the cached lists are, with a nudge to update stale ones
+fn lists_age_view<'a>(state: UadListState) -> Text<'a, Theme, Renderer> {
+    match lists_updated_at() {
+        None => text("Lists never updated: the ones bundled with this release are used")
+            .style(style::Text::Danger),
+        Some(date) if is_stale(date, chrono::Utc::now()) && state != UadListState::Downloading => {
+            text(format!(
+                "Lists updated {}: over {STALE_LISTS_DAYS} days old, update them before reporting a miscategorized package",
+                format_diff_time_from_now(date)
+            ))
+            .style(style::Text::Danger)
+        }
+        Some(date) => text(format!("Lists updated {}", format_diff_time_from_now(date)))
+            .style(style::Text::Commentary),
+    }
+    .size(12)
 }
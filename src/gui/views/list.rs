@@ -1,28 +1,55 @@
-use crate::core::config::DeviceSettings;
+use crate::core::config::{Config, DeviceSettings, GeneralSettings, UserScope};
 use crate::core::helpers::button_primary;
+use crate::core::i18n::t;
+use crate::core::save::{backup_phone, latest_backup, list_available_backups};
 use crate::core::sync::{
-    apply_pkg_state_commands, perform_adb_commands, AdbError, CommandType, Phone, User,
+    active_system_packages, apply_pkg_state_commands, clear_data_commands,
+    component_state_commands, has_root_access, is_package_disabled, is_package_suspended,
+    list_apk_paths, logcat_stream, package_components, package_pid, perform_adb_commands,
+    pull_apks, reclaimable_apk_sizes, root_uninstall, split_uninstall_commands,
+    suspend_pkg_commands, AdbError, AdbStatus, CommandType, PackageComponent, Phone, User,
 };
 use crate::core::theme::Theme;
 use crate::core::uad_lists::{
-    load_debloat_lists, Opposite, PackageHashMap, PackageState, Removal, UadList, UadListState,
+    dependency_warning, detect_oem, is_protected, load_debloat_lists, Oem, Opposite,
+    PackageHashMap, PackageState, Removal, UadList, UadListState, PROTECTED_PACKAGES,
 };
 use crate::core::utils::{
-    export_selection, fetch_packages, open_url, ANDROID_SERIAL, EXPORT_FILE_NAME, NAME,
+    export_selection, fetch_packages, format_size, matches_search, open_folder, open_url, ticks,
+    SearchField, SortBy, ANDROID_SERIAL, EXPORT_FILE_NAME, NAME,
 };
 use crate::gui::style;
 use crate::gui::widgets::navigation_menu::ICONS;
+use std::collections::{HashMap, VecDeque};
 use std::env;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use crate::gui::views::settings::Settings;
 use crate::gui::widgets::modal::Modal;
 use crate::gui::widgets::package_row::{Message as RowMessage, PackageRow};
+use iced::futures::channel::mpsc;
+use iced::futures::{lock, StreamExt};
 use iced::widget::{
     button, checkbox, column, container, horizontal_space, pick_list, radio, row, scrollable, text,
     text_input, tooltip, vertical_rule, Column, Space,
 };
-use iced::{alignment, Alignment, Command, Element, Length, Renderer};
+use iced::{
+    alignment, clipboard, subscription, Alignment, Command, Element, Length, Renderer, Subscription,
+};
+
+/// Lines kept by the logcat panel, older ones are dropped
+const LOGCAT_MAX_LINES: usize = 500;
+/// Oldest entries of the activity log are dropped past this
+const ACTIVITY_MAX_ENTRIES: usize = 1000;
+
+/// How long "Copied!" is shown after copying a package name
+const COPIED_DELAY: Duration = Duration::from_secs(2);
+
+/// The package list is only filtered once typing pauses for this long
+const SEARCH_DEBOUNCE: Duration = Duration::from_millis(150);
 
 #[derive(Debug, Default, Clone)]
 pub struct PackageInfo {
@@ -39,8 +66,166 @@ pub enum LoadingState {
     LoadingPackages,
     _UpdatingUad,
     Ready,
-    RestoringDevice(String),
+    RestoringDevice {
+        /// Last restored package
+        package: String,
+        done: u32,
+        total: u32,
+    },
     FailedToUpdate,
+    /// The device requested with `--device` isn't connected
+    DeviceNotFound(String),
+    /// The selected device (serial) went away, it's selected again if it comes back
+    DeviceDisconnected(String),
+}
+
+/// Expert mode logcat of a package, shown instead of its description
+#[derive(Default, Debug, Clone)]
+struct LogcatPanel {
+    package: String,
+    /// `None` when the package isn't running
+    pid: Option<u32>,
+    lines: VecDeque<String>,
+}
+
+/// How a package action of the activity log ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ActivityOutcome {
+    Success,
+    /// The action failed, but another way of doing it worked
+    Fallback(String),
+    Failure(String),
+}
+
+/// A package action of the session, listed in the activity log
+#[derive(Debug, Clone)]
+struct ActivityEntry {
+    package: String,
+    /// The adb commands that ran
+    command: String,
+    outcome: ActivityOutcome,
+}
+
+impl std::fmt::Display for ActivityEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self {
+            package, command, ..
+        } = self;
+        match &self.outcome {
+            ActivityOutcome::Success => write!(f, "[OK] {package}: {command}"),
+            ActivityOutcome::Fallback(how) => write!(f, "[FALLBACK] {package}: {command} ({how})"),
+            ActivityOutcome::Failure(err) => write!(f, "[FAILED] {package}: {command} -> {err}"),
+        }
+    }
+}
+
+/// Progress of the package state changes issued by "Apply" or a row action
+#[derive(Default, Debug, Clone)]
+pub struct BatchOperation {
+    /// Shared by every task of the batch: once set, tasks that haven't started are skipped
+    cancel: Arc<AtomicBool>,
+    total: usize,
+    ran: usize,
+    skipped: usize,
+    /// Changes that failed so far
+    failed: Vec<BatchItem>,
+    /// Shared by every task of the batch, to limit how many run adb at once
+    slots: Arc<BatchSlots>,
+}
+
+/// Counting semaphore for the tasks of a batch: spawning hundreds of adb commands
+/// at once overwhelms the adb server, and some of them fail at random.
+/// A slot is a token of the channel, waited for without blocking a thread of the executor.
+#[derive(Debug)]
+struct BatchSlots {
+    release: mpsc::UnboundedSender<()>,
+    /// Held by the task waiting for the next free slot, the others wait for it in turn
+    free: lock::Mutex<mpsc::UnboundedReceiver<()>>,
+}
+
+impl Default for BatchSlots {
+    fn default() -> Self {
+        Self::new(GeneralSettings::DEFAULT_ADB_CONCURRENCY)
+    }
+}
+
+impl BatchSlots {
+    fn new(limit: usize) -> Self {
+        let (release, free) = mpsc::unbounded();
+        for _ in 0..limit.max(1) {
+            let _ = release.unbounded_send(());
+        }
+        Self {
+            release,
+            free: lock::Mutex::new(free),
+        }
+    }
+
+    /// Wait until a slot is free. It's released when the returned guard is dropped.
+    async fn acquire(slots: &Arc<Self>) -> BatchSlot {
+        // `None` never comes: `slots` holds the sender
+        let _ = slots.free.lock().await.next().await;
+        BatchSlot(Arc::clone(slots))
+    }
+}
+
+struct BatchSlot(Arc<BatchSlots>);
+
+impl Drop for BatchSlot {
+    fn drop(&mut self) {
+        let _ = self.0.release.unbounded_send(());
+    }
+}
+
+/// A package state change of a batch, kept to retry it if it fails
+#[derive(Debug, Clone, Copy)]
+pub struct BatchItem {
+    user: User,
+    /// Package index in `phone_packages`
+    index: usize,
+    wanted_state: PackageState,
+}
+
+/// A disabled package to check again, see [`List::schedule_recheck`]
+#[derive(Debug, Clone)]
+pub struct DisableRecheck {
+    /// Device the package was disabled on
+    serial: String,
+    package: String,
+    /// Index of the user in `phone_packages`
+    user_index: usize,
+    user: Option<User>,
+    due: Instant,
+}
+
+/// What running the adb commands of a [`BatchItem`] did
+#[derive(Debug, Clone)]
+pub struct BatchRun {
+    /// The adb commands that ran
+    commands: String,
+    res: Result<CommandType, AdbError>,
+    /// How the change got done after its usual command failed
+    fallback: Option<String>,
+    /// `(command, error)` of the commands following the state change that failed
+    follow_up_failures: Vec<(String, String)>,
+    /// See [`BatchOperation::owns`]
+    batch: Arc<AtomicBool>,
+}
+
+impl BatchOperation {
+    /// Whether the task that got `cancel` belongs to this batch. Results of the batch
+    /// of a list that got reloaded since are for packages that may not be there anymore.
+    fn owns(&self, cancel: &Arc<AtomicBool>) -> bool {
+        Arc::ptr_eq(&self.cancel, cancel)
+    }
+
+    fn is_running(&self) -> bool {
+        self.ran + self.skipped < self.total
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancel.load(Ordering::Relaxed)
+    }
 }
 
 #[derive(Default, Debug, Clone)]
@@ -60,45 +245,165 @@ pub struct List {
     pub selected_user: Option<User>,
     all_selected: bool,
     pub input_value: String,
+    search_field: SearchField,
+    sort_by: SortBy,
+    /// Manufacturer of the device, from its brand
+    oem: Option<&'static Oem>,
+    /// Only show the packages of `oem`
+    oem_only: bool,
+    /// Only show the packages changed since the list was loaded, whatever their state
+    modified_only: bool,
+    /// Only show the packages of `bookmarked_packages`
+    bookmarks_only: bool,
+    /// Package states of the latest backup per user id, while only the packages
+    /// in another state than in this backup are shown
+    backup_states: Option<HashMap<u16, HashMap<String, PackageState>>>,
     description: String,
     selection_modal: bool,
     error_modal: Option<String>,
+    /// Restricted package that could be removed with root, waiting for confirmation
+    root_removal_modal: Option<(PackageInfo, String)>,
+    /// Package whose data is to be cleared, waiting for confirmation
+    clear_data_modal: Option<PackageInfo>,
     export_modal: bool,
+    /// The description of the current package, popped out for long notes
+    description_modal: bool,
     current_package_index: usize,
-    is_adb_satisfied: bool,
+    adb_status: AdbStatus,
+    /// Search inputs typed so far, to only filter for the last one
+    search_inputs: u32,
+    /// Whether the last search input is waiting for [`SEARCH_DEBOUNCE`]
+    search_settling: bool,
+    batch: BatchOperation,
+    /// Outcome of the last cancelled or partially failed batch, or of a data clearing
+    batch_report: Option<String>,
+    /// Failed changes of the last batch, offered for a retry
+    failed_changes: Vec<BatchItem>,
+    logcat: Option<LogcatPanel>,
+    /// `(package, components)` listed by the expert mode "Advanced: components" expander
+    components: Option<(String, Vec<PackageComponent>)>,
+    /// Outcome of "Save APK" for the current package
+    apk_save_status: Option<String>,
+    /// Packages per removal category matching every filter but the removal one,
+    /// in [`Removal::CATEGORIES`] order
+    removal_counts: [usize; Removal::CATEGORIES.len()],
+    /// Disabled packages to check again, see [`List::schedule_recheck`]
+    disable_rechecks: Vec<DisableRecheck>,
+    /// Packages of the backup being restored that were restored so far
+    restored: usize,
+    /// `(user_index, pkg_index)` of the packages whose name was copied a moment ago
+    copied: Vec<(usize, usize)>,
+    /// Package actions of the session, oldest first
+    activity: VecDeque<ActivityEntry>,
+    activity_expanded: bool,
 }
 
 #[derive(Debug, Clone)]
 pub enum Message {
     LoadUadList(bool),
     LoadPhonePackages((PackageHashMap, UadListState)),
-    RestoringDevice(Result<CommandType, AdbError>),
+    /// A package of a backup got restored
+    RestoringDevice {
+        output: Result<CommandType, AdbError>,
+        done: u32,
+        total: u32,
+    },
     ApplyFilters(Vec<Vec<PackageRow>>),
     SearchInputChanged(String),
+    /// Typing paused after the n-th search input
+    SearchSettled(u32),
+    SearchSubmitted,
+    SearchHistorySelected(String),
+    SearchFieldSelected(SearchField),
+    SortSelected(SortBy),
+    OemOnly(bool),
+    ModifiedOnly(bool),
+    BookmarksOnly(bool),
+    ChangedSinceBackup(bool),
     ToggleAllSelected(bool),
     ListSelected(UadList),
     UserSelected(User),
     PackageStateSelected(PackageState),
     RemovalSelected(Removal),
     ApplyActionOnSelection,
+    /// Select the enabled Recommended packages only, and review them
+    SafeDebloat,
     List(usize, RowMessage),
-    ChangePackageState(Result<CommandType, AdbError>),
-    Nothing,
+    ChangePackageState(BatchItem, BatchRun),
+    /// A package state change was skipped because its batch got cancelled,
+    /// see [`BatchOperation::owns`]
+    OperationSkipped(Arc<AtomicBool>),
+    CancelBatch,
+    RetryFailed,
     ModalHide,
     ModalUserSelected(User),
     ModalValidate,
     ClearSelectedPackages,
-    ADBSatisfied(bool),
+    ADBSatisfied(AdbStatus),
     UpdateFailed,
     GoToUrl(PathBuf),
     ExportSelection,
     SelectionExported(Result<bool, String>),
+    /// `(package, apk_paths)`
+    ApkPathsFetched(String, Vec<String>),
+    /// `(user_index, pkg_index)` of a package whose name was copied a moment ago
+    CopiedExpired(usize, usize),
+    /// `(package, bytes)` for the packages to uninstall of the review modal
+    ApkSizesFetched(Vec<(String, u64)>),
+    /// `(user_index, pkg_index, suspended)`
+    SuspendStateFetched(usize, usize, bool),
+    LogcatPressed,
+    DescriptionPopOut,
+    /// `(package, pid)`
+    LogcatStarted(String, Option<u32>),
+    LogcatLine(String),
+    LogcatStopped,
+    SaveApkPressed,
+    ApkSaved(Result<Vec<PathBuf>, String>),
+    /// Expand or collapse the components of the current package
+    ComponentsPressed,
+    /// `(package, components)`
+    ComponentsFetched(String, Vec<PackageComponent>),
+    /// `(package/class, enable)`
+    ComponentToggled(String, bool),
+    /// Whether root is available to remove the package the device refused to uninstall
+    RootChecked(PackageInfo, String, bool),
+    /// Whether the device of the serial grants root, probed once its packages are loaded
+    RootProbed(String, bool),
+    RootRemovalConfirmed,
+    ClearDataConfirmed,
+    /// `(package, result)`
+    ClearDataDone(String, Result<(), String>),
+    RootRemovalDone(PackageInfo, Result<(), String>),
+    /// Some disabled packages are due for a re-check
+    RecheckTick(Instant),
+    /// Whether the package is still disabled after `disable_recheck_delay`
+    DisableRechecked(DisableRecheck, bool),
+    PreApplyBackedUp(Result<bool, String>),
+    /// Expand or collapse the activity log
+    ActivityLogPressed,
+    CopyActivityLog,
+}
+
+/// What applying the selection to all users does to one of them, see [`List::cross_user_impact`]
+struct UserImpact {
+    user: User,
+    /// Packages whose state will change
+    changed: usize,
+    /// Packages already in the wanted state
+    unchanged: usize,
+    /// Packages the user doesn't have at all: the action fails for them
+    absent: usize,
 }
 
 pub struct SummaryEntry {
     category: Removal,
-    discard: u8,
-    restore: u8,
+    discard: usize,
+    restore: usize,
+    /// Estimated bytes freed by the packages to uninstall, see [`PackageRow::apk_size`]
+    reclaimed: u64,
+    /// Packages to uninstall whose size isn't known yet
+    unmeasured: usize,
 }
 
 impl From<Removal> for SummaryEntry {
@@ -107,8 +412,37 @@ impl From<Removal> for SummaryEntry {
             category,
             discard: 0,
             restore: 0,
+            reclaimed: 0,
+            unmeasured: 0,
+        }
+    }
+}
+
+/// Count the selected packages to remove and to restore, per removal category.
+/// Shared by the review modal and the live summary bar so they always agree.
+fn summarize_selection(
+    packages: &[PackageRow],
+    settings: &Settings,
+) -> [SummaryEntry; Removal::CATEGORIES.len()] {
+    // 5 element slice is cheap
+    let mut summaries = Removal::CATEGORIES.map(SummaryEntry::from);
+    for p in packages.iter().filter(|p| p.selected) {
+        let summary = &mut summaries[p.removal as usize];
+        match p.state {
+            PackageState::Uninstalled | PackageState::Disabled => summary.restore += 1,
+            _ => {
+                summary.discard += 1;
+                // Disabling frees nothing
+                if !p.disable_mode(settings) {
+                    match p.apk_size {
+                        Some(size) => summary.reclaimed += size,
+                        None => summary.unmeasured += 1,
+                    }
+                }
+            }
         }
     }
+    summaries
 }
 
 impl List {
@@ -126,37 +460,72 @@ impl List {
             Message::ModalHide => {
                 self.selection_modal = false;
                 self.error_modal = None;
+                self.root_removal_modal = None;
+                self.clear_data_modal = None;
                 self.export_modal = false;
+                self.description_modal = false;
                 Command::none()
             }
             Message::ModalValidate => {
-                let mut commands = vec![];
-                self.selected_packages.sort_unstable();
-                self.selected_packages.dedup();
-                for selection in &self.selected_packages {
-                    commands.append(&mut build_action_pkg_commands(
-                        &self.phone_packages,
-                        selected_device,
-                        &settings.device,
-                        *selection,
-                    ));
-                }
                 self.selection_modal = false;
-                Command::batch(commands)
+                if settings.device.auto_backup_before_apply {
+                    info!("[BACKUP] Backing up the device before applying the selection");
+                    return Command::perform(
+                        backup_phone(selected_device.clone(), self.phone_packages.clone(), true),
+                        Message::PreApplyBackedUp,
+                    );
+                }
+                self.apply_selection(settings, selected_device)
             }
-            Message::RestoringDevice(output) => {
-                if let Ok(res) = output {
-                    if let CommandType::PackageManager(p) = res {
-                        self.loading_state = LoadingState::RestoringDevice(
-                            self.phone_packages[i_user][p.index].name.clone(),
-                        );
+            Message::PreApplyBackedUp(res) => match res {
+                Ok(_) => {
+                    settings.device.backup.backups = list_available_backups(
+                        &settings.general.backup_folder.join(&selected_device.adb_id),
+                    );
+                    self.apply_selection(settings, selected_device)
+                }
+                Err(err) => {
+                    error!("[BACKUP FAILED] Pre-apply backup failed: {err}");
+                    self.error_modal = Some(format!(
+                        "The automatic backup failed, so no package was changed.\n\n{err}"
+                    ));
+                    Command::none()
+                }
+            },
+            Message::RestoringDevice {
+                output,
+                done,
+                total,
+            } => {
+                if output.is_ok() {
+                    self.restored += 1;
+                }
+                let package = match output {
+                    Ok(CommandType::PackageManager(p)) => {
+                        self.phone_packages[p.i_user][p.index].name.clone()
                     }
-                } else {
-                    self.loading_state = LoadingState::RestoringDevice("Error [TODO]".to_string());
+                    Ok(CommandType::Shell) => String::default(),
+                    Err(e) => {
+                        error!("[RESTORE] {e}");
+                        "failed, see the logs".to_string()
+                    }
+                };
+                if done == total {
+                    // Only the packages that were actually restored
+                    settings
+                        .device
+                        .record_debloat(std::mem::take(&mut self.restored));
+                    Config::save_changes(settings, &selected_device.adb_id);
                 }
+                self.loading_state = LoadingState::RestoringDevice {
+                    package,
+                    done,
+                    total,
+                };
                 Command::none()
             }
             Message::LoadUadList(remote) => {
+                let remote = remote && !settings.general.offline_mode;
                 info!("{:-^65}", "-");
                 info!(
                     "ANDROID_SDK: {} | DEVICE: {}",
@@ -165,34 +534,71 @@ impl List {
                 info!("{:-^65}", "-");
                 self.loading_state = LoadingState::DownloadingList;
                 Command::perform(
-                    Self::init_apps_view(remote, selected_device.clone()),
+                    Self::init_apps_view(
+                        remote,
+                        settings.general.list_source.clone(),
+                        selected_device.clone(),
+                    ),
                     Message::LoadPhonePackages,
                 )
             }
             Message::LoadPhonePackages((uad_list, list_state)) => {
                 self.loading_state = LoadingState::LoadingPackages;
+                self.logcat = None;
+                // The backup was of the previous device
+                self.backup_states = None;
                 self.uad_lists.clone_from(&uad_list);
                 *list_update_state = list_state;
                 Command::perform(
-                    Self::load_packages(uad_list, selected_device.user_list.clone()),
+                    Self::load_packages(
+                        uad_list,
+                        selected_device.user_list.clone(),
+                        settings.general.show_absent_packages,
+                        settings.general.protected_packages.clone(),
+                    ),
                     Message::ApplyFilters,
                 )
             }
             Message::ApplyFilters(packages) => {
                 self.phone_packages = packages;
+                for p in self.phone_packages.iter_mut().flatten() {
+                    p.bookmarked = settings.general.bookmarked_packages.contains(&p.name);
+                }
                 self.filtered_packages = (0..self.phone_packages[i_user].len()).collect();
-                self.selected_package_state = Some(PackageState::Enabled);
-                self.selected_removal = Some(Removal::Recommended);
-                self.selected_list = Some(UadList::All);
+                // `model` starts with `ro.product.brand`
+                self.oem = selected_device
+                    .model
+                    .split_whitespace()
+                    .next()
+                    .and_then(detect_oem);
+                let filters = settings.device.filters;
+                self.selected_package_state = Some(filters.state);
+                self.selected_removal = Some(filters.removal);
+                self.selected_list = Some(filters.list);
+                self.sort_by = filters.sort_by;
                 self.selected_user = Some(User::default());
                 Self::filter_package_lists(self);
                 self.loading_state = LoadingState::Ready;
+                if selected_device.has_root.is_some() {
+                    return Command::none();
+                }
+                let serial = selected_device.adb_id.clone();
+                Command::perform(async { has_root_access() }, move |root| {
+                    Message::RootProbed(serial, root)
+                })
+            }
+            Message::RootProbed(serial, root) => {
+                // The device may have changed in the meantime
+                if selected_device.adb_id == serial {
+                    selected_device.has_root = Some(root);
+                }
                 Command::none()
             }
             Message::ToggleAllSelected(selected) => {
                 #[allow(unused_must_use)]
                 for i in self.filtered_packages.clone() {
-                    if self.phone_packages[i_user][i].selected != selected {
+                    let package = &self.phone_packages[i_user][i];
+                    if package.selected != selected && !(selected && package.is_kept(settings)) {
                         self.update(
                             settings,
                             selected_device,
@@ -206,21 +612,92 @@ impl List {
             }
             Message::SearchInputChanged(letter) => {
                 self.input_value = letter;
+                self.search_inputs += 1;
+                self.search_settling = true;
+                Command::none()
+            }
+            Message::SearchSettled(input) => {
+                if self.search_settling && input == self.search_inputs {
+                    self.search_settling = false;
+                    Self::filter_package_lists(self);
+                }
+                Command::none()
+            }
+            Message::SearchSubmitted => {
+                // Don't wait for the debounce
+                self.search_inputs += 1;
+                self.search_settling = false;
+                Self::filter_package_lists(self);
+                settings.general.push_search_history(&self.input_value);
+                Config::save_changes(settings, &selected_device.adb_id);
+                Command::none()
+            }
+            Message::SearchHistorySelected(term) => {
+                self.input_value = term;
+                self.search_inputs += 1;
+                self.search_settling = false;
+                Self::filter_package_lists(self);
+                Command::none()
+            }
+            Message::SearchFieldSelected(field) => {
+                self.search_field = field;
+                Self::filter_package_lists(self);
+                Command::none()
+            }
+            Message::SortSelected(sort_by) => {
+                self.sort_by = sort_by;
+                settings.device.filters.sort_by = sort_by;
+                Config::save_changes(settings, &selected_device.adb_id);
+                Self::filter_package_lists(self);
+                Command::none()
+            }
+            Message::OemOnly(toggled) => {
+                self.oem_only = toggled;
+                Self::filter_package_lists(self);
+                Command::none()
+            }
+            Message::ModifiedOnly(toggled) => {
+                self.modified_only = toggled;
+                Self::filter_package_lists(self);
+                Command::none()
+            }
+            Message::BookmarksOnly(toggled) => {
+                self.bookmarks_only = toggled;
+                Self::filter_package_lists(self);
+                Command::none()
+            }
+            Message::ChangedSinceBackup(toggled) => {
+                self.backup_states = None;
+                if toggled {
+                    let dir = settings.general.backup_folder.join(&selected_device.adb_id);
+                    match latest_backup(&dir) {
+                        Some(backup) => self.backup_states = Some(backup.package_states()),
+                        None => {
+                            self.batch_report = Some("No backup of this device yet".to_string());
+                        }
+                    }
+                }
                 Self::filter_package_lists(self);
                 Command::none()
             }
             Message::ListSelected(list) => {
                 self.selected_list = Some(list);
+                settings.device.filters.list = list;
+                Config::save_changes(settings, &selected_device.adb_id);
                 Self::filter_package_lists(self);
                 Command::none()
             }
             Message::PackageStateSelected(package_state) => {
                 self.selected_package_state = Some(package_state);
+                settings.device.filters.state = package_state;
+                Config::save_changes(settings, &selected_device.adb_id);
                 Self::filter_package_lists(self);
                 Command::none()
             }
             Message::RemovalSelected(removal) => {
                 self.selected_removal = Some(removal);
+                settings.device.filters.removal = removal;
+                Config::save_changes(settings, &selected_device.adb_id);
                 Self::filter_package_lists(self);
                 Command::none()
             }
@@ -237,7 +714,10 @@ impl List {
 
                 match row_message {
                     RowMessage::ToggleSelection(toggle) => {
-                        if package.removal == Removal::Unsafe && !settings.general.expert_mode {
+                        if !package.on_device
+                            || package.is_locked(settings)
+                            || (package.removal == Removal::Unsafe && !settings.general.expert_mode)
+                        {
                             package.selected = false;
                             return Command::none();
                         }
@@ -263,53 +743,466 @@ impl List {
                         }
                         Command::none()
                     }
+                    RowMessage::ActionOverridden(action_override) => {
+                        // Keep the override consistent across users: the same package is acted on
+                        for rows in &mut self.phone_packages {
+                            if let Some(row) = rows.get_mut(i_package) {
+                                row.action_override = action_override;
+                            }
+                        }
+                        Command::none()
+                    }
                     RowMessage::ActionPressed => {
                         self.phone_packages[i_user][i_package].selected = true;
+                        self.start_batch(settings.general.adb_concurrency);
                         Command::batch(build_action_pkg_commands(
                             &self.phone_packages,
                             selected_device,
                             &settings.device,
                             (i_user, i_package),
+                            &mut self.batch,
+                        ))
+                    }
+                    RowMessage::RestoreForAllUsers => {
+                        // Same as a row action applied to all users: every user that
+                        // doesn't have the package gets it back via `install-existing`
+                        let all_users = DeviceSettings {
+                            user_scope_choice: Some(UserScope::AllUsers),
+                            ..settings.device.clone()
+                        };
+                        self.start_batch(settings.general.adb_concurrency);
+                        Command::batch(build_action_pkg_commands(
+                            &self.phone_packages,
+                            selected_device,
+                            &all_users,
+                            (i_user, i_package),
+                            &mut self.batch,
                         ))
                     }
                     RowMessage::PackagePressed => {
+                        if !self.input_value.is_empty() {
+                            // The search led somewhere, remember it
+                            settings.general.push_search_history(&self.input_value);
+                            Config::save_changes(settings, &selected_device.adb_id);
+                        }
                         self.description = package.clone().description;
+                        self.apk_save_status = None;
                         package.current = true;
+                        let fetch_apk_paths = if package.apk_paths.is_none() {
+                            let name = package.name.clone();
+                            let user = (selected_device.android_sdk >= 21)
+                                .then_some(self.selected_user)
+                                .flatten();
+                            Command::perform(
+                                async move {
+                                    let paths = list_apk_paths(&name, user.as_ref());
+                                    (name, paths)
+                                },
+                                |(name, paths)| Message::ApkPathsFetched(name, paths),
+                            )
+                        } else {
+                            Command::none()
+                        };
+                        let fetch_suspended =
+                            if package.suspended.is_none() && selected_device.android_sdk >= 24 {
+                                let name = package.name.clone();
+                                let user = self.selected_user.unwrap_or_default();
+                                Command::perform(
+                                    async move { is_package_suspended(&name, &user) },
+                                    move |suspended| {
+                                        Message::SuspendStateFetched(i_user, i_package, suspended)
+                                    },
+                                )
+                            } else {
+                                Command::none()
+                            };
                         if self.current_package_index != i_package {
                             self.phone_packages[i_user][self.current_package_index].current = false;
                         }
                         self.current_package_index = i_package;
+                        let fetch_components = if self.components.is_some() {
+                            self.fetch_components()
+                        } else {
+                            Command::none()
+                        };
+                        Command::batch([fetch_apk_paths, fetch_suspended, fetch_components])
+                    }
+                    RowMessage::CopyName => {
+                        package.copied = true;
+                        let name = package.name.clone();
+                        if !self.copied.contains(&(i_user, i_package)) {
+                            self.copied.push((i_user, i_package));
+                        }
+                        clipboard::write(name)
+                    }
+                    RowMessage::KeepToggled => {
+                        let kept = &mut settings.device.kept_packages;
+                        if let Some(i) = kept.iter().position(|p| *p == package.name) {
+                            kept.remove(i);
+                        } else {
+                            kept.push(package.name.clone());
+                        }
+                        Config::save_changes(settings, &selected_device.adb_id);
+                        Command::none()
+                    }
+                    RowMessage::BookmarkToggled => {
+                        let name = package.name.clone();
+                        let bookmarked = !package.bookmarked;
+                        for p in self.phone_packages.iter_mut().flatten() {
+                            if p.name == name {
+                                p.bookmarked = bookmarked;
+                            }
+                        }
+                        let bookmarks = &mut settings.general.bookmarked_packages;
+                        bookmarks.retain(|p| *p != name);
+                        if bookmarked {
+                            bookmarks.push(name);
+                        }
+                        Config::save_changes(settings, &selected_device.adb_id);
+                        if self.bookmarks_only {
+                            Self::filter_package_lists(self);
+                        }
                         Command::none()
                     }
+                    RowMessage::ClearData => {
+                        self.clear_data_modal = Some(PackageInfo {
+                            i_user,
+                            index: i_package,
+                            removal: package.removal.to_string(),
+                        });
+                        Command::none()
+                    }
+                    RowMessage::SuspendToggled => {
+                        let user = self.selected_user.unwrap_or_default();
+                        let suspend = package.suspended != Some(true);
+                        let actions = match suspend_pkg_commands(
+                            &package.name,
+                            suspend,
+                            &user,
+                            selected_device,
+                        ) {
+                            Ok(actions) => actions,
+                            Err(e) => {
+                                error!("{e}");
+                                return Command::none();
+                            }
+                        };
+                        let name = package.name.clone();
+                        Command::perform(
+                            async move {
+                                for action in actions {
+                                    if let Err(e) =
+                                        perform_adb_commands(action, CommandType::Shell).await
+                                    {
+                                        error!("{e}");
+                                    }
+                                }
+                                is_package_suspended(&name, &user)
+                            },
+                            move |suspended| {
+                                Message::SuspendStateFetched(i_user, i_package, suspended)
+                            },
+                        )
+                    }
                 }
             }
             Message::ApplyActionOnSelection => {
-                self.selection_modal = true;
+                if self.selected_user_protected() {
+                    warn!("Can't apply a selection to a protected user");
+                } else {
+                    self.selection_modal = true;
+                    return self.fetch_apk_sizes(settings, selected_device);
+                }
+                Command::none()
+            }
+            Message::SafeDebloat => self.safe_debloat(settings, selected_device, list_update_state),
+            Message::CopiedExpired(user_index, pkg_index) => {
+                self.copied.retain(|&c| c != (user_index, pkg_index));
+                if let Some(package) = self
+                    .phone_packages
+                    .get_mut(user_index)
+                    .and_then(|rows| rows.get_mut(pkg_index))
+                {
+                    package.copied = false;
+                }
+                Command::none()
+            }
+            Message::ApkSizesFetched(sizes) => {
+                for (name, size) in sizes {
+                    // The APK is shared by every user
+                    for rows in &mut self.phone_packages {
+                        if let Some(p) = rows.iter_mut().find(|p| p.name == name) {
+                            p.apk_size = Some(size);
+                        }
+                    }
+                }
                 Command::none()
             }
             Message::UserSelected(user) => {
                 self.selected_user = Some(user);
+                // They were listed for the previous user
+                self.components = None;
                 self.filtered_packages = (0..self.phone_packages[user.index].len()).collect();
                 Self::filter_package_lists(self);
                 Command::none()
             }
-            Message::ChangePackageState(res) => {
-                match res {
+            Message::ChangePackageState(item, run) => {
+                if !self.batch.owns(&run.batch) {
+                    info!("Result of a change made before the package list got reloaded dropped");
+                    return Command::none();
+                }
+                let changed = self
+                    .phone_packages
+                    .get(item.user.index)
+                    .and_then(|rows| rows.get(item.index));
+                let name =
+                    changed.map_or_else(|| "<unknown package>".to_string(), |p| p.name.clone());
+                let is_unsafe = changed.is_some_and(|p| p.removal == Removal::Unsafe);
+                self.batch.ran += 1;
+                if run.res.is_err() {
+                    self.batch.failed.push(item);
+                }
+                let outcome = match (&run.res, run.fallback) {
+                    (Err(e), _) => ActivityOutcome::Failure(e.to_string()),
+                    (Ok(_), Some(how)) => ActivityOutcome::Fallback(how),
+                    (Ok(_), None) => ActivityOutcome::Success,
+                };
+                self.log_activity(name.clone(), run.commands, outcome);
+                // The state changed anyway
+                for (command, err) in run.follow_up_failures {
+                    warn!("{name}: `{command}` failed after the state change: {err}");
+                    self.log_activity(name.clone(), command, ActivityOutcome::Failure(err));
+                }
+                match run.res {
                     Ok(CommandType::PackageManager(p)) => {
-                        let package = &mut self.phone_packages[p.i_user][p.index];
-                        package.state = package.state.opposite(settings.device.disable_mode);
+                        let Some(package) = self
+                            .phone_packages
+                            .get_mut(p.i_user)
+                            .and_then(|rows| rows.get_mut(p.index))
+                        else {
+                            return self.on_batch_progress(settings, selected_device);
+                        };
+                        package.state = package.state.opposite(package.disable_mode(settings));
                         package.selected = false;
+                        let disabled = package.state == PackageState::Disabled;
                         self.selected_packages
                             .retain(|&x| x.1 != p.index && x.0 != p.i_user);
                         Self::filter_package_lists(self);
+                        if disabled && settings.general.disable_recheck_delay > 0 {
+                            self.schedule_recheck(settings, selected_device, &p);
+                        }
+                    }
+                    // Root removal is never offered for Unsafe packages
+                    Err(AdbError::Restricted(p, err))
+                        if selected_device.has_root != Some(false) && !is_unsafe =>
+                    {
+                        if selected_device.has_root == Some(true) {
+                            self.root_removal_modal = Some((p, err));
+                        } else {
+                            // Not probed yet, e.g. the packages were loading
+                            return Command::batch([
+                                Command::perform(async { has_root_access() }, move |root| {
+                                    Message::RootChecked(p, err, root)
+                                }),
+                                self.on_batch_progress(settings, selected_device),
+                            ]);
+                        }
                     }
-                    Err(AdbError::Generic(err)) => {
-                        self.error_modal = Some(err);
+                    Err(e) => {
+                        self.error_modal = Some(
+                            e.hint()
+                                .map_or_else(|| e.to_string(), |hint| format!("{e}\n\n{hint}")),
+                        );
                     }
-                    _ => {}
+                    Ok(CommandType::Shell) => {}
+                }
+                self.on_batch_progress(settings, selected_device)
+            }
+            Message::RootChecked(p, err, root) => {
+                selected_device.has_root = Some(root);
+                if root {
+                    self.root_removal_modal = Some((p, err));
+                } else {
+                    let e = AdbError::Restricted(p, err);
+                    self.error_modal = Some(
+                        e.hint()
+                            .map_or_else(|| e.to_string(), |hint| format!("{e}\n\n{hint}")),
+                    );
+                }
+                Command::none()
+            }
+            Message::RecheckTick(now) => {
+                let (due, pending) = std::mem::take(&mut self.disable_rechecks)
+                    .into_iter()
+                    .partition(|r: &DisableRecheck| r.due <= now);
+                self.disable_rechecks = pending;
+                Command::batch(due.into_iter().map(|recheck| {
+                    Command::perform(
+                        async move {
+                            let still_disabled = is_package_disabled(
+                                &recheck.serial,
+                                &recheck.package,
+                                recheck.user.as_ref(),
+                            );
+                            (recheck, still_disabled)
+                        },
+                        |(recheck, still_disabled)| {
+                            Message::DisableRechecked(recheck, still_disabled)
+                        },
+                    )
+                }))
+            }
+            Message::DisableRechecked(recheck, still_disabled) => {
+                // The device or its packages may have been reloaded meanwhile
+                if recheck.serial != selected_device.adb_id {
+                    return Command::none();
+                }
+                let Some(package) = self
+                    .phone_packages
+                    .get_mut(recheck.user_index)
+                    .and_then(|rows| rows.iter_mut().find(|p| p.name == recheck.package))
+                else {
+                    return Command::none();
+                };
+                if !still_disabled && package.state == PackageState::Disabled {
+                    warn!("{} was re-enabled after being disabled", package.name);
+                    package.state = PackageState::Enabled;
+                    self.error_modal = Some(format!(
+                        "{} was re-enabled shortly after being disabled.\n\
+                        Google Play Services is likely re-enabling it: \
+                        disable the Google Play Services component that manages it first, \
+                        or uninstall the package instead.",
+                        package.name
+                    ));
+                    Self::filter_package_lists(self);
                 }
                 Command::none()
             }
+            Message::RootRemovalConfirmed => {
+                let Some((p, _)) = self.root_removal_modal.take() else {
+                    return Command::none();
+                };
+                let name = self.phone_packages[p.i_user][p.index].name.clone();
+                let user = selected_device
+                    .user_list
+                    .iter()
+                    .find(|u| u.index == p.i_user)
+                    .copied();
+                info!("[Root] Removing {name} from the system partition");
+                Command::perform(
+                    async move { root_uninstall(&name, user.as_ref()) },
+                    move |res| Message::RootRemovalDone(p, res),
+                )
+            }
+            Message::ClearDataConfirmed => {
+                let Some(p) = self.clear_data_modal.take() else {
+                    return Command::none();
+                };
+                let name = self.phone_packages[p.i_user][p.index].name.clone();
+                let user = selected_device
+                    .user_list
+                    .iter()
+                    .find(|u| u.index == p.i_user)
+                    .copied()
+                    .unwrap_or_default();
+                let actions = match clear_data_commands(&name, &user, selected_device) {
+                    Ok(actions) => actions,
+                    Err(e) => {
+                        self.error_modal = Some(e);
+                        return Command::none();
+                    }
+                };
+                Command::perform(
+                    async move {
+                        for action in actions {
+                            perform_adb_commands(action, CommandType::Shell)
+                                .await
+                                .map_err(|e| e.to_string())?;
+                        }
+                        Ok(())
+                    },
+                    move |res| Message::ClearDataDone(name, res),
+                )
+            }
+            Message::ClearDataDone(package, res) => {
+                match res {
+                    Ok(()) => {
+                        info!("Data of {package} cleared");
+                        self.batch_report = Some(format!(
+                            "Data of {package} cleared (the app is still installed)"
+                        ));
+                    }
+                    Err(err) => self.error_modal = Some(err),
+                }
+                Command::none()
+            }
+            Message::RootRemovalDone(p, res) => {
+                match res {
+                    Ok(()) => {
+                        // The APK is gone for every user
+                        for rows in &mut self.phone_packages {
+                            if let Some(package) = rows.get_mut(p.index) {
+                                package.state = PackageState::Uninstalled;
+                                package.selected = false;
+                            }
+                        }
+                        self.selected_packages.retain(|&x| x.1 != p.index);
+                        let package = self.phone_packages[p.i_user][p.index].name.clone();
+                        self.batch_report = Some(format!(
+                            "{package} removed with root. Reboot the device to complete the removal."
+                        ));
+                        self.log_activity(
+                            package,
+                            "root removal".to_string(),
+                            ActivityOutcome::Fallback(
+                                "removed from the system partition".to_string(),
+                            ),
+                        );
+                        Self::filter_package_lists(self);
+                    }
+                    Err(err) => {
+                        let package = self.phone_packages[p.i_user][p.index].name.clone();
+                        self.log_activity(
+                            package,
+                            "root removal".to_string(),
+                            ActivityOutcome::Failure(err.clone()),
+                        );
+                        self.error_modal = Some(format!("[Root] {err}"));
+                    }
+                }
+                Command::none()
+            }
+            Message::ActivityLogPressed => {
+                self.activity_expanded = !self.activity_expanded;
+                Command::none()
+            }
+            Message::CopyActivityLog => clipboard::write(
+                self.activity
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            ),
+            Message::OperationSkipped(batch) => {
+                if !self.batch.owns(&batch) {
+                    return Command::none();
+                }
+                self.batch.skipped += 1;
+                self.on_batch_progress(settings, selected_device)
+            }
+            Message::CancelBatch => {
+                info!("Cancelling the remaining package state changes");
+                self.batch.cancel.store(true, Ordering::Relaxed);
+                Command::none()
+            }
+            Message::RetryFailed => {
+                let items = std::mem::take(&mut self.failed_changes);
+                info!("Retrying {} failed package state changes", items.len());
+                self.start_batch(settings.general.adb_concurrency);
+                Command::batch(items.into_iter().map(|item| {
+                    pkg_state_command(&self.phone_packages, selected_device, item, &mut self.batch)
+                }))
+            }
             Message::ModalUserSelected(user) => {
                 self.selected_user = Some(user);
                 self.update(
@@ -323,8 +1216,8 @@ impl List {
                 self.selected_packages = Vec::new();
                 Command::none()
             }
-            Message::ADBSatisfied(result) => {
-                self.is_adb_satisfied = result;
+            Message::ADBSatisfied(status) => {
+                self.adb_status = status;
                 Command::none()
             }
             Message::UpdateFailed => {
@@ -346,8 +1239,244 @@ impl List {
                 };
                 Command::none()
             }
-            Message::Nothing => Command::none(),
+            Message::ApkPathsFetched(name, paths) => {
+                // The APKs are shared by every user
+                for package in self.phone_packages.iter_mut().flatten() {
+                    if package.name == name {
+                        package.apk_paths = Some(paths.clone());
+                    }
+                }
+                Command::none()
+            }
+            Message::DescriptionPopOut => {
+                self.description_modal = true;
+                Command::none()
+            }
+            Message::LogcatPressed => {
+                let Some(package) = self
+                    .phone_packages
+                    .get(self.selected_user.unwrap_or_default().index)
+                    .and_then(|rows| rows.get(self.current_package_index))
+                else {
+                    return Command::none();
+                };
+                let name = package.name.clone();
+                Command::perform(
+                    async move {
+                        let pid = package_pid(&name);
+                        (name, pid)
+                    },
+                    |(name, pid)| Message::LogcatStarted(name, pid),
+                )
+            }
+            Message::LogcatStarted(package, pid) => {
+                self.logcat = Some(LogcatPanel {
+                    package,
+                    pid,
+                    lines: VecDeque::new(),
+                });
+                Command::none()
+            }
+            Message::LogcatLine(line) => {
+                let Some(panel) = self.logcat.as_mut() else {
+                    return Command::none();
+                };
+                if panel.lines.len() == LOGCAT_MAX_LINES {
+                    panel.lines.pop_front();
+                }
+                panel.lines.push_back(line);
+                scrollable::snap_to(
+                    scrollable::Id::new("logcat"),
+                    scrollable::RelativeOffset::END,
+                )
+            }
+            Message::LogcatStopped => {
+                self.logcat = None;
+                Command::none()
+            }
+            Message::SaveApkPressed => {
+                let Some(package) = self
+                    .phone_packages
+                    .get(self.selected_user.unwrap_or_default().index)
+                    .and_then(|rows| rows.get(self.current_package_index))
+                else {
+                    return Command::none();
+                };
+                let name = package.name.clone();
+                let user = (selected_device.android_sdk >= 21)
+                    .then_some(self.selected_user)
+                    .flatten();
+                Command::perform(
+                    async move {
+                        let folder = open_folder()
+                            .await
+                            .map_err(|_| "no folder selected".to_string())?;
+                        pull_apks(&name, user.as_ref(), &folder)
+                    },
+                    Message::ApkSaved,
+                )
+            }
+            Message::ApkSaved(res) => {
+                self.apk_save_status = Some(match res {
+                    Ok(files) => match files.first().and_then(|f| f.parent()) {
+                        Some(dir) => format!("{} APK saved in {}", files.len(), dir.display()),
+                        None => "No APK saved".to_string(),
+                    },
+                    Err(e) => {
+                        error!("[APK] {e}");
+                        format!("APK not saved: {e}")
+                    }
+                });
+                Command::none()
+            }
+            Message::ComponentsPressed => {
+                if self.components.take().is_some() {
+                    Command::none()
+                } else {
+                    self.fetch_components()
+                }
+            }
+            Message::ComponentsFetched(package, components) => {
+                self.components = Some((package, components));
+                Command::none()
+            }
+            Message::ComponentToggled(component, enable) => {
+                let user = self.selected_user.unwrap_or_default();
+                let actions =
+                    match component_state_commands(&component, enable, &user, selected_device) {
+                        Ok(actions) => actions,
+                        Err(e) => {
+                            error!("{e}");
+                            return Command::none();
+                        }
+                    };
+                let package = component
+                    .split_once('/')
+                    .map_or_else(String::new, |(package, _)| package.to_string());
+                Command::perform(
+                    async move {
+                        for action in actions {
+                            if let Err(e) = perform_adb_commands(action, CommandType::Shell).await {
+                                error!("{e}");
+                            }
+                        }
+                        let components = package_components(&package, &user);
+                        (package, components)
+                    },
+                    |(package, components)| Message::ComponentsFetched(package, components),
+                )
+            }
+            Message::SuspendStateFetched(user_index, pkg_index, suspended) => {
+                if let Some(package) = self
+                    .phone_packages
+                    .get_mut(user_index)
+                    .and_then(|rows| rows.get_mut(pkg_index))
+                {
+                    package.suspended = Some(suspended);
+                }
+                Command::none()
+            }
+        }
+    }
+
+    /// Run the state changes of every selected package
+    fn apply_selection(
+        &mut self,
+        settings: &Settings,
+        selected_device: &Phone,
+    ) -> Command<Message> {
+        let mut commands = vec![];
+        self.selected_packages.sort_unstable();
+        self.selected_packages.dedup();
+        let selections = self.selections(settings.device.user_scope());
+        self.start_batch(settings.general.adb_concurrency);
+        for selection in &selections {
+            commands.append(&mut build_action_pkg_commands(
+                &self.phone_packages,
+                selected_device,
+                &settings.device,
+                *selection,
+                &mut self.batch,
+            ));
+        }
+        Command::batch(commands)
+    }
+
+    /// The selections to apply. Each one applies to its own user, except with `AllUsers`
+    /// where there is one per package: the one of the selected user when it's selected there.
+    fn selections(&self, scope: UserScope) -> Vec<(usize, usize)> {
+        let i_user = self.selected_user.unwrap_or_default().index;
+        let mut selections = self.selected_packages.clone();
+        selections.sort_unstable();
+        selections.dedup();
+        if scope == UserScope::AllUsers {
+            selections.sort_by_key(|s| (s.1, s.0 != i_user));
+            selections.dedup_by_key(|s| s.1);
+        }
+        selections
+    }
+
+    /// Reset the batch tracking, unless operations are still running
+    /// in which case new ones join the current batch.
+    fn start_batch(&mut self, concurrency: usize) {
+        if !self.batch.is_running() {
+            self.batch = BatchOperation {
+                slots: Arc::new(BatchSlots::new(concurrency)),
+                ..BatchOperation::default()
+            };
+            self.batch_report = None;
+            self.failed_changes.clear();
+        }
+    }
+
+    /// Once every task of a batch has either run or been skipped, record it as the last
+    /// debloat of the device and report the failed ones so they can be retried.
+    /// A cancelled batch also reloads the packages so the list reflects the partial completion.
+    fn on_batch_progress(
+        &mut self,
+        settings: &mut Settings,
+        selected_device: &Phone,
+    ) -> Command<Message> {
+        if self.batch.is_running() {
+            return Command::none();
+        }
+        let failed = self.batch.failed.len();
+        if self.batch.ran > failed {
+            settings.device.record_debloat(self.batch.ran - failed);
+            Config::save_changes(settings, &selected_device.adb_id);
+        }
+        if !self.batch.is_cancelled() {
+            if failed > 0 {
+                let report = format!(
+                    "{} of {} changes applied, {failed} failed",
+                    self.batch.ran - failed,
+                    self.batch.total
+                );
+                warn!("{report}");
+                self.batch_report = Some(report);
+                self.failed_changes = std::mem::take(&mut self.batch.failed);
+            }
+            return Command::none();
         }
+        let report = format!(
+            "Cancelled: {} of {} operations ran, {failed} failed",
+            self.batch.ran, self.batch.total
+        );
+        info!("{report}");
+        self.batch_report = Some(report);
+        self.failed_changes = std::mem::take(&mut self.batch.failed);
+        self.batch = BatchOperation::default();
+        self.selected_packages.clear();
+        self.loading_state = LoadingState::LoadingPackages;
+        Command::perform(
+            Self::load_packages(
+                self.uad_lists.clone(),
+                selected_device.user_list.clone(),
+                settings.general.show_absent_packages,
+                settings.general.protected_packages.clone(),
+            ),
+            Message::ApplyFilters,
+        )
     }
 
     /// Builds the main view for the app list interface
@@ -363,17 +1492,14 @@ impl List {
                 style::Text::Default,
             ),
             LoadingState::FindingPhones => {
-                if self.is_adb_satisfied {
-                    waiting_view("Finding connected devices...", None, style::Text::Default)
-                } else {
+                if selected_device.unauthorized {
                     waiting_view(
-                        "ADB is not installed on your system, install ADB and relaunch application.",
-                        Some(button("Read on how to get started.")
-                    .on_press(Message::GoToUrl(PathBuf::from(
-                        "https://github.com/Universal-Debloater-Alliance/universal-android-debloater-next-generation/wiki/Getting-started",
-                    )))),
-                        style::Text::Danger,
+                        "Accept the USB debugging prompt on your phone",
+                        None,
+                        style::Text::Commentary,
                     )
+                } else {
+                    self.adb_status_view()
                 }
             }
             LoadingState::LoadingPackages => waiting_view(
@@ -386,8 +1512,12 @@ impl List {
                 None,
                 style::Text::Default,
             ),
-            LoadingState::RestoringDevice(device) => waiting_view(
-                &format!("Restoring device: {device}"),
+            LoadingState::RestoringDevice {
+                package,
+                done,
+                total,
+            } => waiting_view(
+                &format!("Restoring device: {package} ({done} of {total})"),
                 None,
                 style::Text::Default,
             ),
@@ -397,15 +1527,131 @@ impl List {
                 Some(button("Go back").on_press(Message::LoadUadList(false))),
                 style::Text::Danger,
             ),
+            LoadingState::DeviceNotFound(err) => waiting_view(
+                &format!("{err}\nConnect it and refresh, or select another device."),
+                None,
+                style::Text::Danger,
+            ),
+            LoadingState::DeviceDisconnected(serial) => waiting_view(
+                &format!(
+                    "Device {serial} disconnected.\n\
+                    Plug it back in to resume, or select another device."
+                ),
+                None,
+                style::Text::Danger,
+            ),
+        }
+    }
+
+    /// Check `p` again once `disable_recheck_delay` has passed,
+    /// as some OEMs let Google Play Services re-enable disabled packages.
+    fn schedule_recheck(&mut self, settings: &Settings, selected_device: &Phone, p: &PackageInfo) {
+        let user = selected_device
+            .user_list
+            .iter()
+            .find(|u| u.index == p.i_user)
+            .copied();
+        self.disable_rechecks.push(DisableRecheck {
+            serial: selected_device.adb_id.clone(),
+            package: self.phone_packages[p.i_user][p.index].name.clone(),
+            user_index: p.i_user,
+            user: (selected_device.android_sdk >= 21)
+                .then_some(user)
+                .flatten(),
+            due: Instant::now() + Duration::from_secs(settings.general.disable_recheck_delay),
+        });
+    }
+
+    fn oem_banner(&self) -> Option<Element<'_, Message, Theme, Renderer>> {
+        let oem = self.oem?;
+        let toggle_btn = if self.oem_only {
+            button_primary("Show all packages").on_press(Message::OemOnly(false))
+        } else {
+            button_primary(text(format!("Only {} packages", oem.name)))
+                .on_press(Message::OemOnly(true))
+        };
+        Some(
+            container(
+                row![
+                    text(format!(
+                        "Detected {} device: its pre-installed apps are mostly documented in the `oem` list.",
+                        oem.name
+                    )),
+                    Space::new(Length::Fill, Length::Shrink),
+                    toggle_btn,
+                ]
+                .spacing(10)
+                .align_items(Alignment::Center),
+            )
+            .padding([5, 10])
+            .width(Length::Fill)
+            .style(style::Container::Frame)
+            .into(),
+        )
+    }
+
+    fn adb_status_view(&self) -> Element<'_, Message, Theme, Renderer> {
+        let get_started_btn = || {
+            Some(button(t!("adb.get_started")).on_press(Message::GoToUrl(PathBuf::from(
+                "https://github.com/Universal-Debloater-Alliance/universal-android-debloater-next-generation/wiki/Getting-started",
+            ))))
+        };
+        match &self.adb_status {
+            AdbStatus::NotFound => waiting_view(
+                t!("adb.not_installed"),
+                get_started_btn(),
+                style::Text::Danger,
+            ),
+            AdbStatus::Broken { version, error } => waiting_view(
+                &format!(
+                    "ADB{} is installed but doesn't work: {error}\n\
+                    Update the Android platform-tools, or run `adb kill-server` and refresh.",
+                    version
+                        .as_ref()
+                        .map(|v| format!(" {v}"))
+                        .unwrap_or_default()
+                ),
+                get_started_btn(),
+                style::Text::Danger,
+            ),
+            AdbStatus::NoDevices { .. } => waiting_view(
+                "Finding connected devices...\nPlug in your phone and enable USB debugging.",
+                None,
+                style::Text::Default,
+            ),
+            AdbStatus::Unknown | AdbStatus::DevicesPresent { .. } => {
+                waiting_view("Finding connected devices...", None, style::Text::Default)
+            }
         }
     }
 
-    fn control_panel(&self, selected_device: &Phone) -> Element<Message, Theme, Renderer> {
+    fn control_panel(
+        &self,
+        settings: &Settings,
+        selected_device: &Phone,
+    ) -> Element<Message, Theme, Renderer> {
         let search_packages = text_input("Search packages...", &self.input_value)
             .width(Length::Fill)
             .on_input(Message::SearchInputChanged)
+            .on_submit(Message::SearchSubmitted)
             .padding([5, 10]);
 
+        let search_history_picklist = (!settings.general.search_history.is_empty()).then(|| {
+            pick_list(
+                settings.general.search_history.clone(),
+                None::<String>,
+                Message::SearchHistorySelected,
+            )
+            .placeholder("Recent")
+            .width(100)
+        });
+
+        let search_field_picklist = pick_list(
+            SearchField::ALL,
+            Some(self.search_field),
+            Message::SearchFieldSelected,
+        );
+
         let select_all_checkbox = checkbox("", self.all_selected)
             .on_toggle(Message::ToggleAllSelected)
             .style(style::CheckBox::SettingsEnabled)
@@ -424,14 +1670,27 @@ impl List {
         .gap(4)]
         .padding(8);
 
-        let user_picklist = pick_list(
-            selected_device.user_list.clone(),
-            self.selected_user,
-            Message::UserSelected,
-        )
-        .width(85);
+        // Protected users can't be acted on, see `selected_user_protected`
+        let users: Vec<User> = selected_device
+            .user_list
+            .iter()
+            .filter(|u| settings.general.show_protected_users || !u.protected)
+            .copied()
+            .collect();
+        let user_picklist = pick_list(users, self.selected_user, Message::UserSelected).width(85);
 
         let list_picklist = pick_list(UadList::ALL, self.selected_list, Message::ListSelected);
+        let unlisted_count = tooltip(
+            button(text(format!("{} unlisted", self.unlisted_count())))
+                .on_press(Message::ListSelected(UadList::Unlisted))
+                .style(style::Button::Primary)
+                .padding([5, 10]),
+            "Packages on the device that aren't documented in the lists yet.\n\
+            Click to show only them.",
+            tooltip::Position::Bottom,
+        )
+        .style(style::Container::Tooltip)
+        .gap(4);
         let package_state_picklist = pick_list(
             PackageState::ALL,
             self.selected_package_state,
@@ -444,21 +1703,293 @@ impl List {
             Message::RemovalSelected,
         );
 
-        row![
-            col_sel_all,
-            search_packages,
-            user_picklist,
-            removal_picklist,
-            package_state_picklist,
-            list_picklist,
+        let sort_picklist = pick_list(SortBy::ALL, Some(self.sort_by), Message::SortSelected);
+
+        row![col_sel_all, search_packages, search_field_picklist]
+            .push_maybe(search_history_picklist)
+            .push(user_picklist)
+            .push(removal_picklist)
+            .push(package_state_picklist)
+            .push(list_picklist)
+            .push(unlisted_count)
+            .push(self.modified_checkbox())
+            .push(
+                checkbox("Bookmarks", self.bookmarks_only)
+                    .on_toggle(Message::BookmarksOnly)
+                    .style(style::CheckBox::SettingsEnabled),
+            )
+            .push(
+                tooltip(
+                    checkbox("Since backup", self.backup_states.is_some())
+                        .on_toggle(Message::ChangedSinceBackup)
+                        .style(style::CheckBox::SettingsEnabled),
+                    "Only show the packages in another state than in the latest backup \
+                    of the device, whatever their state",
+                    tooltip::Position::Bottom,
+                )
+                .style(style::Container::Tooltip)
+                .gap(4),
+            )
+            .push(sort_picklist)
+            .push_maybe(root_badge(selected_device))
+            .width(Length::Fill)
+            .align_items(Alignment::Center)
+            .spacing(6)
+            .padding([0, 16, 0, 0])
+            .into()
+    }
+
+    /// See [`Self::modified_only`]
+    fn modified_checkbox(&self) -> Element<'_, Message, Theme, Renderer> {
+        tooltip(
+            checkbox(
+                format!("Modified ({})", self.modified_count()),
+                self.modified_only,
+            )
+            .on_toggle(Message::ModifiedOnly)
+            .style(style::CheckBox::SettingsEnabled),
+            "Only show the packages changed since the list was loaded, whatever their state.\n\
+            Useful to review your changes, or spot one the device reverted.",
+            tooltip::Position::Bottom,
+        )
+        .style(style::Container::Tooltip)
+        .gap(4)
+        .into()
+    }
+
+    /// "Retry failed" button listing the failed changes of the last batch in its tooltip
+    fn retry_failed_button(&self) -> Option<Element<'_, Message, Theme, Renderer>> {
+        if self.failed_changes.is_empty() {
+            return None;
+        }
+        let failed = self
+            .failed_changes
+            .iter()
+            .filter_map(|item| {
+                self.phone_packages
+                    .get(item.user.index)
+                    .and_then(|rows| rows.get(item.index))
+                    .map(|p| format!("{} ({})", p.name, item.user))
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        Some(
+            tooltip(
+                button_primary(text(format!(
+                    "Retry failed ({})",
+                    self.failed_changes.len()
+                )))
+                .on_press(Message::RetryFailed),
+                text(failed),
+                tooltip::Position::Top,
+            )
+            .style(style::Container::Tooltip)
+            .gap(4)
+            .into(),
+        )
+    }
+
+    /// Measure the selected packages to uninstall, for the estimate of the review modal
+    fn fetch_apk_sizes(&self, settings: &Settings, device: &Phone) -> Command<Message> {
+        let packages: Vec<String> = self
+            .phone_packages
+            .get(self.selected_user.unwrap_or_default().index)
+            .into_iter()
+            .flatten()
+            .filter(|p| {
+                p.selected
+                    && p.apk_size.is_none()
+                    && p.state == PackageState::Enabled
+                    && !p.disable_mode(settings)
+            })
+            .map(|p| p.name.clone())
+            .collect();
+        if packages.is_empty() {
+            return Command::none();
+        }
+        let user = (device.android_sdk >= 21)
+            .then_some(self.selected_user)
+            .flatten();
+        Command::perform(
+            async move {
+                let sizes = reclaimable_apk_sizes(&packages, user.as_ref());
+                // Packages that couldn't be measured count as 0 rather than being measured again
+                packages
+                    .into_iter()
+                    .map(|p| {
+                        let size = sizes.get(&p).copied().unwrap_or_default();
+                        (p, size)
+                    })
+                    .collect()
+            },
+            Message::ApkSizesFetched,
+        )
+    }
+
+    /// List the components of the current package for the selected user
+    fn fetch_components(&self) -> Command<Message> {
+        let Some(package) = self
+            .phone_packages
+            .get(self.selected_user.unwrap_or_default().index)
+            .and_then(|rows| rows.get(self.current_package_index))
+        else {
+            return Command::none();
+        };
+        let name = package.name.clone();
+        let user = self.selected_user.unwrap_or_default();
+        Command::perform(
+            async move {
+                let components = package_components(&name, &user);
+                (name, components)
+            },
+            |(name, components)| Message::ComponentsFetched(name, components),
+        )
+    }
+
+    /// Follows the logcat of the package picked in the logcat panel, while it's open
+    pub fn subscription(&self) -> Subscription<Message> {
+        let logcat = match self.logcat.as_ref().and_then(|panel| panel.pid) {
+            Some(pid) => subscription::run_with_id(("logcat", pid), logcat_stream(pid))
+                .map(Message::LogcatLine),
+            None => Subscription::none(),
+        };
+        let rechecks = if self.disable_rechecks.is_empty() {
+            Subscription::none()
+        } else {
+            subscription::run_with_id("disable-rechecks", ticks(Duration::from_secs(1)))
+                .map(Message::RecheckTick)
+        };
+        // Restarted by each input, so it only fires once typing paused
+        let input = self.search_inputs;
+        let search = if self.search_settling {
+            subscription::run_with_id(("search", input), ticks(SEARCH_DEBOUNCE))
+                .map(move |_| Message::SearchSettled(input))
+        } else {
+            Subscription::none()
+        };
+        let copied = self.copied.iter().map(|&(i_user, i_package)| {
+            subscription::run_with_id(("copied", i_user, i_package), ticks(COPIED_DELAY))
+                .map(move |_| Message::CopiedExpired(i_user, i_package))
+        });
+        Subscription::batch([logcat, rechecks, search].into_iter().chain(copied))
+    }
+
+    /// The guided debloat for new users: the selection is replaced by the enabled
+    /// Recommended packages of the user, kept packages excluded, and the review modal opens.
+    /// Expert and Unsafe packages are never selected.
+    fn safe_debloat(
+        &mut self,
+        settings: &mut Settings,
+        selected_device: &mut Phone,
+        list_update_state: &mut UadListState,
+    ) -> Command<Message> {
+        let i_user = self.selected_user.unwrap_or_default().index;
+        // Whatever was selected before isn't necessarily safe
+        for package in self.phone_packages.iter_mut().flatten() {
+            package.selected = false;
+        }
+        self.selected_packages.clear();
+        self.all_selected = false;
+
+        let recommended: Vec<usize> = self.phone_packages[i_user]
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| {
+                p.on_device
+                    && p.removal == Removal::Recommended
+                    && p.state == PackageState::Enabled
+                    && !p.is_kept(settings)
+            })
+            .map(|(i, _)| i)
+            .collect();
+        if recommended.is_empty() {
+            self.batch_report = Some("No enabled Recommended package left to remove".to_string());
+            return Command::none();
+        }
+        #[expect(unused_must_use, reason = "side-effect")]
+        for i in recommended {
+            self.update(
+                settings,
+                selected_device,
+                list_update_state,
+                Message::List(i, RowMessage::ToggleSelection(true)),
+            );
+        }
+        self.update(
+            settings,
+            selected_device,
+            list_update_state,
+            Message::ApplyActionOnSelection,
+        )
+    }
+
+    /// Record a package action in the activity log
+    fn log_activity(&mut self, package: String, command: String, outcome: ActivityOutcome) {
+        if self.activity.len() == ACTIVITY_MAX_ENTRIES {
+            self.activity.pop_front();
+        }
+        self.activity.push_back(ActivityEntry {
+            package,
+            command,
+            outcome,
+        });
+    }
+
+    /// The "Activity log" expander, with the log itself when expanded
+    fn activity_view(&self) -> Element<'_, Message, Theme, Renderer> {
+        let label = if self.activity_expanded {
+            format!("Activity log ({}) \u{25BE}", self.activity.len())
+        } else {
+            format!("Activity log ({}) \u{25B8}", self.activity.len())
+        };
+        let header = row![
+            button(text(label).size(12))
+                .padding([2, 6])
+                .on_press(Message::ActivityLogPressed)
+                .style(style::Button::Primary),
+            horizontal_space(),
         ]
+        .push_maybe(
+            (self.activity_expanded && !self.activity.is_empty()).then(|| {
+                button(text("Copy").size(12))
+                    .padding([2, 6])
+                    .on_press(Message::CopyActivityLog)
+                    .style(style::Button::Primary)
+            }),
+        )
+        .align_items(Alignment::Center);
+        if !self.activity_expanded {
+            return header.into();
+        }
+        let entries = self.activity.iter().fold(column![], |col, entry| {
+            let style = match entry.outcome {
+                ActivityOutcome::Success => style::Text::Default,
+                ActivityOutcome::Fallback(_) => style::Text::Commentary,
+                ActivityOutcome::Failure(_) => style::Text::Danger,
+            };
+            col.push(text(entry).size(12).style(style))
+        });
+        container(
+            column![
+                header,
+                scrollable(entries)
+                    .width(Length::Fill)
+                    .height(Length::Fixed(150.0))
+                    .style(style::Scrollable::Description),
+            ]
+            .spacing(4),
+        )
+        .padding(6)
         .width(Length::Fill)
-        .align_items(Alignment::Center)
-        .spacing(6)
-        .padding([0, 16, 0, 0])
+        .style(style::Container::Frame)
         .into()
     }
 
+    /// Protected users (e.g. work profiles) are listed but ADB isn't allowed to change them
+    fn selected_user_protected(&self) -> bool {
+        self.selected_user.is_some_and(|u| u.protected)
+    }
+
     // TODO: refactor later
     #[allow(clippy::too_many_lines)]
     fn ready_view(
@@ -466,13 +1997,35 @@ impl List {
         settings: &Settings,
         selected_device: &Phone,
     ) -> Element<Message, Theme, Renderer> {
+        let i_user = self.selected_user.unwrap_or_default().index;
+        let users: Vec<User> = selected_device
+            .user_list
+            .iter()
+            .filter(|&u| !u.protected)
+            .copied()
+            .collect();
         let packages = self
             .filtered_packages
             .iter()
             .fold(column![].spacing(6), |col, &i| {
+                let package = &self.phone_packages[i_user][i];
+                let user_states: Vec<(User, PackageState)> = if users.len() > 1 {
+                    users
+                        .iter()
+                        .filter_map(|u| {
+                            self.phone_packages
+                                .get(u.index)
+                                .and_then(|rows| rows.get(i))
+                                .filter(|p| p.name == package.name)
+                                .map(|p| (*u, p.state))
+                        })
+                        .collect()
+                } else {
+                    vec![]
+                };
                 col.push(
-                    self.phone_packages[self.selected_user.unwrap_or_default().index][i]
-                        .view(settings, selected_device)
+                    package
+                        .view(settings, selected_device, &user_states)
                         .map(move |msg| Message::List(i, msg)),
                 )
             });
@@ -481,8 +2034,87 @@ impl List {
             .height(Length::FillPortion(6))
             .style(style::Scrollable::Packages);
 
-        let description_scroll = scrollable(text(&self.description).width(Length::Fill))
-            .style(style::Scrollable::Description);
+        let current_package = self
+            .phone_packages
+            .get(self.selected_user.unwrap_or_default().index)
+            .and_then(|rows| rows.get(self.current_package_index));
+        let split_info = match current_package {
+            Some(p) if p.is_stub() => "Stub app: the real app is downloaded on first run, \
+                restoring it may only bring back the stub"
+                .to_string(),
+            _ => current_package
+                .and_then(PackageRow::split_parts)
+                .map_or_else(String::new, |parts| format!("Split APK ({parts} parts)")),
+        };
+
+        let save_apk_btn = current_package
+            .filter(|p| p.on_device && p.state != PackageState::Uninstalled)
+            .map(|_| {
+                tooltip(
+                    button(text("Save APK").size(12))
+                        .padding([2, 6])
+                        .on_press(Message::SaveApkPressed)
+                        .style(style::Button::Primary),
+                    "Pull the APK files into a folder, to sideload the app back if needed",
+                    tooltip::Position::Top,
+                )
+                .style(style::Container::Tooltip)
+                .gap(4)
+            });
+
+        // `pidof` and `logcat --pid` require Android 7.0+
+        let logcat_btn =
+            (settings.general.expert_mode && selected_device.android_sdk >= 24).then(|| {
+                button(text("Logcat").size(12))
+                    .padding([2, 6])
+                    .on_press(Message::LogcatPressed)
+                    .style(style::Button::Primary)
+            });
+
+        let components_btn = settings.general.expert_mode.then(|| {
+            let label = if self.components.is_some() {
+                "Advanced: components \u{25BE}"
+            } else {
+                "Advanced: components \u{25B8}"
+            };
+            button(text(label).size(12))
+                .padding([2, 6])
+                .on_press(Message::ComponentsPressed)
+                .style(style::Button::Primary)
+        });
+
+        let description_scroll: Element<Message, Theme, Renderer> = match &self.logcat {
+            Some(panel) => logcat_view(panel),
+            None => scrollable(
+                column![
+                    row![
+                        text(split_info).style(style::Text::Commentary),
+                        text(self.apk_save_status.as_deref().unwrap_or_default())
+                            .style(style::Text::Commentary),
+                        horizontal_space()
+                    ]
+                    .push_maybe(save_apk_btn)
+                    .push_maybe((!self.description.is_empty()).then(|| {
+                        button(text("Pop out").size(12))
+                            .padding([2, 6])
+                            .on_press(Message::DescriptionPopOut)
+                            .style(style::Button::Primary)
+                    }))
+                    .push_maybe(components_btn)
+                    .push_maybe(logcat_btn)
+                    .spacing(6),
+                    text(&self.description).width(Length::Fill),
+                ]
+                .push_maybe(
+                    self.components
+                        .as_ref()
+                        .map(|(package, components)| components_view(package, components)),
+                )
+                .spacing(4),
+            )
+            .style(style::Scrollable::Description)
+            .into(),
+        };
 
         let description_panel = container(description_scroll)
             .padding(6)
@@ -490,12 +2122,13 @@ impl List {
             .width(Length::Fill)
             .style(style::Container::Frame);
 
+        let user_protected = self.selected_user_protected();
         let review_selection = {
             let tmp_widget = text(format!(
                 "Review selection ({})",
                 self.selected_packages.len()
             ));
-            if self.selected_packages.is_empty() {
+            if self.selected_packages.is_empty() || user_protected {
                 button(tmp_widget).padding([5, 10])
             } else {
                 button_primary(tmp_widget).on_press(Message::ApplyActionOnSelection)
@@ -515,9 +2148,54 @@ impl List {
         // lock
         let export_selection = export_selection;
 
+        let safe_debloat = tooltip(
+            if user_protected || self.batch.is_running() {
+                button(text("Safe debloat (Recommended only)")).padding([5, 10])
+            } else {
+                button_primary(text("Safe debloat (Recommended only)"))
+                    .on_press(Message::SafeDebloat)
+            },
+            "Replace the selection with the enabled Recommended packages, kept ones excepted, \
+            and review it",
+            tooltip::Position::Top,
+        )
+        .style(style::Container::Tooltip)
+        .gap(4);
+
+        let batch_status = if user_protected {
+            row![text(format!(
+                "ADB can't act on {} (work profile or Secure Folder): select another user",
+                self.selected_user.unwrap_or_default()
+            ))
+            .style(style::Text::Danger)]
+        } else if self.batch.is_running() {
+            let cancel_btn = if self.batch.is_cancelled() {
+                button(text("Cancelling...")).padding([5, 10])
+            } else {
+                button_primary(text("Cancel")).on_press(Message::CancelBatch)
+            };
+            row![
+                text(format!(
+                    "Applying ({}/{})",
+                    self.batch.ran + self.batch.skipped,
+                    self.batch.total
+                )),
+                cancel_btn,
+            ]
+        } else {
+            row![text(self.batch_report.clone().unwrap_or_default()).style(style::Text::Commentary)]
+                .push_maybe(self.retry_failed_button())
+        }
+        .spacing(10)
+        .align_items(Alignment::Center);
+
+        let selection_summary = self.selection_summary(settings, i_user);
+
         let action_row = row![
+            safe_debloat,
             export_selection,
             Space::new(Length::Fill, Length::Shrink),
+            batch_status,
             review_selection
         ]
         .width(Length::Fill)
@@ -539,8 +2217,15 @@ impl List {
                 .center_x()
                 .style(style::Container::BorderedFrame);
 
-        let control_panel = self.control_panel(selected_device);
-        let content = if selected_device.user_list.is_empty()
+        let control_panel = self.control_panel(settings, selected_device);
+        let content = if fully_managed(selected_device) {
+            column![
+                control_panel,
+                container(fully_managed_view())
+                    .height(Length::Fill)
+                    .center_y(),
+            ]
+        } else if selected_device.user_list.is_empty()
             || match self.selected_user {
                 Some(u) => !self.phone_packages[u.index].is_empty(),
                 // If no user has been selected,
@@ -549,13 +2234,16 @@ impl List {
                 // However, this is inconsistent,
                 // because other parts of the code simply use a `default` `User`.
                 None => true,
-            } {
-            column![
-                control_panel,
-                packages_scrollable,
-                description_panel,
-                action_row,
-            ]
+            }
+        {
+            column![control_panel]
+                .push_maybe(self.oem_banner())
+                .push(self.removal_counts_view())
+                .push(packages_scrollable)
+                .push(description_panel)
+                .push(self.activity_view())
+                .push_maybe(selection_summary)
+                .push(action_row)
         } else {
             column![
                 control_panel,
@@ -612,13 +2300,131 @@ impl List {
                 .into();
         }
 
-        if let Some(err) = &self.error_modal {
+        if let Some((p, err)) = &self.root_removal_modal {
+            let package = &self.phone_packages[p.i_user][p.index].name;
+            root_removal_view(package, err, content).into()
+        } else if let Some(p) = &self.clear_data_modal {
+            let package = &self.phone_packages[p.i_user][p.index].name;
+            let user = self.selected_user.unwrap_or_default();
+            clear_data_view(package, user, content).into()
+        } else if let Some(err) = &self.error_modal {
             error_view(err, content).into()
+        } else if self.description_modal {
+            let package = self
+                .selected_user
+                .and_then(|u| self.phone_packages.get(u.index))
+                .and_then(|rows| rows.get(self.current_package_index))
+                .map_or("", |p| p.name.as_str());
+            description_view(package, &self.description, content).into()
         } else {
             container(content).height(Length::Fill).padding(10).into()
         }
     }
 
+    /// Compact, live version of the review modal recap
+    fn selection_summary(
+        &self,
+        settings: &Settings,
+        i_user: usize,
+    ) -> Option<Element<'_, Message, Theme, Renderer>> {
+        let packages = self.phone_packages.get(i_user)?;
+        let summaries = summarize_selection(packages, settings);
+        let (discard, restore) = summaries
+            .iter()
+            .fold((0, 0), |(d, r), s| (d + s.discard, r + s.restore));
+        if discard + restore == 0 {
+            return None;
+        }
+
+        let (discard_label, restore_label) = if settings.device.disable_mode {
+            ("disable", "enable")
+        } else {
+            ("uninstall", "restore")
+        };
+        let details = summaries
+            .iter()
+            .filter(|s| s.discard + s.restore > 0)
+            .map(|s| format!("{}: {}/{}", s.category, s.discard, s.restore))
+            .collect::<Vec<String>>()
+            .join("  ");
+
+        Some(
+            row![
+                text(format!("{discard} to {discard_label}")).style(style::Text::Danger),
+                text(format!("{restore} to {restore_label}")).style(style::Text::Ok),
+                text(details).style(style::Text::Commentary),
+            ]
+            .spacing(15)
+            .width(Length::Fill)
+            .padding([0, 10])
+            .into(),
+        )
+    }
+
+    /// Pre-flight check of the review modal when the selection applies to all users,
+    /// from the states already fetched for each user
+    fn cross_user_impact(&self, device: &Phone, settings: &Settings) -> Vec<UserImpact> {
+        let i_user = self.selected_user.unwrap_or_default().index;
+        let selection: Vec<&PackageRow> = self
+            .selected_packages
+            .iter()
+            .filter(|s| s.0 == i_user)
+            .filter_map(|s| self.phone_packages.get(s.0)?.get(s.1))
+            .collect();
+        let wanted = |p: &PackageRow| p.state.opposite(p.disable_mode(settings));
+        device
+            .user_list
+            .iter()
+            .filter(|u| !u.protected)
+            .map(|&user| {
+                let mut impact = UserImpact {
+                    user,
+                    changed: 0,
+                    unchanged: 0,
+                    absent: 0,
+                };
+                let rows = self.phone_packages.get(user.index);
+                for p in &selection {
+                    match rows.and_then(|rows| rows.iter().find(|r| r.name == p.name)) {
+                        Some(r) if !r.on_device => impact.absent += 1,
+                        Some(r) if r.state == wanted(p) => impact.unchanged += 1,
+                        Some(_) => impact.changed += 1,
+                        None => impact.absent += 1,
+                    }
+                }
+                impact
+            })
+            .collect()
+    }
+
+    /// `(package, warning)` for the packages of the selection which other apps may depend on,
+    /// see [`dependency_warning`]
+    fn dependency_warnings(&self) -> Vec<(String, String)> {
+        let i_user = self.selected_user.unwrap_or_default().index;
+        self.selected_packages
+            .iter()
+            .filter(|s| s.0 == i_user)
+            .filter_map(|s| self.phone_packages.get(s.0)?.get(s.1))
+            // Only removals can break other apps
+            .filter(|p| p.state == PackageState::Enabled)
+            .filter_map(|p| {
+                dependency_warning(&p.name, self.uad_lists.get(&p.name))
+                    .map(|warning| (p.name.clone(), warning))
+            })
+            .collect()
+    }
+
+    /// `(package, reason)` for the packages of the selection for which
+    /// [`apply_pkg_state_commands`] builds no command: applying would silently do nothing.
+    fn noop_selection(&self, device: &Phone, settings: &Settings) -> Vec<(String, String)> {
+        let items: Vec<BatchItem> = self
+            .selections(settings.device.user_scope())
+            .into_iter()
+            .flat_map(|s| selection_items(&self.phone_packages, device, &settings.device, s))
+            .collect();
+        noop_items(&self.phone_packages, device, &items)
+    }
+
     // TODO: refactor later
     #[allow(clippy::too_many_lines)]
     fn apply_selection_modal(
@@ -629,15 +2435,7 @@ impl List {
     ) -> Element<Message, Theme, Renderer> {
         const PACK_NO_USER_MSG: &str = "`selected_packages` implies a user must be selected";
 
-        // 5 element slice is cheap
-        let mut summaries = Removal::CATEGORIES.map(SummaryEntry::from);
-        for p in packages.iter().filter(|p| p.selected) {
-            let summary = &mut summaries[p.removal as usize];
-            match p.state {
-                PackageState::Uninstalled | PackageState::Disabled => summary.restore += 1,
-                _ => summary.discard += 1,
-            }
-        }
+        let summaries = summarize_selection(packages, settings);
 
         let radio_btn_users = device.user_list.iter().filter(|&u| !u.protected).fold(
             row![].spacing(10),
@@ -703,7 +2501,17 @@ impl List {
             .iter()
             .fold(column![].spacing(6).width(Length::Fill), |col, r| {
                 col.push(recap(settings, r))
-            });
+            })
+            .push_maybe(reclaimed_view(&summaries))
+            .push_maybe(self.other_users_view(settings))
+            .push_maybe(warnings_view(
+                "Other apps may stop working without:",
+                &self.dependency_warnings(),
+            ))
+            .push_maybe(warnings_view(
+                "Nothing will be done for:",
+                &self.noop_selection(device, settings),
+            ));
 
         let selected_pkgs_ctn = container(
             container(
@@ -746,7 +2554,10 @@ impl List {
                                                     .state
                                                 {
                                                     PackageState::Enabled =>
-                                                        if settings.device.disable_mode {
+                                                        if self.phone_packages[selection.0]
+                                                            [selection.1]
+                                                            .disable_mode(settings)
+                                                        {
                                                             text("Disable")
                                                                 .style(style::Text::Danger)
                                                         } else {
@@ -787,19 +2598,20 @@ impl List {
 
         container(
             if device.user_list.iter().filter(|&u| !u.protected).count() > 1
-                && settings.device.multi_user_mode
+                && settings.device.user_scope() == UserScope::AllUsers
             {
                 column![
                     title_ctn,
                     users_ctn,
                     row![explaination_ctn].padding([0, 10, 0, 10]),
+                    cross_user_view(&self.cross_user_impact(device, settings)),
                     container(recap_view).padding(10),
                     selected_pkgs_ctn,
                     modal_btn_row,
                 ]
                 .spacing(10)
                 .align_items(Alignment::Center)
-            } else if !settings.device.multi_user_mode {
+            } else if settings.device.user_scope() == UserScope::SelectedUser {
                 column![
                     title_ctn,
                     users_ctn,
@@ -826,6 +2638,28 @@ impl List {
         .style(style::Container::Background)
         .into()
     }
+
+    /// Packages selected on other users than the selected one, which only the selected
+    /// user's list shows: with `SelectedUser` they're changed on their own user too.
+    fn other_users_view(
+        &self,
+        settings: &Settings,
+    ) -> Option<Element<'_, Message, Theme, Renderer>> {
+        let i_user = self.selected_user.unwrap_or_default().index;
+        let others = self
+            .selected_packages
+            .iter()
+            .filter(|s| s.0 != i_user)
+            .count();
+        (others > 0 && settings.device.user_scope() == UserScope::SelectedUser).then(|| {
+            text(format!(
+                "{others} package(s) selected on other users will also change, on their own user"
+            ))
+            .style(style::Text::Danger)
+            .into()
+        })
+    }
+
     fn filter_package_lists(&mut self) {
         let list_filter: UadList = self.selected_list.expect("UAD-list type must be selected");
         let package_filter: PackageState = self
@@ -835,36 +2669,133 @@ impl List {
             .selected_removal
             .expect("removal recommendation must be selected");
 
-        self.filtered_packages = self.phone_packages
-            [self.selected_user.expect("User must be selected").index]
+        let user = self.selected_user.expect("User must be selected");
+        let user_id = user.id;
+        let packages = &self.phone_packages[user.index];
+        // we must filter the indices associated with pack-rows,
+        // that's why `enumerate` is before `filter`.
+        let in_scope: Vec<usize> = packages
             .iter()
-            // we must filter the indices associated with pack-rows,
-            // that's why `enumerate` is before `filter`.
             .enumerate()
             .filter(|(_, p)| {
                 (list_filter == UadList::All || p.uad_list == list_filter)
-                    && (package_filter == PackageState::All || p.state == package_filter)
-                    && (removal_filter == Removal::All || p.removal == removal_filter)
-                    && (self.input_value.is_empty() || p.name.contains(&self.input_value))
+                    && if self.modified_only {
+                        p.state != p.previous_state
+                    } else if let Some(states) = &self.backup_states {
+                        // Packages installed since the backup are changes too
+                        p.on_device
+                            && states.get(&user_id).and_then(|states| states.get(&p.name))
+                                != Some(&p.state)
+                    } else {
+                        package_filter == PackageState::All || p.state == package_filter
+                    }
+                    && (!self.oem_only || self.oem.is_some_and(|oem| oem.owns(&p.name)))
+                    && (!self.bookmarks_only || p.bookmarked)
+                    && matches_search(
+                        &p.name,
+                        &p.description,
+                        &self.input_value,
+                        self.search_field,
+                    )
             })
             .map(|(i, _)| i)
             .collect();
+
+        self.removal_counts = Removal::CATEGORIES.map(|removal| {
+            in_scope
+                .iter()
+                .filter(|&&i| packages[i].removal == removal)
+                .count()
+        });
+        self.filtered_packages = in_scope
+            .into_iter()
+            .filter(|&i| removal_filter == Removal::All || packages[i].removal == removal_filter)
+            .collect();
+
+        // Rows are sorted by name, and the sorts are stable: ties keep that order
+        match self.sort_by {
+            SortBy::DeviceOrder => self
+                .filtered_packages
+                .sort_by_key(|&i| packages[i].device_order),
+            SortBy::Name => self
+                .filtered_packages
+                .sort_by_cached_key(|&i| packages[i].name.to_lowercase()),
+            SortBy::Removal => self.filtered_packages.sort_by_key(|&i| packages[i].removal),
+            SortBy::State => self.filtered_packages.sort_by_key(|&i| packages[i].state),
+        }
     }
-    #[expect(clippy::unused_async, reason = "1 call-site")]
-    async fn load_packages(uad_list: PackageHashMap, user_list: Vec<User>) -> Vec<Vec<PackageRow>> {
-        if user_list.len() <= 1 {
-            vec![fetch_packages(&uad_list, None)]
+
+    /// "Recommended: 58 · Advanced: 120 · ...", the scope of each removal category
+    /// with the other filters and the search applied
+    fn removal_counts_view(&self) -> Element<'_, Message, Theme, Renderer> {
+        let counts = Removal::CATEGORIES
+            .iter()
+            .zip(self.removal_counts)
+            .map(|(removal, count)| format!("{removal}: {count}"))
+            .collect::<Vec<_>>()
+            .join(" \u{b7} ");
+        text(counts).size(12).style(style::Text::Commentary).into()
+    }
+
+    /// Number of packages of the selected user changed since the list was loaded
+    fn modified_count(&self) -> usize {
+        self.selected_user
+            .and_then(|u| self.phone_packages.get(u.index))
+            .map_or(0, |packages| {
+                packages
+                    .iter()
+                    .filter(|p| p.state != p.previous_state)
+                    .count()
+            })
+    }
+
+    /// Number of on-device packages of the selected user that aren't in the lists
+    fn unlisted_count(&self) -> usize {
+        self.selected_user
+            .and_then(|u| self.phone_packages.get(u.index))
+            .map_or(0, |packages| {
+                packages
+                    .iter()
+                    .filter(|p| p.on_device && p.uad_list == UadList::Unlisted)
+                    .count()
+            })
+    }
+
+    #[expect(clippy::unused_async, reason = "run by `Command::perform`")]
+    async fn load_packages(
+        uad_list: PackageHashMap,
+        user_list: Vec<User>,
+        show_absent: bool,
+        extra_protected: Vec<String>,
+    ) -> Vec<Vec<PackageRow>> {
+        let mut packages: Vec<Vec<PackageRow>> = if user_list.len() <= 1 {
+            vec![fetch_packages(&uad_list, None, show_absent)]
         } else {
             user_list
                 .iter()
-                .map(|user| fetch_packages(&uad_list, Some(user)))
+                .map(|user| fetch_packages(&uad_list, Some(user), show_absent))
                 .collect()
+        };
+
+        let protected: Vec<String> = PROTECTED_PACKAGES
+            .iter()
+            .map(ToString::to_string)
+            .chain(extra_protected)
+            .chain(active_system_packages())
+            .collect();
+        for p in packages.iter_mut().flatten() {
+            p.protected = is_protected(&p.name, &protected);
         }
+        packages
     }
 
     #[expect(clippy::unused_async, reason = "1 call-site")]
-    async fn init_apps_view(remote: bool, phone: Phone) -> (PackageHashMap, UadListState) {
-        let uad_lists = load_debloat_lists(remote);
+    async fn init_apps_view(
+        remote: bool,
+        list_source: String,
+        phone: Phone,
+    ) -> (PackageHashMap, UadListState) {
+        let uad_lists = load_debloat_lists(remote, &list_source);
         match uad_lists {
             Ok(list) => {
                 env::set_var(ANDROID_SERIAL, phone.adb_id.clone());
@@ -914,6 +2845,161 @@ fn error_view<'a>(
     Modal::new(content, ctn).on_blur(Message::ModalHide)
 }
 
+fn root_removal_view<'a>(
+    package: &'a str,
+    error: &'a str,
+    content: Column<'a, Message, Theme, Renderer>,
+) -> Modal<'a, Message, Theme, Renderer> {
+    let title_ctn = container(
+        row![text("Uninstall restricted by the device").size(24)].align_items(Alignment::Center),
+    )
+    .width(Length::Fill)
+    .style(style::Container::Frame)
+    .padding([10, 0, 10, 0])
+    .center_y()
+    .center_x();
+
+    let text_box = column![
+        text(error).style(style::Text::Commentary),
+        text(format!(
+            "Root access is available: {package} can be deleted from the system partition instead."
+        )),
+        text("This can't be undone without reflashing the system image, and a reboot is needed to complete it.")
+            .style(style::Text::Danger),
+    ]
+    .spacing(10)
+    .width(Length::Fill);
+
+    let modal_btn_row = row![
+        button(text("Cancel")).on_press(Message::ModalHide),
+        horizontal_space(),
+        button(text("Remove with root"))
+            .style(style::Button::UninstallPackage)
+            .on_press(Message::RootRemovalConfirmed),
+    ]
+    .padding([10, 0, 0, 0]);
+
+    let ctn = container(column![title_ctn, text_box, modal_btn_row].spacing(10))
+        .height(Length::Shrink)
+        .width(600)
+        .padding(10)
+        .style(style::Container::Frame);
+
+    Modal::new(content, ctn).on_blur(Message::ModalHide)
+}
+
+fn description_view<'a>(
+    package: &'a str,
+    description: &'a str,
+    content: Column<'a, Message, Theme, Renderer>,
+) -> Modal<'a, Message, Theme, Renderer> {
+    let title_ctn = container(row![text(package).size(24)].align_items(Alignment::Center))
+        .width(Length::Fill)
+        .style(style::Container::Frame)
+        .padding([10, 0, 10, 0])
+        .center_y()
+        .center_x();
+
+    let text_box = scrollable(text(description).width(Length::Fill))
+        .style(style::Scrollable::Description)
+        .height(Length::Fill);
+
+    let modal_btn_row = row![
+        horizontal_space(),
+        button(text("Close")).on_press(Message::ModalHide)
+    ]
+    .padding([10, 0, 0, 0]);
+
+    let ctn = container(column![title_ctn, text_box, modal_btn_row].spacing(10))
+        .height(600)
+        .width(900)
+        .padding(10)
+        .style(style::Container::Frame);
+
+    Modal::new(content, ctn).on_blur(Message::ModalHide)
+}
+
+fn clear_data_view<'a>(
+    package: &'a str,
+    user: User,
+    content: Column<'a, Message, Theme, Renderer>,
+) -> Modal<'a, Message, Theme, Renderer> {
+    let title_ctn = container(row![text("Clear app data").size(24)].align_items(Alignment::Center))
+        .width(Length::Fill)
+        .style(style::Container::Frame)
+        .padding([10, 0, 10, 0])
+        .center_y()
+        .center_x();
+
+    let text_box = column![
+        text(format!(
+            "All the data of {package} for {user} (settings, accounts, files, cache) will be wiped."
+        )),
+        text(
+            "This does NOT uninstall or disable the app: it stays installed, as if freshly set up."
+        )
+        .style(style::Text::Commentary),
+        text("The data can't be recovered.").style(style::Text::Danger),
+    ]
+    .spacing(10)
+    .width(Length::Fill);
+
+    let modal_btn_row = row![
+        button(text("Cancel")).on_press(Message::ModalHide),
+        horizontal_space(),
+        button(text("Clear data"))
+            .style(style::Button::UninstallPackage)
+            .on_press(Message::ClearDataConfirmed),
+    ]
+    .padding([10, 0, 0, 0]);
+
+    let ctn = container(column![title_ctn, text_box, modal_btn_row].spacing(10))
+        .height(Length::Shrink)
+        .width(600)
+        .padding(10)
+        .style(style::Container::Frame);
+
+    Modal::new(content, ctn).on_blur(Message::ModalHide)
+}
+
+/// Root unlocks the removal of packages the device refuses to uninstall, see [`root_uninstall`]
+fn root_badge<'a>(device: &Phone) -> Option<Element<'a, Message, Theme, Renderer>> {
+    (device.has_root == Some(true)).then(|| {
+        tooltip(
+            text("Root").style(style::Text::Ok),
+            "Root access (su) is available: packages the device refuses to uninstall\n\
+            (DELETE_FAILED_USER_RESTRICTED) can still be removed with root, after a confirmation.",
+            tooltip::Position::Bottom,
+        )
+        .style(style::Container::Tooltip)
+        .gap(4)
+        .into()
+    })
+}
+
+/// Every user of the device is protected (e.g. a fully managed work phone):
+/// `adb` can't list nor change packages of any of them.
+fn fully_managed(device: &Phone) -> bool {
+    !device.user_list.is_empty() && device.user_list.iter().all(|u| u.protected)
+}
+
+fn fully_managed_view<'a>() -> Element<'a, Message, Theme, Renderer> {
+    container(
+        column![
+            text("This device is fully managed").size(20).style(style::Text::Danger),
+            text("All its users are protected by a device policy (MDM, work phone), so ADB can't list nor change their packages. Debloating this device via ADB isn't possible: ask its administrator, or factory reset it if you own it.")
+                .style(style::Text::Commentary)
+                .horizontal_alignment(alignment::Horizontal::Center),
+        ]
+        .spacing(6)
+        .align_items(Alignment::Center),
+    )
+    .padding(10)
+    .center_x()
+    .style(style::Container::BorderedFrame)
+    .into()
+}
+
 fn waiting_view<'a>(
     displayed_text: &str,
     btn: Option<button::Button<'a, Message, Theme, Renderer>>,
@@ -938,46 +3024,334 @@ fn waiting_view<'a>(
         .into()
 }
 
+/// Every package state change is registered in `batch`,
+/// so that the tasks can be cancelled before they start.
 fn build_action_pkg_commands(
     packages: &[Vec<PackageRow>],
     device: &Phone,
     settings: &DeviceSettings,
     selection: (usize, usize),
+    batch: &mut BatchOperation,
 ) -> Vec<Command<Message>> {
+    selection_items(packages, device, settings, selection)
+        .into_iter()
+        .map(|item| pkg_state_command(packages, device, item, batch))
+        .collect()
+}
+
+/// The state change `selection` makes on each user it applies to, see [`UserScope`]
+fn selection_items(
+    packages: &[Vec<PackageRow>],
+    device: &Phone,
+    settings: &DeviceSettings,
+    selection: (usize, usize),
+) -> Vec<BatchItem> {
     let pkg = &packages[selection.0][selection.1];
-    let wanted_state = pkg.state.opposite(settings.disable_mode);
-
-    let mut commands = vec![];
-    for u in device.user_list.iter().filter(|&&u| {
-        !u.protected && (packages[u.index][selection.1].selected || settings.multi_user_mode)
-    }) {
-        let u_pkg = &packages[u.index][selection.1];
-        let wanted_state = if settings.multi_user_mode {
-            wanted_state
-        } else {
-            u_pkg.state.opposite(settings.disable_mode)
-        };
+    let disable_mode = pkg.action_override.disable_mode(settings.disable_mode);
+    let wanted_state = pkg.state.opposite(disable_mode);
 
-        let actions = apply_pkg_state_commands(&u_pkg.into(), wanted_state, u, device);
-        for (j, action) in actions.into_iter().enumerate() {
-            let p_info = PackageInfo {
-                i_user: u.index,
+    let all_users = settings.user_scope() == UserScope::AllUsers;
+    device
+        .user_list
+        .iter()
+        .filter(|&&u| !u.protected && (u.index == selection.0 || all_users))
+        .map(|&user| {
+            let wanted_state = if all_users {
+                wanted_state
+            } else {
+                packages[user.index][selection.1]
+                    .state
+                    .opposite(disable_mode)
+            };
+            BatchItem {
+                user,
                 index: selection.1,
-                removal: pkg.removal.to_string(),
+                wanted_state,
+            }
+        })
+        .collect()
+}
+
+/// `(package, reason)` for the `items` for which [`apply_pkg_state_commands`]
+/// builds no command.
+fn noop_items(
+    packages: &[Vec<PackageRow>],
+    device: &Phone,
+    items: &[BatchItem],
+) -> Vec<(String, String)> {
+    items
+        .iter()
+        .filter_map(|item| {
+            let p = packages.get(item.user.index)?.get(item.index)?;
+            match apply_pkg_state_commands(&p.into(), item.wanted_state, &item.user, device) {
+                Ok(commands) if commands.is_empty() => Some((
+                    p.name.clone(),
+                    if item.wanted_state == p.state {
+                        format!("already in the target state for {}", item.user)
+                    } else {
+                        format!(
+                            "can't be {} on Android SDK {}",
+                            item.wanted_state.to_string().to_lowercase(),
+                            device.android_sdk
+                        )
+                    },
+                )),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Bring a package to `item.wanted_state` for `item.user`, counted in `batch`.
+/// The first adb command changes the package state and decides the outcome.
+/// The following ones (e.g. `pm clear`) only run once it succeeded, and their failures
+/// are only reported in the activity log.
+/// A failed `pm uninstall` of a split package is retried with [`split_uninstall_commands`].
+fn pkg_state_command(
+    packages: &[Vec<PackageRow>],
+    device: &Phone,
+    item: BatchItem,
+    batch: &mut BatchOperation,
+) -> Command<Message> {
+    let pkg = &packages[item.user.index][item.index];
+    let actions = match apply_pkg_state_commands(&pkg.into(), item.wanted_state, &item.user, device)
+    {
+        Ok(actions) if actions.is_empty() => {
+            warn!(
+                "No command brings {} to {:?} on SDK {}, skipped",
+                pkg.name, item.wanted_state, device.android_sdk
+            );
+            return Command::none();
+        }
+        Ok(actions) => actions,
+        Err(e) => {
+            error!("{e}");
+            batch.total += 1;
+            let run = BatchRun {
+                commands: String::new(),
+                res: Err(AdbError::Other(e)),
+                fallback: None,
+                follow_up_failures: vec![],
+                batch: Arc::clone(&batch.cancel),
             };
-            // In the end there is only one package state change
-            // even if we run multiple adb commands
-            commands.push(Command::perform(
-                perform_adb_commands(action, CommandType::PackageManager(p_info)),
-                if j == 0 {
-                    Message::ChangePackageState
-                } else {
-                    |_| Message::Nothing
-                },
-            ));
+            return Command::perform(async move { run }, move |run| {
+                Message::ChangePackageState(item, run)
+            });
         }
+    };
+    let mut follow_ups = actions;
+    let action = follow_ups.remove(0);
+    let p_info = PackageInfo {
+        i_user: item.user.index,
+        index: item.index,
+        removal: pkg.removal.to_string(),
+    };
+    let name = pkg.name.clone();
+    let user = (device.android_sdk >= 21).then_some(item.user);
+    batch.total += 1;
+    let cancel = Arc::clone(&batch.cancel);
+    let batch_id = Arc::clone(&batch.cancel);
+    let slots = Arc::clone(&batch.slots);
+    Command::perform(
+        async move {
+            let _slot = BatchSlots::acquire(&slots).await;
+            // Checked once a slot is free: the batch may have been cancelled meanwhile
+            if cancel.load(Ordering::Relaxed) {
+                return None;
+            }
+            let command_type = || CommandType::PackageManager(p_info.clone());
+            let mut ran = vec![action.clone()];
+            let mut res = perform_adb_commands(action, command_type()).await;
+            let mut fallback = None;
+            // `pm uninstall` of split packages fails on some devices
+            if res.is_err() && item.wanted_state == PackageState::Uninstalled {
+                let retry = split_uninstall_commands(&name, user.as_ref());
+                if !retry.is_empty() {
+                    warn!("Uninstalling the split APKs of {name} one by one");
+                    let mut retry_res = Ok(command_type());
+                    for retry_action in retry {
+                        ran.push(retry_action.clone());
+                        retry_res = perform_adb_commands(retry_action, command_type()).await;
+                        if retry_res.is_err() {
+                            break;
+                        }
+                    }
+                    if retry_res.is_ok() {
+                        res = retry_res;
+                        fallback = Some("split APKs uninstalled one by one".to_string());
+                    }
+                }
+            }
+            let mut follow_up_failures = vec![];
+            if res.is_ok() {
+                for follow_up in follow_ups {
+                    ran.push(follow_up.clone());
+                    if let Err(e) = perform_adb_commands(follow_up.clone(), command_type()).await {
+                        follow_up_failures.push((follow_up, e.to_string()));
+                    }
+                }
+            }
+            Some(BatchRun {
+                commands: ran.join("; "),
+                res,
+                fallback,
+                follow_up_failures,
+                batch: cancel,
+            })
+        },
+        move |run| {
+            run.map_or(Message::OperationSkipped(batch_id), |run| {
+                Message::ChangePackageState(item, run)
+            })
+        },
+    )
+}
+
+fn logcat_view(panel: &LogcatPanel) -> Element<'_, Message, Theme, Renderer> {
+    let status = panel.pid.map_or_else(
+        || format!("{} isn't running", panel.package),
+        |pid| format!("Logcat of {} (PID {pid})", panel.package),
+    );
+    let lines = panel
+        .lines
+        .iter()
+        .fold(column![], |col, line| col.push(text(line).size(12)));
+    column![
+        row![
+            text(status).style(style::Text::Commentary),
+            horizontal_space(),
+            button(text("Stop").size(12))
+                .padding([2, 6])
+                .on_press(Message::LogcatStopped)
+                .style(style::Button::Primary),
+        ]
+        .align_items(Alignment::Center),
+        scrollable(lines)
+            .id(scrollable::Id::new("logcat"))
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .style(style::Scrollable::Description),
+    ]
+    .spacing(4)
+    .into()
+}
+
+fn components_view<'a>(
+    package: &str,
+    components: &'a [PackageComponent],
+) -> Element<'a, Message, Theme, Renderer> {
+    if components.is_empty() {
+        return text(format!("No component of {package} found"))
+            .style(style::Text::Commentary)
+            .into();
+    }
+    components
+        .iter()
+        .fold(column![].spacing(2), |col, component| {
+            let (state, action) = if component.enabled {
+                (text(&component.name).size(12), "Disable")
+            } else {
+                (
+                    text(&component.name).size(12).style(style::Text::Danger),
+                    "Enable",
+                )
+            };
+            col.push(
+                row![
+                    state.width(Length::Fill),
+                    button(text(action).size(12))
+                        .padding([2, 6])
+                        .on_press(Message::ComponentToggled(
+                            component.name.clone(),
+                            !component.enabled
+                        ))
+                        .style(style::Button::Primary),
+                ]
+                .spacing(6)
+                .align_items(Alignment::Center),
+            )
+        })
+        .into()
+}
+
+/// What the selection does on each user, when actions apply to all users
+fn cross_user_view<'a>(impacts: &[UserImpact]) -> Element<'a, Message, Theme, Renderer> {
+    impacts
+        .iter()
+        .fold(column![].spacing(2).padding([0, 10]), |col, i| {
+            col.push(text(format!(
+                "{}: {} package(s) will change, {} already in that state",
+                i.user, i.changed, i.unchanged
+            )))
+            .push_maybe((i.absent > 0).then(|| {
+                text(format!(
+                    "{}: {} package(s) aren't on this user, their change will fail",
+                    i.user, i.absent
+                ))
+                .style(style::Text::Danger)
+            }))
+        })
+        .width(Length::Fill)
+        .into()
+}
+
+/// `(package, reason)` list under `title`, if there is any
+fn warnings_view<'a>(
+    title: &'a str,
+    warnings: &[(String, String)],
+) -> Option<Element<'a, Message, Theme, Renderer>> {
+    if warnings.is_empty() {
+        return None;
+    }
+    Some(
+        warnings
+            .iter()
+            .fold(
+                column![text(title).style(style::Text::Danger)]
+                    .spacing(2)
+                    .padding([0, 10]),
+                |col, (name, warning)| {
+                    col.push(
+                        text(format!("{name}: {warning}"))
+                            .size(14)
+                            .style(style::Text::Danger),
+                    )
+                },
+            )
+            .into(),
+    )
+}
+
+/// Estimated space freed by the uninstalls of the selection, if there are any
+fn reclaimed_view<'a>(summaries: &[SummaryEntry]) -> Option<Element<'a, Message, Theme, Renderer>> {
+    let (reclaimed, unmeasured) = summaries
+        .iter()
+        .fold((0, 0), |(r, u), s| (r + s.reclaimed, u + s.unmeasured));
+    if reclaimed == 0 && unmeasured == 0 {
+        return None;
     }
-    commands
+    let estimate = if unmeasured > 0 {
+        format!(
+            "Estimated space reclaimed: {}, measuring {unmeasured} more package(s)...",
+            format_size(reclaimed)
+        )
+    } else {
+        format!("Estimated space reclaimed: {}", format_size(reclaimed))
+    };
+    Some(
+        column![
+            text(estimate),
+            text(
+                "Estimate from the APKs installed in /data (app updates, user apps). \
+                System APKs stay on their read-only partition and app data isn't counted."
+            )
+            .size(12)
+            .style(style::Text::Commentary),
+        ]
+        .spacing(2)
+        .padding([0, 10])
+        .into(),
+    )
 }
 
 fn recap<'a>(settings: &Settings, recap: &SummaryEntry) -> Element<'a, Message, Theme, Renderer> {
@@ -986,10 +3360,11 @@ fn recap<'a>(settings: &Settings, recap: &SummaryEntry) -> Element<'a, Message,
             text(recap.category).size(19).width(Length::FillPortion(1)),
             vertical_rule(5),
             row![
+                // The symbols don't rely on colors only
                 if settings.device.disable_mode {
-                    text("Disable").style(style::Text::Danger)
+                    text("\u{2717} Disable").style(style::Text::Danger)
                 } else {
-                    text("Uninstall").style(style::Text::Danger)
+                    text("\u{2717} Uninstall").style(style::Text::Danger)
                 },
                 horizontal_space(),
                 text(recap.discard).style(style::Text::Danger)
@@ -998,9 +3373,9 @@ fn recap<'a>(settings: &Settings, recap: &SummaryEntry) -> Element<'a, Message,
             vertical_rule(5),
             row![
                 if settings.device.disable_mode {
-                    text("Enable").style(style::Text::Ok)
+                    text("\u{2713} Enable").style(style::Text::Ok)
                 } else {
-                    text("Restore").style(style::Text::Ok)
+                    text("\u{2713} Restore").style(style::Text::Ok)
                 },
                 horizontal_space(),
                 text(recap.restore).style(style::Text::Ok)
@@ -1018,3 +3393,82 @@ fn recap<'a>(settings: &Settings, recap: &SummaryEntry) -> Element<'a, Message,
     .style(style::Container::Frame)
     .into()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_noop_items() {
+        let row = |state| {
+            PackageRow::new(
+                "com.foo",
+                state,
+                "",
+                UadList::Oem,
+                Removal::Recommended,
+                false,
+                false,
+            )
+        };
+        let packages = vec![
+            vec![row(PackageState::Enabled)],
+            vec![row(PackageState::Uninstalled)],
+        ];
+        let device = Phone {
+            android_sdk: 30,
+            user_list: vec![
+                User {
+                    id: 0,
+                    index: 0,
+                    protected: false,
+                },
+                User {
+                    id: 10,
+                    index: 1,
+                    protected: false,
+                },
+            ],
+            ..Phone::default()
+        };
+        let mut settings = DeviceSettings {
+            disable_mode: false,
+            user_scope_choice: Some(UserScope::AllUsers),
+            ..DeviceSettings::default()
+        };
+
+        let items = selection_items(&packages, &device, &settings, (0, 0));
+        assert_eq!(items.len(), 2);
+        assert!(items
+            .iter()
+            .all(|item| item.wanted_state == PackageState::Uninstalled));
+        assert_eq!(
+            noop_items(&packages, &device, &items),
+            vec![(
+                "com.foo".to_string(),
+                "already in the target state for user 10".to_string()
+            )]
+        );
+
+        // Each user gets the opposite of its own state
+        settings.user_scope_choice = Some(UserScope::SelectedUser);
+        let own_state_items = selection_items(&packages, &device, &settings, (1, 0));
+        assert_eq!(own_state_items.len(), 1);
+        assert_eq!(own_state_items[0].wanted_state, PackageState::Enabled);
+        assert!(noop_items(&packages, &device, &own_state_items).is_empty());
+
+        // No command restores an uninstalled package before KitKat
+        let old_device = Phone {
+            android_sdk: 18,
+            ..device
+        };
+        let old_device_items = selection_items(&packages, &old_device, &settings, (1, 0));
+        assert_eq!(
+            noop_items(&packages, &old_device, &old_device_items),
+            vec![(
+                "com.foo".to_string(),
+                "can't be enabled on Android SDK 18".to_string()
+            )]
+        );
+    }
+}
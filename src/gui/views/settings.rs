@@ -1,14 +1,23 @@
+use crate::core::compare::{compare_devices, PackageDiff};
 use crate::core::helpers::button_primary;
+use crate::core::i18n::t;
 use crate::core::sync::AdbError;
 
-use crate::core::config::{BackupSettings, Config, DeviceSettings, GeneralSettings};
+use crate::core::config::{
+    BackupSettings, Config, DeviceSettings, GeneralSettings, ListFilters, UserScope,
+};
 use crate::core::save::{
     backup_phone, list_available_backup_user, list_available_backups, restore_backup,
 };
-use crate::core::sync::{get_android_sdk, perform_adb_commands, CommandType, Phone, User};
-use crate::core::theme::Theme;
+use crate::core::sync::{
+    adb_console_command, adb_path_from_launch, apply_profile, get_android_sdk, get_device_info,
+    install_apks, list_restorable_packages, perform_adb_commands, restore_all_packages,
+    set_adb_path, ApplyOptions, CommandType, CorePackage, DeviceInfo, Phone, RebootTarget, User,
+};
+use crate::core::theme::{export_theme, Theme, CUSTOM_THEME_FILE};
+use crate::core::uad_lists::{is_protected, PackageState, PROTECTED_PACKAGES};
 use crate::core::utils::{
-    export_packages, open_folder, open_url, string_to_theme, DisplayablePath,
+    export_packages, format_size, open_folder, open_url, string_to_theme, DisplayablePath,
     UNINSTALLED_PACKAGES_FILE_NAME,
 };
 use crate::gui::style;
@@ -18,9 +27,10 @@ use crate::gui::widgets::navigation_menu::ICONS;
 use crate::gui::widgets::package_row::PackageRow;
 
 use iced::widget::{
-    button, checkbox, column, container, pick_list, radio, row, scrollable, text, Space, Text,
+    button, checkbox, column, container, pick_list, radio, row, scrollable, text, text_input,
+    Column, Space, Text,
 };
-use iced::{alignment, Alignment, Command, Element, Length, Renderer};
+use iced::{alignment, Alignment, Command, Element, Font, Length, Renderer};
 use std::path::PathBuf;
 
 use crate::core::utils::{Error, NAME};
@@ -28,6 +38,16 @@ use crate::core::utils::{Error, NAME};
 #[derive(Debug, Clone)]
 pub enum PopUpModal {
     ExportUninstalled,
+    /// Confirmation before re-enabling these packages
+    RestoreAll(Vec<CorePackage>),
+    /// Outcome of each re-enabled package
+    RestoreAllDone(Vec<(String, Result<(), String>)>),
+    /// Differences with another device, before making it match the selected one
+    Compare(Phone, Vec<PackageDiff>),
+    /// Outcome of each package changed on the other device
+    CompareDone(Vec<(String, Result<(), String>)>),
+    /// Confirmation before rebooting into recovery or the bootloader
+    Reboot(RebootTarget),
 }
 
 #[derive(Debug, Clone)]
@@ -36,6 +56,37 @@ pub struct Settings {
     pub device: DeviceSettings,
     is_loading: bool,
     modal: Option<PopUpModal>,
+    adb_console: AdbConsole,
+    theme_export_state: String,
+    /// Outcome of the last "Install APK"
+    apk_install_state: String,
+    /// Fetched each time the settings are opened, the battery level changes
+    device_info: Option<DeviceInfo>,
+}
+
+/// Delay of the re-check of disabled packages, in seconds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct RecheckDelay(u64);
+
+impl RecheckDelay {
+    const ALL: [Self; 4] = [Self(0), Self(5), Self(15), Self(30)];
+}
+
+impl std::fmt::Display for RecheckDelay {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            0 => write!(f, "Off"),
+            s => write!(f, "After {s} s"),
+        }
+    }
+}
+
+/// Expert-only panel running one-off `adb shell` commands on the selected device
+#[derive(Default, Debug, Clone)]
+struct AdbConsole {
+    input: String,
+    output: String,
+    is_running: bool,
 }
 
 impl Default for Settings {
@@ -45,6 +96,10 @@ impl Default for Settings {
             device: DeviceSettings::default(),
             is_loading: false,
             modal: None,
+            adb_console: AdbConsole::default(),
+            theme_export_state: String::default(),
+            apk_install_state: String::default(),
+            device_info: None,
         }
     }
 }
@@ -53,9 +108,24 @@ impl Default for Settings {
 pub enum Message {
     LoadDeviceSettings,
     ExpertMode(bool),
+    ClearSearchHistory,
+    ShowAbsentPackages(bool),
+    ShowProtectedUsers(bool),
+    DefaultDisableMode(bool),
+    OfflineMode(bool),
+    LogLevelSelected(log::LevelFilter),
+    DisableRecheckDelay(u64),
+    AdbConcurrencySelected(usize),
+    AllowProtectedPackages(bool),
+    AdbPathChanged(String),
+    AdbPathSubmitted,
+    ListSourceChanged(String),
     DisableMode(bool),
     MultiUserMode(bool),
+    UserScopeSelected(UserScope),
+    AutoBackupBeforeApply(bool),
     ApplyTheme(Theme),
+    ExportTheme,
     UrlPressed(PathBuf),
     BackupSelected(DisplayablePath),
     BackupDevice,
@@ -66,7 +136,26 @@ pub enum Message {
     FolderChosen(Result<PathBuf, Error>),
     ExportPackages,
     PackagesExported(Result<bool, String>),
+    FetchDeviceInfo,
+    /// `(serial, info)`
+    DeviceInfoFetched(String, DeviceInfo),
+    InstallApkPressed,
+    ApkInstalled(Result<String, AdbError>),
     ModalHide,
+    AdbConsoleInputChanged(String),
+    AdbConsoleRun,
+    AdbConsoleOutput(Result<String, String>),
+    RestoreAllPressed,
+    RestoreAllListed(Vec<CorePackage>),
+    RestoreAllConfirmed,
+    RestoreAllDone(Vec<(String, Result<(), String>)>),
+    CompareDeviceSelected(Phone),
+    CompareLoaded(Phone, Vec<PackageDiff>),
+    MatchDeviceConfirmed,
+    MatchDeviceDone(Vec<(String, Result<(), String>)>),
+    RebootPressed(RebootTarget),
+    /// Handled by the app, which forgets the device while it reboots
+    RebootConfirmed(RebootTarget),
 }
 
 impl Settings {
@@ -81,7 +170,8 @@ impl Settings {
         selected_user: Option<User>,
     ) -> Command<Message> {
         match msg {
-            Message::ModalHide => {
+            // The app reboots the device on `RebootConfirmed`
+            Message::ModalHide | Message::RebootConfirmed(_) => {
                 self.modal = None;
                 Command::none()
             }
@@ -91,6 +181,102 @@ impl Settings {
                 Config::save_changes(self, &phone.adb_id);
                 Command::none()
             }
+            Message::AdbConsoleInputChanged(input) => {
+                self.adb_console.input = input;
+                Command::none()
+            }
+            Message::AdbConsoleRun => {
+                let command = self.adb_console.input.trim().to_string();
+                if command.is_empty() || self.adb_console.is_running || !self.general.expert_mode {
+                    return Command::none();
+                }
+                self.adb_console.is_running = true;
+                Command::perform(
+                    async move { adb_console_command(&command) },
+                    Message::AdbConsoleOutput,
+                )
+            }
+            Message::AdbConsoleOutput(output) => {
+                self.adb_console.is_running = false;
+                self.adb_console.output = match output {
+                    Ok(stdout) => stdout,
+                    Err(stderr) => format!("[ERROR] {stderr}"),
+                };
+                Command::none()
+            }
+            Message::ShowAbsentPackages(toggled) => {
+                self.general.show_absent_packages = toggled;
+                debug!("Config change: {:?}", self);
+                Config::save_changes(self, &phone.adb_id);
+                Command::none()
+            }
+            Message::ShowProtectedUsers(toggled) => {
+                self.general.show_protected_users = toggled;
+                debug!("Config change: {:?}", self);
+                Config::save_changes(self, &phone.adb_id);
+                Command::none()
+            }
+            Message::ClearSearchHistory => {
+                self.general.search_history.clear();
+                debug!("Config change: {:?}", self);
+                Config::save_changes(self, &phone.adb_id);
+                Command::none()
+            }
+            Message::AllowProtectedPackages(toggled) => {
+                self.general.allow_protected_packages = toggled;
+                debug!("Config change: {:?}", self);
+                Config::save_changes(self, &phone.adb_id);
+                Command::none()
+            }
+            Message::AdbPathChanged(path) => {
+                self.general.adb_path = path;
+                Command::none()
+            }
+            Message::AdbPathSubmitted => {
+                if adb_path_from_launch() {
+                    return Command::none();
+                }
+                set_adb_path(&self.general.adb_path, false);
+                debug!("Config change: {:?}", self);
+                Config::save_changes(self, &phone.adb_id);
+                Command::none()
+            }
+            Message::ListSourceChanged(source) => {
+                self.general.list_source = source;
+                debug!("Config change: {:?}", self);
+                Config::save_changes(self, &phone.adb_id);
+                Command::none()
+            }
+            Message::AdbConcurrencySelected(concurrency) => {
+                self.general.adb_concurrency = concurrency;
+                debug!("Config change: {:?}", self);
+                Config::save_changes(self, &phone.adb_id);
+                Command::none()
+            }
+            Message::DisableRecheckDelay(delay) => {
+                self.general.disable_recheck_delay = delay;
+                debug!("Config change: {:?}", self);
+                Config::save_changes(self, &phone.adb_id);
+                Command::none()
+            }
+            Message::LogLevelSelected(level) => {
+                self.general.log_level = level.to_string().to_lowercase();
+                debug!("Config change: {:?}", self);
+                Config::save_changes(self, &phone.adb_id);
+                Command::none()
+            }
+            Message::OfflineMode(toggled) => {
+                self.general.offline_mode = toggled;
+                debug!("Config change: {:?}", self);
+                Config::save_changes(self, &phone.adb_id);
+                Command::none()
+            }
+            Message::DefaultDisableMode(toggled) => {
+                self.general.default_disable_mode = toggled;
+                debug!("Config change: {:?}", self);
+                Config::save_changes(self, &phone.adb_id);
+                Command::none()
+            }
             Message::DisableMode(toggled) => {
                 if phone.android_sdk >= 23 {
                     self.device.disable_mode = toggled;
@@ -105,17 +291,43 @@ impl Settings {
                 Config::save_changes(self, &phone.adb_id);
                 Command::none()
             }
+            Message::UserScopeSelected(scope) => {
+                self.device.user_scope_choice = Some(scope);
+                debug!("Config change: {:?}", self);
+                Config::save_changes(self, &phone.adb_id);
+                Command::none()
+            }
+            Message::AutoBackupBeforeApply(toggled) => {
+                self.device.auto_backup_before_apply = toggled;
+                debug!("Config change: {:?}", self);
+                Config::save_changes(self, &phone.adb_id);
+                Command::none()
+            }
             Message::ApplyTheme(theme) => {
                 self.general.theme = theme.to_string();
                 debug!("Config change: {:?}", self);
                 Config::save_changes(self, &phone.adb_id);
                 Command::none()
             }
+            Message::ExportTheme => {
+                self.theme_export_state = match export_theme(string_to_theme(&self.general.theme)) {
+                    Ok(path) => format!(
+                        "Exported to {} (rename it to `{CUSTOM_THEME_FILE}` to use it)",
+                        path.display()
+                    ),
+                    Err(err) => {
+                        error!("Failed to export the theme: {err}");
+                        format!("Export failed: {err}")
+                    }
+                };
+                Command::none()
+            }
             Message::UrlPressed(url) => {
                 open_url(url);
                 Command::none()
             }
             Message::LoadDeviceSettings => {
+                self.device_info = None;
                 let backups =
                     list_available_backups(&self.general.backup_folder.join(&phone.adb_id));
                 let backup = BackupSettings {
@@ -138,7 +350,14 @@ impl Settings {
                         self.device = DeviceSettings {
                             device_id: phone.adb_id.clone(),
                             multi_user_mode: phone.android_sdk > 21,
-                            disable_mode: false,
+                            user_scope_choice: None,
+                            // `pm disable-user` requires Android 6.0+
+                            disable_mode: self.general.default_disable_mode
+                                && phone.android_sdk >= 23,
+                            auto_backup_before_apply: false,
+                            filters: ListFilters::default(),
+                            last_debloated: None,
+                            kept_packages: vec![],
                             backup,
                         }
                     }
@@ -147,15 +366,18 @@ impl Settings {
             }
             Message::BackupSelected(d_path) => {
                 self.device.backup.selected = Some(d_path.clone());
-                self.device.backup.users = list_available_backup_user(d_path);
+                match list_available_backup_user(d_path) {
+                    Ok(users) => self.device.backup.users = users,
+                    Err(e) => {
+                        error!("[BACKUP] {e}");
+                        self.device.backup.users = vec![];
+                        self.device.backup.backup_state = e;
+                    }
+                }
                 Command::none()
             }
             Message::BackupDevice => Command::perform(
-                backup_phone(
-                    phone.user_list.clone(),
-                    self.device.device_id.clone(),
-                    packages.to_vec(),
-                ),
+                backup_phone(phone.clone(), packages.to_vec(), false),
                 Message::DeviceBackedUp,
             ),
             Message::DeviceBackedUp(is_backed_up) => {
@@ -174,34 +396,49 @@ impl Settings {
                 Command::none()
             }
             Message::RestoreDevice => match restore_backup(phone, packages, &self.device) {
-                Ok(r_packages) => {
-                    let mut commands = vec![];
-                    *nb_running_async_adb_commands = 0;
-                    for p in &r_packages {
+                Ok(plan) => {
+                    let r_packages = plan.packages;
+                    let warning = plan.warning;
+                    // One task per package: its commands run in order,
+                    // and only its overall result is reported
+                    *nb_running_async_adb_commands =
+                        r_packages.len().try_into().unwrap_or(u32::MAX);
+                    let commands = r_packages.iter().map(|p| {
                         let p_info = PackageInfo {
-                            i_user: 0,
+                            i_user: p.i_user,
                             index: p.index,
                             removal: "RESTORE".to_string(),
                         };
-                        for command in p.commands.clone() {
-                            *nb_running_async_adb_commands += 1;
-                            commands.push(Command::perform(
-                                perform_adb_commands(
-                                    command,
-                                    CommandType::PackageManager(p_info.clone()),
-                                ),
-                                Message::RestoringDevice,
-                            ));
-                        }
-                    }
-                    if r_packages.is_empty() {
-                        if get_android_sdk() == 0 {
-                            self.device.backup.backup_state = "Device is not connected".to_string();
+                        let p_commands = p.commands.clone();
+                        Command::perform(
+                            async move {
+                                let mut res = Ok(CommandType::PackageManager(p_info.clone()));
+                                for command in p_commands {
+                                    res = perform_adb_commands(
+                                        command,
+                                        CommandType::PackageManager(p_info.clone()),
+                                    )
+                                    .await;
+                                    if res.is_err() {
+                                        break;
+                                    }
+                                }
+                                res
+                            },
+                            Message::RestoringDevice,
+                        )
+                    });
+                    self.device.backup.backup_state = if r_packages.is_empty() {
+                        let status = if get_android_sdk() == 0 {
+                            "Device is not connected"
                         } else {
-                            self.device.backup.backup_state =
-                                "Device state is already restored".to_string();
-                        }
-                    }
+                            "Device state is already restored"
+                        };
+                        // The device mismatch may be why nothing is restored
+                        warning.map_or_else(|| status.to_string(), |w| format!("{status}\n{w}"))
+                    } else {
+                        warning.unwrap_or_default()
+                    };
                     info!(
                         "[RESTORE] Restoring backup {}",
                         self.device.backup.selected.as_ref().unwrap()
@@ -216,6 +453,83 @@ impl Settings {
             },
             // Trigger an action in mod.rs (Message::SettingsAction(msg))
             Message::RestoringDevice(_) => Command::none(),
+            Message::RestoreAllPressed => {
+                let user = (phone.android_sdk >= 21).then(|| user_zero(phone));
+                Command::perform(
+                    async move { list_restorable_packages(user.as_ref()) },
+                    Message::RestoreAllListed,
+                )
+            }
+            Message::RestoreAllListed(restorable) => {
+                self.modal = Some(PopUpModal::RestoreAll(restorable));
+                Command::none()
+            }
+            Message::RestoreAllConfirmed => match self.modal.take() {
+                Some(PopUpModal::RestoreAll(restorable)) => {
+                    info!("[RESTORE ALL] Re-enabling {} package(s)", restorable.len());
+                    Command::perform(
+                        restore_all_packages(restorable, user_zero(phone), phone.clone()),
+                        Message::RestoreAllDone,
+                    )
+                }
+                _ => Command::none(),
+            },
+            Message::RestoreAllDone(results) => {
+                self.device
+                    .record_debloat(results.iter().filter(|(_, r)| r.is_ok()).count());
+                debug!("Config change: {:?}", self);
+                Config::save_changes(self, &phone.adb_id);
+                self.modal = Some(PopUpModal::RestoreAllDone(results));
+                Command::none()
+            }
+            Message::RebootPressed(target) => {
+                self.modal = Some(PopUpModal::Reboot(target));
+                Command::none()
+            }
+            Message::CompareDeviceSelected(target) => Command::perform(
+                compare_devices(phone.clone(), target.clone()),
+                move |diffs| Message::CompareLoaded(target.clone(), diffs),
+            ),
+            Message::CompareLoaded(target, diffs) => {
+                self.modal = Some(PopUpModal::Compare(target, diffs));
+                Command::none()
+            }
+            Message::MatchDeviceConfirmed => match self.modal.take() {
+                Some(PopUpModal::Compare(target, diffs)) => {
+                    // Packages missing on either device or protected are skipped,
+                    // as shown in the modal
+                    let can_match = self.can_match();
+                    let profile: Vec<_> = diffs
+                        .into_iter()
+                        .filter(|d| can_match(d))
+                        .filter_map(|d| d.source.map(|state| (d.name, state)))
+                        .collect();
+                    info!(
+                        "[COMPARE] Matching {} package(s) of {} on {}",
+                        profile.len(),
+                        phone.model,
+                        target.model
+                    );
+                    let opts = ApplyOptions {
+                        user: user_zero(&target),
+                        verify: true,
+                    };
+                    Command::perform(
+                        async move {
+                            apply_profile(&target, &profile, opts)
+                                .into_iter()
+                                .map(|o| (o.package, o.result))
+                                .collect()
+                        },
+                        Message::MatchDeviceDone,
+                    )
+                }
+                _ => Command::none(),
+            },
+            Message::MatchDeviceDone(results) => {
+                self.modal = Some(PopUpModal::CompareDone(results));
+                Command::none()
+            }
             Message::FolderChosen(result) => {
                 self.is_loading = false;
 
@@ -247,6 +561,47 @@ impl Settings {
                 export_packages(selected_user.unwrap_or_default(), packages.to_vec()),
                 Message::PackagesExported,
             ),
+            Message::InstallApkPressed => {
+                let user = (phone.android_sdk >= 21).then_some(selected_user).flatten();
+                Command::perform(
+                    async move {
+                        let dir = open_folder()
+                            .await
+                            .map_err(|_| AdbError::Other("no folder selected".to_string()))?;
+                        install_apks(&dir, user.as_ref())
+                    },
+                    Message::ApkInstalled,
+                )
+            }
+            Message::FetchDeviceInfo => {
+                if phone.adb_id.is_empty() || phone.unauthorized {
+                    return Command::none();
+                }
+                let serial = phone.adb_id.clone();
+                Command::perform(get_device_info(), move |info| {
+                    Message::DeviceInfoFetched(serial.clone(), info)
+                })
+            }
+            Message::DeviceInfoFetched(serial, info) => {
+                // The device may have been switched in the meantime
+                if serial == phone.adb_id {
+                    self.device_info = Some(info);
+                }
+                Command::none()
+            }
+            Message::ApkInstalled(res) => {
+                self.apk_install_state = match res {
+                    Ok(_) => "APK installed, refresh the package list to see it".to_string(),
+                    Err(e) => {
+                        error!("{e}");
+                        e.hint().map_or_else(
+                            || format!("APK not installed: {e}"),
+                            |hint| format!("APK not installed: {hint}"),
+                        )
+                    }
+                };
+                Command::none()
+            }
             Message::PackagesExported(exported) => {
                 match exported {
                     Ok(_) => self.modal = Some(PopUpModal::ExportUninstalled),
@@ -259,21 +614,34 @@ impl Settings {
 
     // TODO: refactor later
     #[allow(clippy::too_many_lines)]
-    pub fn view(&self, phone: &Phone, apps_view: &AppsView) -> Element<Message, Theme, Renderer> {
-        let radio_btn_theme = Theme::ALL
-            .iter()
-            .fold(row![].spacing(10), |column, option| {
-                column.push(
-                    radio(
-                        format!("{}", option.clone()),
-                        *option,
-                        Some(string_to_theme(&self.general.theme)),
-                        Message::ApplyTheme,
+    /// `devices` are the connected devices, the selected one can be compared with the others
+    pub fn view(
+        &self,
+        phone: &Phone,
+        devices: &[Phone],
+        apps_view: &AppsView,
+    ) -> Element<Message, Theme, Renderer> {
+        let radio_btn_theme =
+            Theme::available()
+                .into_iter()
+                .fold(row![].spacing(10), |column, option| {
+                    column.push(
+                        radio(
+                            format!("{option}"),
+                            option,
+                            Some(string_to_theme(&self.general.theme)),
+                            Message::ApplyTheme,
+                        )
+                        .size(24),
                     )
-                    .size(24),
-                )
-            });
-        let theme_ctn = container(radio_btn_theme)
+                });
+        let export_theme_row = row![
+            button_primary("Export current theme").on_press(Message::ExportTheme),
+            text(&self.theme_export_state).style(style::Text::Commentary),
+        ]
+        .spacing(10)
+        .align_items(Alignment::Center);
+        let theme_ctn = container(column![radio_btn_theme, export_theme_row].spacing(10))
             .padding(10)
             .width(Length::Fill)
             .height(Length::Shrink)
@@ -308,13 +676,154 @@ impl Settings {
         .spacing(10)
         .align_items(Alignment::Center);
 
+        let clear_search_history_btn = if self.general.search_history.is_empty() {
+            button_primary("Clear search history")
+        } else {
+            button_primary("Clear search history").on_press(Message::ClearSearchHistory)
+        };
+
+        let search_history_row = row![
+            clear_search_history_btn,
+            text(format!(
+                "{} recent search term(s) saved",
+                self.general.search_history.len()
+            ))
+            .style(style::Text::Commentary),
+        ]
+        .spacing(10)
+        .align_items(Alignment::Center);
+
+        let recheck_row = row![
+            "Re-check disabled packages",
+            pick_list(
+                RecheckDelay::ALL,
+                Some(RecheckDelay(self.general.disable_recheck_delay)),
+                |d| Message::DisableRecheckDelay(d.0),
+            )
+            .padding(6),
+            text("Warns when a disabled package is re-enabled by Google Play Services")
+                .style(style::Text::Commentary),
+        ]
+        .spacing(10)
+        .align_items(Alignment::Center);
+
+        let adb_concurrency_row = self.general.expert_mode.then(|| {
+            row![
+                "Simultaneous ADB commands",
+                pick_list(
+                    [1, 2, 4, 8, 16],
+                    Some(self.general.adb_concurrency),
+                    Message::AdbConcurrencySelected,
+                )
+                .padding(6),
+                text("Lower it if changes fail at random on large selections or slow devices")
+                    .style(style::Text::Commentary),
+            ]
+            .spacing(10)
+            .align_items(Alignment::Center)
+        });
+
+        let log_level_row = row![
+            "Log level",
+            pick_list(
+                log::LevelFilter::iter().collect::<Vec<_>>(),
+                self.general.log_level.parse::<log::LevelFilter>().ok(),
+                Message::LogLevelSelected,
+            )
+            .padding(6),
+            text("Applied on next launch. `--log-level` and `RUST_LOG` take precedence.")
+                .style(style::Text::Commentary),
+        ]
+        .spacing(10)
+        .align_items(Alignment::Center);
+
+        // Without `on_input` the path can't be edited
+        let from_launch = adb_path_from_launch();
+        let adb_path_input = text_input("adb from the PATH", &self.general.adb_path)
+            .on_submit(Message::AdbPathSubmitted)
+            .padding(6);
+        let adb_path_row = row![
+            "ADB path",
+            if from_launch {
+                adb_path_input
+            } else {
+                adb_path_input.on_input(Message::AdbPathChanged)
+            },
+            text(if from_launch {
+                "Set by `--adb-path` or `ADB_PATH` for this session"
+            } else {
+                "Press Enter to apply"
+            })
+            .style(style::Text::Commentary),
+        ]
+        .spacing(10)
+        .align_items(Alignment::Center);
+
+        let list_source_row = row![
+            "Debloat lists",
+            text_input("Official lists", &self.general.list_source)
+                .on_input(Message::ListSourceChanged)
+                .padding(6),
+            text("URL or path of a JSON file, used on the next list update")
+                .style(style::Text::Commentary),
+        ]
+        .spacing(10)
+        .align_items(Alignment::Center);
+
+        let show_absent_checkbox = checkbox(
+            "Show list entries that aren't on the device",
+            self.general.show_absent_packages,
+        )
+        .on_toggle(Message::ShowAbsentPackages)
+        .style(style::CheckBox::SettingsEnabled);
+
+        let show_absent_descr = text(
+            "They are listed as \"Uninstalled\" for reference only: they can't be selected or acted upon",
+        )
+        .style(style::Text::Commentary);
+
+        let show_protected_users_checkbox = checkbox(
+            "Show users ADB can't access (work profile, Secure Folder) in the user list",
+            self.general.show_protected_users,
+        )
+        .on_toggle(Message::ShowProtectedUsers)
+        .style(style::CheckBox::SettingsEnabled);
+
+        let default_disable_mode_checkbox = checkbox(
+            "Start new devices in disable mode (Android 6.0+), rather than uninstalling",
+            self.general.default_disable_mode,
+        )
+        .on_toggle(Message::DefaultDisableMode)
+        .style(style::CheckBox::SettingsEnabled);
+
+        let offline_mode_checkbox = checkbox("Offline mode", self.general.offline_mode)
+            .on_toggle(Message::OfflineMode)
+            .style(style::CheckBox::SettingsEnabled);
+
+        let offline_mode_descr = text(
+            "Never access the network: the cached package lists are used and updates aren't checked",
+        )
+        .style(style::Text::Commentary);
+
         let general_ctn = container(
             column![
                 expert_mode_checkbox,
                 expert_mode_descr,
+                offline_mode_checkbox,
+                offline_mode_descr,
+                show_absent_checkbox,
+                show_absent_descr,
+                show_protected_users_checkbox,
+                default_disable_mode_checkbox,
                 choose_backup_row,
                 choose_backup_descr,
+                search_history_row,
+                recheck_row,
+                log_level_row,
+                adb_path_row,
+                list_source_row,
             ]
+            .push_maybe(adb_concurrency_row)
             .spacing(10),
         )
         .padding(10)
@@ -328,6 +837,11 @@ impl Settings {
                     .style(style::Text::Danger),
                 text(phone.model.clone()),
                 Space::new(Length::Fill, Length::Shrink),
+                text(self.device.last_debloated.map_or_else(
+                    || "Never debloated".to_string(),
+                    |record| format!("Last debloated: {record}")
+                ))
+                .style(style::Text::Commentary),
                 text(phone.adb_id.clone()).style(style::Text::Commentary)
             ]
             .spacing(7),
@@ -352,12 +866,24 @@ impl Settings {
         ];
 
         let multi_user_mode_checkbox = checkbox(
-            "Affect all the users of the device (not only the selected user)",
+            "Select packages for all the users of the device at once",
             self.device.multi_user_mode,
         )
         .on_toggle(Message::MultiUserMode)
         .style(style::CheckBox::SettingsEnabled);
 
+        let user_scope_row = row![
+            "Apply actions to",
+            pick_list(
+                UserScope::ALL,
+                Some(self.device.user_scope()),
+                Message::UserScopeSelected,
+            )
+            .padding(6),
+        ]
+        .spacing(10)
+        .align_items(Alignment::Center);
+
         let disable_checkbox_style = if phone.android_sdk >= 23 {
             style::CheckBox::SettingsEnabled
         } else {
@@ -400,13 +926,44 @@ impl Settings {
             .width(Length::Fill)
         };
 
+        let auto_backup_checkbox = checkbox(
+            "Back up the device before applying a selection",
+            self.device.auto_backup_before_apply,
+        )
+        .on_toggle(Message::AutoBackupBeforeApply)
+        .style(style::CheckBox::SettingsEnabled);
+
+        let auto_backup_descr = text(
+            "Nothing is changed if the backup fails. These backups are suffixed with `_pre-apply`",
+        )
+        .style(style::Text::Commentary);
+
+        // To recover from a debloat gone wrong without a terminal
+        let reboot_row = self.general.expert_mode.then(|| {
+            row![
+                button_primary("Reboot to recovery")
+                    .on_press(Message::RebootPressed(RebootTarget::Recovery)),
+                button_primary("Reboot to bootloader")
+                    .on_press(Message::RebootPressed(RebootTarget::Bootloader)),
+                text("For recovery workflows, e.g. a bootloop after a debloat")
+                    .style(style::Text::Danger),
+            ]
+            .spacing(10)
+            .align_items(Alignment::Center)
+        });
+
         let device_specific_ctn = container(
             column![
-                multi_user_mode_checkbox,
+                device_info_row(self.device_info.as_ref()),
+                user_scope_row,
                 multi_user_mode_descr,
+                multi_user_mode_checkbox,
                 disable_setting_row,
                 disable_mode_descr,
+                auto_backup_checkbox,
+                auto_backup_descr,
             ]
+            .push_maybe(reboot_row)
             .spacing(10),
         )
         .padding(10)
@@ -453,6 +1010,32 @@ impl Settings {
 
         let export_btn = button_primary("Export").on_press(Message::ExportPackages);
 
+        let restore_all_row = row![
+            button(text("Restore everything"))
+                .padding([5, 10])
+                .on_press(Message::RestoreAllPressed)
+                .style(style::Button::UninstallPackage),
+            "Re-enable every disabled or uninstalled system package of user 0, without a backup",
+        ]
+        .spacing(10)
+        .align_items(Alignment::Center);
+
+        let other_devices: Vec<Phone> = devices
+            .iter()
+            .filter(|d| d.adb_id != phone.adb_id && !d.unauthorized)
+            .cloned()
+            .collect();
+        let compare_row = (!other_devices.is_empty()).then(|| {
+            row![
+                pick_list(other_devices, None::<Phone>, Message::CompareDeviceSelected)
+                    .placeholder("Compare with...")
+                    .padding([5, 10]),
+                "See which packages differ on another device, and make it match this one",
+            ]
+            .spacing(10)
+            .align_items(Alignment::Center)
+        });
+
         let backup_row = row![
             backup_btn,
             "Backup the current state of the phone",
@@ -485,13 +1068,13 @@ impl Settings {
 
         let content = if phone.adb_id.is_empty() {
             column![
-                text("Theme").size(26),
+                text(t!("settings.theme")).size(26),
                 theme_ctn,
-                text("General").size(26),
+                text(t!("settings.general")).size(26),
                 general_ctn,
-                text("Current device").size(26),
+                text(t!("settings.current_device")).size(26),
                 no_device_ctn(),
-                text("Backup / Restore").size(26),
+                text(t!("settings.backup_restore")).size(26),
                 no_device_ctn(),
             ]
             .width(Length::Fill)
@@ -509,28 +1092,61 @@ impl Settings {
             .spacing(10)
             .align_items(Alignment::Center);
 
-            let backup_restore_ctn =
-                container(column![backup_row, restore_row, export_row].spacing(10))
-                    .padding(10)
-                    .width(Length::Fill)
-                    .height(Length::Shrink)
-                    .style(style::Container::Frame);
+            let install_apk_row = row![
+                button_primary("Install APK").on_press(Message::InstallApkPressed),
+                "Sideload an app back from a folder saved with \"Save APK\"",
+                Space::new(Length::Fill, Length::Shrink),
+                text(&self.apk_install_state).style(style::Text::Commentary),
+            ]
+            .spacing(10)
+            .align_items(Alignment::Center);
 
-            column![
-                text("Theme").size(26),
+            let backup_restore_ctn = container(
+                column![
+                    backup_row,
+                    restore_row,
+                    restore_all_row,
+                    export_row,
+                    install_apk_row
+                ]
+                .push_maybe(compare_row)
+                .spacing(10),
+            )
+            .padding(10)
+            .width(Length::Fill)
+            .height(Length::Shrink)
+            .style(style::Container::Frame);
+
+            let content = column![
+                text(t!("settings.theme")).size(26),
                 theme_ctn,
-                text("General").size(26),
+                text(t!("settings.general")).size(26),
                 general_ctn,
-                text("Current device").size(26),
+                text(t!("settings.current_device")).size(26),
                 warning_ctn,
                 device_specific_ctn,
-                text("Backup / Restore").size(26),
+                text(t!("settings.backup_restore")).size(26),
                 backup_restore_ctn,
             ]
             .width(Length::Fill)
-            .spacing(20)
+            .spacing(20);
+
+            if self.general.expert_mode {
+                content
+                    .push(text("Advanced").size(26))
+                    .push(self.protected_packages_view())
+                    .push(self.adb_console_view())
+            } else {
+                content
+            }
         };
 
+        if let Some(ctn) = self.action_modal() {
+            return Modal::new(content.padding(10), ctn)
+                .on_blur(Message::ModalHide)
+                .into();
+        }
+
         if let Some(PopUpModal::ExportUninstalled) = self.modal {
             let title = container(row![text("Success").size(24)].align_items(Alignment::Center))
                 .width(Length::Fill)
@@ -576,4 +1192,254 @@ impl Settings {
             .height(Length::Fill)
             .into()
     }
+
+    /// Whether "Make it match" changes a diff on the other device: it must be on both
+    /// devices, and not be protected unless protected packages are allowed, as in the list
+    fn can_match(&self) -> impl Fn(&PackageDiff) -> bool + '_ {
+        let protected: Vec<String> = PROTECTED_PACKAGES
+            .iter()
+            .map(ToString::to_string)
+            .chain(self.general.protected_packages.iter().cloned())
+            .collect();
+        move |diff| {
+            diff.is_actionable()
+                && (self.general.allow_protected_packages || !is_protected(&diff.name, &protected))
+        }
+    }
+
+    /// Confirmation and outcome of "Restore everything" and of matching another device
+    fn action_modal(&self) -> Option<Element<'_, Message, Theme, Renderer>> {
+        let (title, body, confirm) = match self.modal.as_ref()? {
+            PopUpModal::RestoreAll(packages) if packages.is_empty() => (
+                "Nothing to restore",
+                column![text("No system package is disabled or uninstalled for user 0.")],
+                None,
+            ),
+            PopUpModal::RestoreAll(packages) => (
+                "Restore everything?",
+                column![
+                    text(format!(
+                        "{} disabled or uninstalled system package(s) will be re-enabled for user 0.",
+                        packages.len()
+                    )),
+                    text("This also restores packages removed on purpose, and can't be undone from a backup made afterwards.")
+                        .style(style::Text::Commentary),
+                ]
+                .spacing(10),
+                Some(("Restore".to_string(), Message::RestoreAllConfirmed)),
+            ),
+            PopUpModal::RestoreAllDone(results) => ("Restore finished", outcomes_view(results), None),
+            PopUpModal::Reboot(target) => (
+                "Reboot the device?",
+                column![
+                    text(format!("The device will reboot into {target}, and be disconnected meanwhile.")),
+                    text("Only do this if you know how to get out of it: use the volume and power keys to navigate, and choose to reboot the system to come back.")
+                        .style(style::Text::Danger),
+                ]
+                .spacing(10),
+                Some((format!("Reboot into {target}"), Message::RebootConfirmed(*target))),
+            ),
+            PopUpModal::Compare(target, diffs) if diffs.is_empty() => (
+                "No difference",
+                column![text(format!(
+                    "The system packages of {target} are in the same state as on this device."
+                ))],
+                None,
+            ),
+            PopUpModal::Compare(target, diffs) => {
+                let can_match = self.can_match();
+                let actionable = diffs.iter().filter(|d| can_match(d)).count();
+                let state = |s: Option<PackageState>| s.map_or("missing".to_string(), |s| s.to_string());
+                let rows = diffs.iter().fold(Column::new().spacing(4), |col, d| {
+                    let line = text(format!(
+                        "{}: {} here, {} on the other device",
+                        d.name,
+                        state(d.source),
+                        state(d.target)
+                    ));
+                    col.push(if can_match(d) {
+                        line
+                    } else {
+                        line.style(style::Text::Commentary)
+                    })
+                });
+                (
+                    "Device comparison",
+                    column![
+                        text(format!(
+                            "{} package(s) differ on {target}. {actionable} will be changed to match this device, \
+                            the {} missing on either device or protected are skipped.",
+                            diffs.len(),
+                            diffs.len() - actionable
+                        )),
+                        scrollable(rows).height(Length::Shrink),
+                    ]
+                    .spacing(10),
+                    (actionable > 0)
+                        .then(|| ("Make it match".to_string(), Message::MatchDeviceConfirmed)),
+                )
+            }
+            PopUpModal::CompareDone(results) => ("Matching finished", outcomes_view(results), None),
+            PopUpModal::ExportUninstalled => return None,
+        };
+
+        let title = container(row![text(title).size(24)].align_items(Alignment::Center))
+            .width(Length::Fill)
+            .style(style::Container::Frame)
+            .padding([10, 0, 10, 0])
+            .center_y()
+            .center_x();
+
+        let modal_btn_row = row![
+            Space::new(Length::Fill, Length::Shrink),
+            button(text(if confirm.is_some() { "Cancel" } else { "Close" }))
+                .on_press(Message::ModalHide),
+        ]
+        .push_maybe(confirm.map(|(label, msg)| {
+            button(text(label))
+                .on_press(msg)
+                .style(style::Button::UninstallPackage)
+        }))
+        .spacing(10);
+
+        Some(
+            container(column![title, body.padding(20), modal_btn_row])
+                .height(Length::Shrink)
+                .max_height(500)
+                .width(600)
+                .padding(10)
+                .style(style::Container::Frame)
+                .into(),
+        )
+    }
+
+    fn protected_packages_view(&self) -> Element<'_, Message, Theme, Renderer> {
+        let allow_checkbox = checkbox(
+            "Allow to remove protected packages (SystemUI, default launcher and keyboard, ...)",
+            self.general.allow_protected_packages,
+        )
+        .on_toggle(Message::AllowProtectedPackages)
+        .style(style::CheckBox::SettingsEnabled);
+
+        let descr = text(format!(
+            "More packages can be protected with `protected_packages` in the config file ({} currently).",
+            self.general.protected_packages.len()
+        ))
+        .style(style::Text::Commentary);
+
+        container(column![allow_checkbox, descr].spacing(10))
+            .padding(10)
+            .width(Length::Fill)
+            .style(style::Container::Frame)
+            .into()
+    }
+
+    fn adb_console_view(&self) -> Element<'_, Message, Theme, Renderer> {
+        let console_input = text_input("pm list packages -d", &self.adb_console.input)
+            .on_input(Message::AdbConsoleInputChanged)
+            .on_submit(Message::AdbConsoleRun)
+            .font(Font::MONOSPACE)
+            .padding([5, 10]);
+
+        let run_btn = if self.adb_console.is_running {
+            button_primary("Running...")
+        } else {
+            button_primary("Run").on_press(Message::AdbConsoleRun)
+        };
+
+        let console_output = container(
+            scrollable(
+                text(&self.adb_console.output)
+                    .font(Font::MONOSPACE)
+                    .width(Length::Fill),
+            )
+            .style(style::Scrollable::Description),
+        )
+        .padding(6)
+        .max_height(250)
+        .width(Length::Fill)
+        .style(style::Container::BorderedFrame);
+
+        container(
+            column![
+                text("ADB console").size(20),
+                text("Commands are run with `adb shell` on the selected device and logged. There is no safety net here.")
+                    .style(style::Text::Danger),
+                row![text("adb shell").font(Font::MONOSPACE), console_input, run_btn]
+                    .spacing(10)
+                    .align_items(Alignment::Center),
+                console_output,
+            ]
+            .spacing(10),
+        )
+        .padding(10)
+        .width(Length::Fill)
+        .height(Length::Shrink)
+        .style(style::Container::Frame)
+        .into()
+    }
+}
+
+/// Number of successful outcomes, and the errors of the others
+fn outcomes_view<'a>(
+    results: &[(String, Result<(), String>)],
+) -> Column<'a, Message, Theme, Renderer> {
+    let failed: Vec<_> = results
+        .iter()
+        .filter_map(|(name, r)| r.as_ref().err().map(|e| (name, e)))
+        .collect();
+    let failures = failed
+        .iter()
+        .fold(Column::new().spacing(4), |col, (name, e)| {
+            col.push(text(format!("✗ {name}: {e}")).style(style::Text::Danger))
+        });
+    column![
+        text(format!(
+            "{} succeeded, {} failed",
+            results.len() - failed.len(),
+            failed.len()
+        )),
+        scrollable(failures).height(Length::Shrink),
+    ]
+    .spacing(10)
+}
+
+/// The main user, that "Restore everything" acts on
+fn user_zero(phone: &Phone) -> User {
+    phone
+        .user_list
+        .iter()
+        .find(|u| u.id == 0)
+        .copied()
+        .unwrap_or_default()
+}
+
+/// "Storage: 21.9 GB free of 110 GB · Battery: 85% · Security patch: 2024-05-01"
+fn device_info_row(info: Option<&DeviceInfo>) -> Element<'_, Message, Theme, Renderer> {
+    let Some(info) = info else {
+        return text("Reading device info...")
+            .style(style::Text::Commentary)
+            .into();
+    };
+    let facts = [
+        info.storage.map(|(total, free)| {
+            format!(
+                "Storage: {} free of {}",
+                format_size(free),
+                format_size(total)
+            )
+        }),
+        info.battery.map(|level| format!("Battery: {level}%")),
+        (!info.security_patch.is_empty())
+            .then(|| format!("Security patch: {}", info.security_patch)),
+    ];
+    text(
+        facts
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(" \u{b7} "),
+    )
+    .style(style::Text::Commentary)
+    .into()
 }
@@ -1,4 +1,5 @@
 use crate::core::helpers::button_primary;
+use crate::core::i18n::t;
 pub use crate::core::sync::Phone;
 use crate::core::theme::Theme;
 use crate::core::update::{SelfUpdateState, SelfUpdateStatus};
@@ -28,17 +29,26 @@ pub fn nav_menu<'a>(
     )
     .on_press(Message::RefreshButtonPressed);
 
-    let apps_refresh_tooltip = tooltip(apps_refresh_btn, "Refresh apps", tooltip::Position::Bottom)
-        .style(style::Container::Tooltip)
-        .gap(4);
+    let apps_refresh_tooltip = tooltip(
+        apps_refresh_btn,
+        t!("nav.refresh"),
+        tooltip::Position::Bottom,
+    )
+    .style(style::Container::Tooltip)
+    .gap(4);
 
-    let reboot_btn = button_primary("Reboot").on_press(Message::RebootButtonPressed);
+    let reboot_btn = button_primary(t!("nav.reboot")).on_press(Message::RebootButtonPressed);
 
     #[allow(clippy::option_if_let_else)]
     let uad_version_text = if let Some(r) = &self_update_state.latest_release {
         match self_update_state.status {
-            SelfUpdateStatus::Failed => Text::new(format!("Failed to update to {}", r.tag_name)),
-            SelfUpdateStatus::Checking => Text::new(SelfUpdateStatus::Checking.to_string()),
+            SelfUpdateStatus::Failed => match &self_update_state.error {
+                Some(e) => Text::new(format!("Failed to update to {}: {e}", r.tag_name)),
+                None => Text::new(format!("Failed to update to {}", r.tag_name)),
+            },
+            SelfUpdateStatus::Checking | SelfUpdateStatus::Offline => {
+                Text::new(self_update_state.status.to_string())
+            }
             SelfUpdateStatus::Done => Text::new(format!(
                 "Update available: {} -> {}",
                 env!("CARGO_PKG_VERSION"),
@@ -46,12 +56,14 @@ pub fn nav_menu<'a>(
             )),
             SelfUpdateStatus::Updating => Text::new("Updating please wait..."),
         }
+    } else if self_update_state.status == SelfUpdateStatus::Offline {
+        Text::new(format!("v{} (offline)", env!("CARGO_PKG_VERSION")))
     } else {
         Text::new(format!("v{}", env!("CARGO_PKG_VERSION")))
     };
 
     let update_btn = if self_update_state.latest_release.is_some() {
-        button("Update")
+        button(t!("nav.update"))
             .on_press(Message::AboutAction(AboutMessage::DoSelfUpdate))
             .padding([5, 10])
             .style(style::Button::SelfUpdate)
@@ -59,9 +71,9 @@ pub fn nav_menu<'a>(
         button("").height(0).width(0).style(style::Button::Hidden)
     };
 
-    let apps_btn = button_primary("Apps").on_press(Message::AppsPress);
+    let apps_btn = button_primary(t!("nav.apps")).on_press(Message::AppsPress);
 
-    let about_btn = button_primary("About").on_press(Message::AboutPressed);
+    let about_btn = button_primary(t!("nav.about")).on_press(Message::AboutPressed);
 
     let settings_btn = button_primary(
         Text::new("\u{E994}")
@@ -72,8 +84,8 @@ pub fn nav_menu<'a>(
     .on_press(Message::SettingsPressed);
 
     let device_list_text = match apps_view.loading_state {
-        ListLoadingState::FindingPhones => text("Finding connected devices..."),
-        _ => text("No devices/emulators found"),
+        ListLoadingState::FindingPhones => text(t!("nav.finding_devices")),
+        _ => text(t!("nav.no_devices")),
     };
 
     let row = match selected_device {
@@ -1,21 +1,86 @@
-use crate::core::sync::Phone;
+use crate::core::sync::{is_stub_apk, Phone, User};
 use crate::core::theme::Theme;
 use crate::core::uad_lists::{PackageState, Removal, UadList};
 use crate::gui::style;
 use crate::gui::views::settings::Settings;
 
-use iced::widget::{button, checkbox, row, text, Space};
+use iced::widget::{button, checkbox, pick_list, row, text, tooltip, Space};
 use iced::{alignment, Alignment, Command, Element, Length, Renderer};
 
 #[derive(Clone, Debug)]
+#[allow(clippy::struct_excessive_bools, reason = "Not a state-machine")]
 pub struct PackageRow {
     pub name: String,
     pub state: PackageState,
+    /// State when the package list was loaded, to tell what was changed since
+    pub previous_state: PackageState,
     pub description: String,
     pub uad_list: UadList,
     pub removal: Removal,
     pub selected: bool,
     pub current: bool,
+    /// APK files reported by `pm path`, fetched lazily when the row is opened
+    pub apk_paths: Option<Vec<String>>,
+    /// Unlisted package flagged as likely removable by [`heuristic_vendor`]
+    ///
+    /// [`heuristic_vendor`]: crate::core::uad_lists::heuristic_vendor
+    pub heuristic: bool,
+    /// Useful for packages that bootloop when uninstalled but are fine disabled
+    pub action_override: ActionOverride,
+    /// `false` for list entries shown for reference, which can't be acted upon
+    pub on_device: bool,
+    /// Critical package (e.g. `SystemUI`, the default launcher) that mustn't be removed
+    pub protected: bool,
+    /// In `bookmarked_packages`, shared by all devices
+    pub bookmarked: bool,
+    /// `pm suspend` state, fetched lazily when the row is opened (Android 7.0+)
+    pub suspended: Option<bool>,
+    /// Bytes uninstalling it would free, fetched lazily for the review modal.
+    /// See [`reclaimable_apk_sizes`].
+    ///
+    /// [`reclaimable_apk_sizes`]: crate::core::sync::reclaimable_apk_sizes
+    pub apk_size: Option<u64>,
+    /// The name was just copied to the clipboard
+    pub copied: bool,
+    /// Position in the `pm list packages` output, `usize::MAX` when not on the device
+    pub device_order: usize,
+}
+
+/// Per-package choice between disabling and uninstalling,
+/// taking precedence over the device-wide `disable_mode`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ActionOverride {
+    #[default]
+    Default,
+    Disable,
+    Uninstall,
+}
+
+impl ActionOverride {
+    pub const ALL: [Self; 3] = [Self::Default, Self::Disable, Self::Uninstall];
+
+    /// Resolve the effective disable mode given the device-wide one
+    pub const fn disable_mode(self, device_disable_mode: bool) -> bool {
+        match self {
+            Self::Default => device_disable_mode,
+            Self::Disable => true,
+            Self::Uninstall => false,
+        }
+    }
+}
+
+impl std::fmt::Display for ActionOverride {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Default => "Default",
+                Self::Disable => "Disable instead",
+                Self::Uninstall => "Uninstall anyway",
+            }
+        )
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -23,6 +88,16 @@ pub enum Message {
     PackagePressed,
     ActionPressed,
     ToggleSelection(bool),
+    ActionOverridden(ActionOverride),
+    RestoreForAllUsers,
+    SuspendToggled,
+    /// Wipe the data of the package, after a confirmation
+    ClearData,
+    CopyName,
+    /// Add or remove the package from the keep-list, see [`PackageRow::is_kept`]
+    KeepToggled,
+    /// Star or unstar the package, see [`PackageRow::bookmarked`]
+    BookmarkToggled,
 }
 
 impl PackageRow {
@@ -38,53 +113,213 @@ impl PackageRow {
         Self {
             name: name.to_string(),
             state,
+            previous_state: state,
             description: description.to_string(),
             uad_list,
             removal,
             selected,
             current,
+            apk_paths: None,
+            heuristic: false,
+            action_override: ActionOverride::default(),
+            on_device: true,
+            protected: false,
+            bookmarked: false,
+            suspended: None,
+            apk_size: None,
+            copied: false,
+            device_order: usize::MAX,
         }
     }
 
+    /// Number of APK parts when the package is distributed as split APKs
+    pub fn split_parts(&self) -> Option<usize> {
+        self.apk_paths
+            .as_ref()
+            .map(Vec::len)
+            .filter(|&parts| parts > 1)
+    }
+
+    /// Known once the row has been opened, see [`is_stub_apk`]
+    pub fn is_stub(&self) -> bool {
+        self.apk_paths.as_deref().is_some_and(is_stub_apk)
+    }
+
+    /// Whether removing this package disables it rather than uninstalling it
+    pub const fn disable_mode(&self, settings: &Settings) -> bool {
+        self.action_override
+            .disable_mode(settings.device.disable_mode)
+    }
+
+    /// Protected packages can't be removed unless the protection is lifted in the settings
+    pub fn is_locked(&self, settings: &Settings) -> bool {
+        self.protected
+            && self.state == PackageState::Enabled
+            && !settings.general.allow_protected_packages
+    }
+
+    /// Kept packages are skipped by "Select all", as a safety net for bulk actions
+    pub fn is_kept(&self, settings: &Settings) -> bool {
+        settings.device.kept_packages.contains(&self.name)
+    }
+
     pub fn update(&mut self, _message: &Message) -> Command<Message> {
         Command::none()
     }
 
-    pub fn view(&self, settings: &Settings, _phone: &Phone) -> Element<Message, Theme, Renderer> {
-        //let trash_svg = format!("{}/resources/assets/trash.svg", env!("CARGO_MANIFEST_DIR"));
-        //let restore_svg = format!("{}/resources/assets/rotate.svg", env!("CARGO_MANIFEST_DIR"));
-        let button_style;
-        let action_text;
-        let action_btn;
-        let selection_checkbox;
-
+    /// Label and style of the action button
+    fn action(&self, settings: &Settings) -> (&'static str, style::Button) {
         match self.state {
-            PackageState::Enabled => {
-                action_text = if settings.device.disable_mode {
+            PackageState::Enabled => (
+                if self.disable_mode(settings) {
                     "Disable"
                 } else {
                     "Uninstall"
-                };
-                button_style = style::Button::UninstallPackage;
-            }
-            PackageState::Disabled => {
-                action_text = "Enable";
-                button_style = style::Button::RestorePackage;
-            }
-            PackageState::Uninstalled => {
-                action_text = "Restore";
-                button_style = style::Button::RestorePackage;
-            }
+                },
+                style::Button::UninstallPackage,
+            ),
+            PackageState::Disabled => ("Enable", style::Button::RestorePackage),
+            PackageState::Uninstalled => ("Restore", style::Button::RestorePackage),
             PackageState::All => {
-                action_text = "Error";
-                button_style = style::Button::RestorePackage;
                 warn!("Incredible! Something impossible happened!");
+                ("Error", style::Button::RestorePackage)
             }
         }
+    }
+
+    /// Known once the row has been opened, see [`Self::suspended`]
+    fn suspend_button(&self) -> Option<Element<'_, Message, Theme, Renderer>> {
+        self.suspended
+            .filter(|_| self.on_device && self.state == PackageState::Enabled)
+            .map(|suspended| {
+                button(text(if suspended { "Unsuspend" } else { "Suspend" }).size(12))
+                    .padding([2, 6])
+                    .on_press(Message::SuspendToggled)
+                    .style(style::Button::Primary)
+                    .into()
+            })
+    }
+
+    fn badge(&self) -> Option<&'static str> {
+        if !self.on_device {
+            Some("list only")
+        } else if matches!(self.suspended, Some(true)) {
+            Some("suspended")
+        } else if self.is_stub() {
+            Some("stub app")
+        } else if self.heuristic {
+            Some("likely removable (heuristic)")
+        } else {
+            None
+        }
+    }
+
+    /// Copy the package name, to research it elsewhere
+    fn copy_button(&self) -> Element<'_, Message, Theme, Renderer> {
+        tooltip(
+            button(text(if self.copied { "Copied!" } else { "Copy" }).size(12))
+                .padding([2, 6])
+                .on_press(Message::CopyName)
+                .style(style::Button::Primary),
+            "Copy the package name",
+            tooltip::Position::Top,
+        )
+        .style(style::Container::Tooltip)
+        .gap(4)
+        .into()
+    }
+
+    fn bookmark_button(&self) -> Element<'_, Message, Theme, Renderer> {
+        tooltip(
+            button(
+                text(if self.bookmarked {
+                    "\u{2605}"
+                } else {
+                    "\u{2606}"
+                })
+                .size(12),
+            )
+            .padding([2, 6])
+            .on_press(Message::BookmarkToggled)
+            .style(if self.bookmarked {
+                style::Button::SelectedPackage
+            } else {
+                style::Button::Primary
+            }),
+            if self.bookmarked {
+                "Bookmarked on all devices. Click to remove the bookmark"
+            } else {
+                "Bookmark it, to find it again on any device"
+            },
+            tooltip::Position::Top,
+        )
+        .style(style::Container::Tooltip)
+        .gap(4)
+        .into()
+    }
+
+    fn keep_button(&self, settings: &Settings) -> Element<'_, Message, Theme, Renderer> {
+        let kept = self.is_kept(settings);
+        tooltip(
+            button(text(if kept { "\u{2691} Kept" } else { "Keep" }).size(12))
+                .padding([2, 6])
+                .on_press(Message::KeepToggled)
+                .style(if kept {
+                    style::Button::SelectedPackage
+                } else {
+                    style::Button::Primary
+                }),
+            if kept {
+                "Kept: \"Select all\" skips it. Click to release it"
+            } else {
+                "Keep it out of \"Select all\""
+            },
+            tooltip::Position::Top,
+        )
+        .style(style::Container::Tooltip)
+        .gap(4)
+        .into()
+    }
+
+    /// Only on the opened row, to keep it apart from the removal action
+    fn clear_data_button(
+        &self,
+        settings: &Settings,
+    ) -> Option<Element<'_, Message, Theme, Renderer>> {
+        (self.current
+            && self.on_device
+            && self.state != PackageState::Uninstalled
+            && !self.is_locked(settings))
+        .then(|| {
+            button(text("Clear data").size(12))
+                .padding([2, 6])
+                .on_press(Message::ClearData)
+                .style(style::Button::Primary)
+                .into()
+        })
+    }
+
+    /// `user_states` holds the state of this package for each non-protected user.
+    /// A compact per-user indicator is only rendered when there are several of them.
+    pub fn view(
+        &self,
+        settings: &Settings,
+        phone: &Phone,
+        user_states: &[(User, PackageState)],
+    ) -> Element<Message, Theme, Renderer> {
+        //let trash_svg = format!("{}/resources/assets/trash.svg", env!("CARGO_MANIFEST_DIR"));
+        //let restore_svg = format!("{}/resources/assets/rotate.svg", env!("CARGO_MANIFEST_DIR"));
+        let (action_text, button_style) = self.action(settings);
+        let action_btn;
+        let selection_checkbox;
+
         // Disable any removal action for unsafe packages if expert_mode is disabled
-        if self.removal != Removal::Unsafe
-            || self.state != PackageState::Enabled
-            || settings.general.expert_mode
+        // and for protected packages, and any action at all for packages that aren't on the device
+        if self.on_device
+            && !self.is_locked(settings)
+            && (self.removal != Removal::Unsafe
+                || self.state != PackageState::Enabled
+                || settings.general.expert_mode)
         {
             selection_checkbox = checkbox("", self.selected)
                 .on_toggle(Message::ToggleSelection)
@@ -102,19 +337,77 @@ impl PackageRow {
                 .style(style::CheckBox::PackageDisabled);
 
             action_btn = button(
-                text(action_text)
-                    .horizontal_alignment(alignment::Horizontal::Center)
-                    .width(100),
+                text(if self.on_device {
+                    action_text
+                } else {
+                    "Not on device"
+                })
+                .horizontal_alignment(alignment::Horizontal::Center)
+                .width(100),
             );
         }
 
+        // Disabling requires Android 6.0+ (see `apply_pkg_state_commands`)
+        let override_picker =
+            (self.on_device && self.state == PackageState::Enabled && phone.android_sdk >= 23)
+                .then(|| {
+                    pick_list(
+                        ActionOverride::ALL,
+                        Some(self.action_override),
+                        Message::ActionOverridden,
+                    )
+                    .text_size(12)
+                    .padding([2, 6])
+                });
+
+        let users_indicator = users_indicator(user_states);
+
+        let suspend_btn = self.suspend_button();
+
+        let clear_data_btn = self.clear_data_button(settings);
+
+        // `user_states` is only filled when there are several users
+        let restore_all_btn =
+            (self.on_device && self.state == PackageState::Uninstalled && !user_states.is_empty())
+                .then(|| {
+                    button(text("Restore for all users").size(14))
+                        .on_press(Message::RestoreForAllUsers)
+                        .style(style::Button::RestorePackage)
+                });
+
+        let selection: Element<Message, Theme, Renderer> = if self.is_locked(settings) {
+            tooltip(
+                selection_checkbox,
+                "Protected: removing it would make the device unusable",
+                tooltip::Position::Right,
+            )
+            .style(style::Container::Tooltip)
+            .gap(4)
+            .into()
+        } else {
+            selection_checkbox.into()
+        };
+
         row![
             button(
                 row![
-                    selection_checkbox,
+                    selection,
                     text(&self.name).width(Length::FillPortion(8)),
-                    action_btn.style(button_style)
+                    self.bookmark_button(),
+                    self.copy_button()
                 ]
+                .push_maybe(self.on_device.then(|| self.keep_button(settings)))
+                .push_maybe(
+                    self.badge()
+                        .map(|b| text(b).size(12).style(style::Text::Commentary))
+                )
+                .push_maybe(users_indicator)
+                .push_maybe(override_picker)
+                .push_maybe(suspend_btn)
+                .push_maybe(clear_data_btn)
+                .push_maybe(restore_all_btn)
+                .push(action_btn.style(button_style))
+                .spacing(5)
                 .align_items(Alignment::Center)
             )
             .padding(8)
@@ -131,3 +424,34 @@ impl PackageRow {
         .into()
     }
 }
+
+/// Compact per-user state dots, with the details in a tooltip
+fn users_indicator<'a>(
+    user_states: &[(User, PackageState)],
+) -> Option<Element<'a, Message, Theme, Renderer>> {
+    (user_states.len() > 1).then(|| {
+        let dots =
+            user_states
+                .iter()
+                .fold(row![].spacing(2).padding([0, 10]), |dots, (_, state)| {
+                    // Shapes differ too, so that colors aren't the only cue
+                    let (symbol, style) = match state {
+                        PackageState::Enabled => ("\u{25CF}", style::Text::Ok),
+                        PackageState::Uninstalled => ("\u{2717}", style::Text::Danger),
+                        PackageState::Disabled | PackageState::All => {
+                            ("\u{25CB}", style::Text::Commentary)
+                        }
+                    };
+                    dots.push(text(symbol).size(12).style(style))
+                });
+        let details = user_states
+            .iter()
+            .map(|(user, state)| format!("{user}: {state}"))
+            .collect::<Vec<String>>()
+            .join("\n");
+        tooltip(dots, text(details), tooltip::Position::Left)
+            .style(style::Container::Tooltip)
+            .gap(4)
+            .into()
+    })
+}
@@ -2,15 +2,19 @@
 #[macro_use]
 extern crate log;
 
-use crate::core::utils::setup_uad_dir;
+use crate::core::config::Config;
+use crate::core::sync::{check_adb, connected_devices, get_target_device, set_adb_path, ADB_PATH};
+use crate::core::uad_lists::{get_local_lists, search_lists};
+use crate::core::utils::{collect_bug_report, setup_uad_dir, write_bug_report};
 use fern::{
     colors::{Color, ColoredLevelConfig},
     FormatCallback,
 };
-use log::Record;
+use log::{LevelFilter, Record};
 use static_init::dynamic;
-use std::path::PathBuf;
-use std::{fmt::Arguments, fs::OpenOptions};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use std::{fmt::Arguments, fs, fs::OpenOptions};
 
 mod core;
 mod gui;
@@ -21,9 +25,133 @@ static CONFIG_DIR: PathBuf = setup_uad_dir(&dirs::config_dir().expect("Can't det
 #[dynamic]
 static CACHE_DIR: PathBuf = setup_uad_dir(&dirs::cache_dir().expect("Can't detect cache dir"));
 
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+/// Log files older than this are deleted on startup
+const LOG_RETENTION: Duration = Duration::from_secs(14 * SECONDS_PER_DAY);
+/// The log file of the day is truncated on startup when it grows past this size (10 MiB)
+const LOG_MAX_SIZE: u64 = 10 * 1024 * 1024;
+
 fn main() -> iced::Result {
     setup_logger().expect("setup logging");
-    gui::UadGui::start()
+    if let Some(command) = logs_command(std::env::args().skip(1)) {
+        print_logs(command);
+        return Ok(());
+    }
+    if let Some(term) = arg_value(std::env::args().skip(1), "--search") {
+        print_search(&term);
+        return Ok(());
+    }
+    // In order of precedence: `--adb-path <path>`, `ADB_PATH`, then the `adb_path` setting
+    if let Some(path) = arg_value(std::env::args().skip(1), "--adb-path") {
+        set_adb_path(&path, true);
+    } else if let Some(path) = std::env::var(ADB_PATH).ok().filter(|p| !p.is_empty()) {
+        set_adb_path(&path, true);
+    } else {
+        set_adb_path(&Config::load_configuration_file().general.adb_path, false);
+    }
+    if let Some(dir) = diagnose_dir(std::env::args().skip(1)) {
+        diagnose(&dir);
+        return Ok(());
+    }
+    gui::UadGui::start(arg_value(std::env::args().skip(1), "--device"))
+}
+
+/// Today's log file
+fn log_path() -> PathBuf {
+    CACHE_DIR.join(format!("UAD_{}.log", chrono::Local::now().format("%Y%m%d")))
+}
+
+/// Print the log file instead of launching the GUI, for bug reports
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LogsCommand {
+    /// `--log-path`: path of today's log file
+    Path,
+    /// `--log-tail <N>`: last N lines of today's log file
+    Tail(usize),
+}
+
+fn logs_command(mut args: impl Iterator<Item = String>) -> Option<LogsCommand> {
+    let mut command = None;
+    while let Some(arg) = args.next() {
+        if arg == "--log-path" {
+            command = Some(LogsCommand::Path);
+        } else if arg == "--log-tail" {
+            command = args
+                .next()
+                .and_then(|n| n.parse().ok())
+                .map(LogsCommand::Tail);
+        } else if let Some(n) = arg.strip_prefix("--log-tail=") {
+            command = n.parse().ok().map(LogsCommand::Tail);
+        }
+    }
+    command
+}
+
+fn print_logs(command: LogsCommand) {
+    let path = log_path();
+    match command {
+        LogsCommand::Path => println!("{}", path.display()),
+        LogsCommand::Tail(n) => match fs::read_to_string(&path) {
+            Ok(logs) => {
+                let lines: Vec<&str> = logs.lines().collect();
+                for line in &lines[lines.len().saturating_sub(n)..] {
+                    println!("{line}");
+                }
+            }
+            Err(e) => eprintln!("Can't read {}: {e}", path.display()),
+        },
+    }
+}
+
+/// `--search <term>`: print the packages of the cached lists whose name or description
+/// contains `term`, to research them without a device
+fn print_search(term: &str) {
+    let lists = get_local_lists();
+    let matches = search_lists(&lists, term);
+    if matches.is_empty() {
+        println!("No package matches \"{term}\"");
+    }
+    for (name, package) in matches {
+        println!("{name} [{}]", package.removal);
+        for line in package.description.lines() {
+            println!("    {line}");
+        }
+    }
+}
+
+/// `--diagnose [dir]`: the folder to write the bug report in, the current one by default
+fn diagnose_dir(mut args: impl Iterator<Item = String>) -> Option<PathBuf> {
+    while let Some(arg) = args.next() {
+        if arg == "--diagnose" {
+            let dir = args.next().filter(|a| !a.starts_with("--"));
+            return Some(dir.map_or_else(|| PathBuf::from("."), PathBuf::from));
+        } else if let Some(dir) = arg.strip_prefix("--diagnose=") {
+            return Some(PathBuf::from(dir));
+        }
+    }
+    None
+}
+
+/// Write the bug report of the About view in `dir` instead of launching the GUI,
+/// for the device given with `--device` or else the first connected one
+fn diagnose(dir: &Path) {
+    let devices = connected_devices();
+    let phone = match get_target_device(
+        &devices,
+        arg_value(std::env::args().skip(1), "--device").as_deref(),
+    ) {
+        Ok(phone) => phone.cloned().unwrap_or_default(),
+        Err(e) => {
+            eprintln!("{e}");
+            return;
+        }
+    };
+    let adb_status = iced::futures::executor::block_on(check_adb());
+    let report = collect_bug_report(&phone, adb_status.version(), false);
+    match write_bug_report(dir, &report) {
+        Ok(path) => println!("Bug report written to {}", path.display()),
+        Err(e) => eprintln!("Can't write the bug report in {}: {e}", dir.display()),
+    }
 }
 
 /// Sets up logging to a new file in `CACHE_DIR/UAD`_{time}.log
@@ -57,25 +185,34 @@ fn setup_logger() -> Result<(), fern::InitError> {
         }
     };
 
-    let default_log_level = log::LevelFilter::Warn;
+    let default_log_level = LevelFilter::Warn;
+    let uad_log_level = log_level(
+        std::env::args().skip(1),
+        std::env::var("RUST_LOG").ok(),
+        &Config::load_configuration_file().general.log_level,
+    );
+
+    let log_path = log_path();
+    let oversized = fs::metadata(&log_path).is_ok_and(|m| m.len() > LOG_MAX_SIZE);
     let log_file = OpenOptions::new()
         .create(true)
-        .append(true)
-        .truncate(false)
-        .open(CACHE_DIR.join(format!("UAD_{}.log", chrono::Local::now().format("%Y%m%d"))))?;
+        .write(true)
+        .append(!oversized)
+        .truncate(oversized)
+        .open(log_path)?;
 
     let file_dispatcher = fern::Dispatch::new()
         .format(make_formatter(false))
         .level(default_log_level)
         // Rust compiler makes module names use _ instead of -
-        .level_for("uad_ng", log::LevelFilter::Debug)
+        .level_for("uad_ng", uad_log_level)
         .chain(log_file);
 
     let stdout_dispatcher = fern::Dispatch::new()
         .format(make_formatter(true))
         .level(default_log_level)
         // Rust compiler makes module names use _ instead of -
-        .level_for("uad_ng", log::LevelFilter::Warn)
+        .level_for("uad_ng", uad_log_level.min(LevelFilter::Warn))
         .chain(std::io::stdout());
 
     fern::Dispatch::new()
@@ -83,9 +220,76 @@ fn setup_logger() -> Result<(), fern::InitError> {
         .chain(file_dispatcher)
         .apply()?;
 
+    remove_old_logs(&CACHE_DIR);
     Ok(())
 }
 
+/// Resolve the log level of the app crate.
+///
+/// In order of precedence: the `--log-level <level>` argument,
+/// a plain `RUST_LOG=<level>` (module directives are not supported),
+/// then the `log_level` of the config file. Defaults to `Debug`.
+fn log_level(
+    mut args: impl Iterator<Item = String>,
+    env_level: Option<String>,
+    config_level: &str,
+) -> LevelFilter {
+    let mut arg_level = None;
+    while let Some(arg) = args.next() {
+        if arg == "--log-level" {
+            arg_level = args.next();
+        } else if let Some(level) = arg.strip_prefix("--log-level=") {
+            arg_level = Some(level.to_string());
+        }
+    }
+
+    [arg_level, env_level, Some(config_level.to_string())]
+        .into_iter()
+        .flatten()
+        .find_map(|level| level.parse().ok())
+        .unwrap_or(LevelFilter::Debug)
+}
+
+/// Value of the `<name> <value>` or `<name>=<value>` argument, the last one winning.
+/// Used for `--device <serial>` (device to select on startup), `--adb-path <path>`
+/// and `--search <term>`.
+fn arg_value(mut args: impl Iterator<Item = String>, name: &str) -> Option<String> {
+    let prefix = format!("{name}=");
+    let mut value = None;
+    while let Some(arg) = args.next() {
+        if arg == name {
+            value = args.next();
+        } else if let Some(v) = arg.strip_prefix(&prefix) {
+            value = Some(v.to_string());
+        }
+    }
+    value.filter(|v| !v.is_empty())
+}
+
+/// Delete `UAD_*.log` files of `dir` which haven't been written to for [`LOG_RETENTION`]
+fn remove_old_logs(dir: &Path) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for path in entries.filter_map(Result::ok).map(|e| e.path()) {
+        let is_log = path.extension().is_some_and(|ext| ext == "log")
+            && path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.starts_with("UAD_"));
+        let is_old = fs::metadata(&path)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+            .is_some_and(|age| age > LOG_RETENTION);
+        if is_log && is_old {
+            if let Err(e) = fs::remove_file(&path) {
+                warn!("Can't remove old log file {}: {e}", path.display());
+            }
+        }
+    }
+}
+
 /// (Windows) Allow the application to display logs to the terminal
 /// regardless if it was compiled with `windows_subsystem = "windows"`.
 ///
@@ -108,4 +312,108 @@ mod tests {
             Err(error) => panic!("Error: {error}"),
         }
     }
+
+    #[test]
+    fn resolve_log_level() {
+        let args = |a: &[&str]| a.iter().map(ToString::to_string).collect::<Vec<_>>();
+
+        assert_eq!(
+            log_level(args(&[]).into_iter(), None, ""),
+            LevelFilter::Debug
+        );
+        assert_eq!(
+            log_level(args(&[]).into_iter(), None, "error"),
+            LevelFilter::Error
+        );
+        assert_eq!(
+            log_level(args(&[]).into_iter(), Some("TRACE".into()), "error"),
+            LevelFilter::Trace
+        );
+        assert_eq!(
+            log_level(
+                args(&["--log-level", "warn"]).into_iter(),
+                Some("trace".into()),
+                "error"
+            ),
+            LevelFilter::Warn
+        );
+        assert_eq!(
+            log_level(args(&["--log-level=info"]).into_iter(), None, "error"),
+            LevelFilter::Info
+        );
+        // Invalid values fall through to the next source
+        assert_eq!(
+            log_level(args(&["--log-level=loud"]).into_iter(), None, "off"),
+            LevelFilter::Off
+        );
+    }
+
+    #[test]
+    fn test_arg_value() {
+        let args = |a: &[&str]| a.iter().map(ToString::to_string).collect::<Vec<_>>();
+        assert_eq!(arg_value(args(&[]).into_iter(), "--device"), None);
+        assert_eq!(
+            arg_value(
+                args(&["--log-level", "warn", "--device", "R58M123"]).into_iter(),
+                "--device"
+            ),
+            Some("R58M123".to_string())
+        );
+        assert_eq!(
+            arg_value(args(&["--device=emulator-5554"]).into_iter(), "--device"),
+            Some("emulator-5554".to_string())
+        );
+        assert_eq!(arg_value(args(&["--device"]).into_iter(), "--device"), None);
+        assert_eq!(
+            arg_value(
+                args(&["--device", "R58M123", "--adb-path=/opt/adb"]).into_iter(),
+                "--adb-path"
+            ),
+            Some("/opt/adb".to_string())
+        );
+    }
+
+    #[test]
+    fn test_logs_command() {
+        let args = |a: &[&str]| a.iter().map(ToString::to_string).collect::<Vec<_>>();
+        assert_eq!(logs_command(args(&[]).into_iter()), None);
+        assert_eq!(
+            logs_command(args(&["--log-path"]).into_iter()),
+            Some(LogsCommand::Path)
+        );
+        assert_eq!(
+            logs_command(args(&["--log-tail", "50"]).into_iter()),
+            Some(LogsCommand::Tail(50))
+        );
+        assert_eq!(
+            logs_command(args(&["--log-tail=5"]).into_iter()),
+            Some(LogsCommand::Tail(5))
+        );
+        assert_eq!(
+            logs_command(args(&["--log-tail", "many"]).into_iter()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_diagnose_dir() {
+        let args = |a: &[&str]| a.iter().map(ToString::to_string).collect::<Vec<_>>();
+        assert_eq!(diagnose_dir(args(&[]).into_iter()), None);
+        assert_eq!(
+            diagnose_dir(args(&["--diagnose"]).into_iter()),
+            Some(PathBuf::from("."))
+        );
+        assert_eq!(
+            diagnose_dir(args(&["--diagnose", "--device", "R58M123"]).into_iter()),
+            Some(PathBuf::from("."))
+        );
+        assert_eq!(
+            diagnose_dir(args(&["--diagnose", "/tmp"]).into_iter()),
+            Some(PathBuf::from("/tmp"))
+        );
+        assert_eq!(
+            diagnose_dir(args(&["--diagnose=reports"]).into_iter()),
+            Some(PathBuf::from("reports"))
+        );
+    }
 }